@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::OnceCell;
+
+use crate::client::McpClient;
+use crate::error::RadiologyError;
+use crate::pool::BackendPool;
+use crate::protocol::Tool;
+
+/// Backend the client given to `RadiologyCluster::new` is registered as
+pub const DEFAULT_BACKEND: &str = "default";
+
+/// A named MCP server connection; the members of a `BackendPool`
+pub struct Backend {
+    name: String,
+    client: Arc<McpClient>,
+    // Fetched on first use; a server's tool list doesn't change during a session
+    tools: OnceCell<Vec<Tool>>,
+}
+
+impl Backend {
+    pub fn new(name: &str, client: Arc<McpClient>) -> Self {
+        Backend {
+            name: name.to_string(),
+            client,
+            tools: OnceCell::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn client(&self) -> &Arc<McpClient> {
+        &self.client
+    }
+
+    /// The tools the server offers, listed once and then cached
+    pub async fn tools(&self) -> Result<&[Tool], RadiologyError> {
+        let tools = self.tools.get_or_try_init(|| self.client.list_tools()).await?;
+        Ok(tools)
+    }
+}
+
+/// Named backends a `RadiologyCluster` routes contexts to.
+///
+/// Each name maps to a `BackendPool`; registering a single client makes a pool
+/// of one. Registering a name again replaces its pool; contexts bound to that
+/// name use the new one from their next submission on.
+#[derive(Default)]
+pub struct BackendRegistry {
+    backends: RwLock<HashMap<String, Arc<BackendPool>>>,
+}
+
+impl BackendRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the backend called `name`, returning the pool it replaced
+    pub fn register(&self, name: &str, client: Arc<McpClient>) -> Option<Arc<BackendPool>> {
+        self.register_pool(BackendPool::single(name, client))
+    }
+
+    /// Add or replace a pool under its own name and start its health checks
+    pub fn register_pool(&self, pool: BackendPool) -> Option<Arc<BackendPool>> {
+        let pool = Arc::new(pool);
+        pool.start();
+        self.backends.write().unwrap().insert(pool.name().to_string(), pool)
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Arc<BackendPool>> {
+        self.backends.write().unwrap().remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<BackendPool>, RadiologyError> {
+        self.backends
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| RadiologyError::UnknownBackend(name.to_string()))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.backends.read().unwrap().contains_key(name)
+    }
+
+    /// Names of every registered backend, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.backends.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use mcp_rust_sdk::protocol::{Notification, Request, RequestId, Response};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::{oneshot, watch};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::connect::ReconnectPolicy;
+use crate::error::RadiologyError;
+use crate::protocol::{self, CallToolResult, GetPromptResult, Implementation, InitializeResult, Prompt, Tool};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+
+/// State of the connection behind an `McpClient`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    /// The socket dropped and a supervised client is reconnecting
+    Reconnecting,
+    /// The connection is gone for good
+    Closed,
+}
+
+/// JSON-RPC client for an MCP server reached over WebSocket.
+///
+/// Unlike the SDK client, requests are multiplexed: any number of tasks can
+/// have a request in flight at once, and responses are routed back to their
+/// caller by id regardless of the order the server answers in.
+///
+/// The MCP handshake runs once, on the first call to `initialize`, `list_tools`
+/// or `call_tool`; `request` and `notify` send raw JSON-RPC messages as they are.
+///
+/// A client created with a `ReconnectPolicy` (see `Connector::reconnect`) is
+/// supervised: when the socket drops it reconnects with backoff, re-runs the
+/// handshake and resends in-flight requests that are safe to repeat, namely
+/// the methods in `protocol::SAFE_TO_REPLAY` and calls to tools the server
+/// listed as read-only or idempotent. Other in-flight requests fail, and new
+/// requests wait until the connection is back.
+///
+/// Dropping a request's future before its response arrives, e.g. because a
+/// `tokio::time::timeout` around it elapsed, sends `notifications/cancelled`
+/// so the server can stop working on it.
+pub struct McpClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    url: String,
+    reconnect: Option<ReconnectPolicy>,
+    // `None` while disconnected
+    writer: tokio::sync::Mutex<Option<WsSink>>,
+    pending: Mutex<Pending>,
+    next_id: AtomicI64,
+    state: watch::Sender<ConnectionState>,
+    // Result of the handshake on the current connection
+    session: Mutex<Option<InitializeResult>>,
+    // Serializes handshakes started by callers so only one runs at a time
+    handshake: tokio::sync::Mutex<()>,
+    idempotent_tools: Mutex<HashSet<String>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+// Requests waiting for a response, keyed by JSON-RPC id
+#[derive(Default)]
+struct Pending {
+    closed: bool,
+    requests: HashMap<RequestId, InFlight>,
+}
+
+struct InFlight {
+    // Gets the response, or the protocol error that took its place
+    waiter: oneshot::Sender<Result<Response, RadiologyError>>,
+    frame: String,
+    replay: bool,
+}
+
+impl McpClient {
+    /// Open a WebSocket connection to `url` and start routing responses
+    pub async fn connect(url: &str) -> Result<Self, RadiologyError> {
+        Self::connect_with(url, None).await
+    }
+
+    /// Open a connection, supervised under `reconnect` when one is given
+    pub async fn connect_with(url: &str, reconnect: Option<ReconnectPolicy>) -> Result<Self, RadiologyError> {
+        let (writer, reader) = open(url).await?;
+        let inner = Arc::new(Inner {
+            url: url.to_string(),
+            reconnect,
+            writer: tokio::sync::Mutex::new(Some(writer)),
+            pending: Mutex::new(Pending::default()),
+            next_id: AtomicI64::new(1),
+            state: watch::Sender::new(ConnectionState::Connected),
+            session: Mutex::new(None),
+            handshake: tokio::sync::Mutex::new(()),
+            idempotent_tools: Mutex::new(HashSet::new()),
+            tasks: Mutex::new(Vec::new()),
+        });
+        inner.spawn(read_responses(reader, Arc::downgrade(&inner)));
+        Ok(McpClient { inner })
+    }
+
+    /// Send a request and wait for the matching response's result
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<Value, RadiologyError> {
+        let replay = protocol::SAFE_TO_REPLAY.contains(&method);
+        self.inner.wait_connected().await?;
+        self.inner.request(method, params, replay).await
+    }
+
+    /// Perform the MCP handshake if it hasn't happened yet and return what the server announced.
+    ///
+    /// Offers `LATEST_PROTOCOL_VERSION` and fails with a protocol error when the
+    /// server answers with a version this client doesn't support.
+    pub async fn initialize(&self) -> Result<InitializeResult, RadiologyError> {
+        if let Some(session) = self.inner.session() {
+            return Ok(session);
+        }
+        let _handshake = self.inner.handshake.lock().await;
+        self.inner.wait_connected().await?;
+        // A reconnect, or the caller ahead of us, may have finished the handshake meanwhile
+        if let Some(session) = self.inner.session() {
+            return Ok(session);
+        }
+        self.inner.handshake().await
+    }
+
+    /// Every tool the server offers, following pagination cursors
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, RadiologyError> {
+        self.initialize().await?;
+        self.inner.wait_connected().await?;
+        self.inner.list_tools(true).await
+    }
+
+    /// Invoke a tool with structured arguments.
+    ///
+    /// A result flagged `isError` is returned as `RadiologyError::Rejected`
+    /// carrying the tool's text output.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult, RadiologyError> {
+        self.initialize().await?;
+
+        let replay = self.inner.idempotent_tools.lock().unwrap().contains(name);
+        let params = json!({ "name": name, "arguments": arguments });
+        self.inner.wait_connected().await?;
+        let result = self.inner.request(protocol::TOOLS_CALL, Some(params), replay).await?;
+        let result: CallToolResult = parse(result, "tool result")?;
+        if result.is_error {
+            let text = result.text();
+            return Err(RadiologyError::Rejected {
+                code: None,
+                message: if text.is_empty() { format!("tool '{}' failed", name) } else { text },
+            });
+        }
+        Ok(result)
+    }
+
+    /// Every prompt the server offers, following pagination cursors
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>, RadiologyError> {
+        self.initialize().await?;
+
+        let mut prompts = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.map(|cursor| json!({ "cursor": cursor }));
+            let mut page = self.request(protocol::PROMPTS_LIST, params).await?;
+            let batch: Vec<Prompt> = parse(page["prompts"].take(), "prompt list")?;
+            prompts.extend(batch);
+
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(prompts);
+            }
+        }
+    }
+
+    /// Have the server render a prompt with the given arguments
+    pub async fn get_prompt(&self, name: &str, arguments: &HashMap<String, String>) -> Result<GetPromptResult, RadiologyError> {
+        self.initialize().await?;
+
+        let params = json!({ "name": name, "arguments": arguments });
+        let result = self.request(protocol::PROMPTS_GET, Some(params)).await?;
+        parse(result, "prompt")
+    }
+
+    /// Send a notification; no response is expected
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> Result<(), RadiologyError> {
+        self.inner.wait_connected().await?;
+        self.inner.notify(method, params).await
+    }
+
+    /// Whether the connection to the server is currently open
+    pub fn is_connected(&self) -> bool {
+        self.state() == ConnectionState::Connected
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        *self.inner.state.borrow()
+    }
+
+    /// Watch connection state changes, e.g. to log reconnects
+    pub fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.state.subscribe()
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        for task in self.inner.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+impl Inner {
+    fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) -> AbortHandle {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        let task = tokio::spawn(task);
+        let abort = task.abort_handle();
+        tasks.push(task);
+        abort
+    }
+
+    fn session(&self) -> Option<InitializeResult> {
+        self.session.lock().unwrap().clone()
+    }
+
+    // Wait out a reconnect; fails once the connection is closed for good
+    async fn wait_connected(&self) -> Result<(), RadiologyError> {
+        let mut state = self.state.subscribe();
+        let state = state
+            .wait_for(|state| *state != ConnectionState::Reconnecting)
+            .await
+            .map_err(|_| connection_closed())?;
+        match *state {
+            ConnectionState::Closed => Err(connection_closed()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn request(self: &Arc<Self>, method: &str, params: Option<Value>, replay: bool) -> Result<Value, RadiologyError> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let frame = serde_json::to_string(&Request::new(method, params, id.clone()))?;
+        let (tx, rx) = oneshot::channel();
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(connection_closed());
+            }
+            let in_flight = InFlight {
+                waiter: tx,
+                frame: frame.clone(),
+                replay,
+            };
+            pending.requests.insert(id.clone(), in_flight);
+        }
+        // The handshake must never be cancelled
+        let mut cancel_on_drop = Cancellation {
+            inner: self,
+            id: id.clone(),
+            armed: method != protocol::INITIALIZE,
+        };
+
+        // A replayable request whose send fails is resent once the connection is back
+        if let Err(e) = self.send(frame).await {
+            if !replay || self.reconnect.is_none() {
+                cancel_on_drop.armed = false;
+                self.pending.lock().unwrap().requests.remove(&id);
+                return Err(e);
+            }
+        }
+
+        let response = rx.await;
+        cancel_on_drop.armed = false;
+        let response = response.map_err(|_| connection_closed())??;
+        if let Some(error) = response.error {
+            return Err(RadiologyError::Rejected {
+                code: Some(error.code),
+                message: error.message,
+            });
+        }
+        response
+            .result
+            .ok_or_else(|| RadiologyError::protocol("response missing result"))
+    }
+
+    async fn notify(&self, method: &str, params: Option<Value>) -> Result<(), RadiologyError> {
+        let notification = Notification::new(method, params);
+        self.send(serde_json::to_string(&notification)?).await
+    }
+
+    async fn send(&self, text: String) -> Result<(), RadiologyError> {
+        let mut writer = self.writer.lock().await;
+        let writer = writer.as_mut().ok_or_else(connection_closed)?;
+        writer
+            .send(Message::Text(text))
+            .await
+            .map_err(|e| RadiologyError::Transport(Box::new(e)))
+    }
+
+    // Every tool the server offers, remembering which may be called again after a reconnect
+    async fn list_tools(self: &Arc<Self>, replay: bool) -> Result<Vec<Tool>, RadiologyError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = cursor.map(|cursor| json!({ "cursor": cursor }));
+            let mut page = self.request(protocol::TOOLS_LIST, params, replay).await?;
+            let batch: Vec<Tool> = parse(page["tools"].take(), "tool list")?;
+            tools.extend(batch);
+
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        let mut idempotent = self.idempotent_tools.lock().unwrap();
+        idempotent.clear();
+        idempotent.extend(tools.iter().filter(|t| t.is_idempotent()).map(|t| t.name.clone()));
+        Ok(tools)
+    }
+
+    async fn handshake(self: &Arc<Self>) -> Result<InitializeResult, RadiologyError> {
+        let params = json!({
+            "protocolVersion": protocol::LATEST_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": Implementation::this_client(),
+        });
+        let result = self.request(protocol::INITIALIZE, Some(params), false).await?;
+        let session: InitializeResult = parse(result, "initialize result")?;
+
+        if !protocol::SUPPORTED_PROTOCOL_VERSIONS.contains(&session.protocol_version.as_str()) {
+            return Err(RadiologyError::protocol(format!(
+                "server requires unsupported protocol version {}",
+                session.protocol_version
+            )));
+        }
+
+        self.notify(protocol::INITIALIZED, None).await?;
+        *self.session.lock().unwrap() = Some(session.clone());
+        Ok(session)
+    }
+
+    // Called by the reader once the socket is gone
+    async fn connection_lost(self: Arc<Self>) {
+        self.writer.lock().await.take();
+        let had_session = self.session.lock().unwrap().take().is_some();
+
+        if self.reconnect.is_none() {
+            self.close();
+            return;
+        }
+
+        // Requests that can't be repeated fail now; their waiters see a closed connection
+        self.pending.lock().unwrap().requests.retain(|_, request| request.replay);
+        // A socket lost while a reconnect is still restoring it is that reconnect's to retry
+        if *self.state.borrow() == ConnectionState::Reconnecting {
+            return;
+        }
+        self.state.send_replace(ConnectionState::Reconnecting);
+        println!("Connection to {} lost, reconnecting", self.url);
+
+        let inner = self.clone();
+        self.spawn(inner.reconnect(had_session));
+    }
+
+    // Boxed because the reader task spawns this and this spawns a reader again
+    fn reconnect(self: Arc<Self>, had_session: bool) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move { self.reconnect_loop(had_session).await })
+    }
+
+    async fn reconnect_loop(self: Arc<Self>, had_session: bool) {
+        let Some(policy) = self.reconnect.clone() else {
+            return;
+        };
+
+        let mut attempt = 0;
+        loop {
+            if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                println!("Giving up on {} after {} reconnection attempts", self.url, attempt);
+                self.close();
+                return;
+            }
+            tokio::time::sleep(policy.delay(attempt)).await;
+            attempt += 1;
+
+            match self.clone().restore(had_session).await {
+                Ok(()) => {
+                    println!("Reconnected to {} after {} attempt(s)", self.url, attempt);
+                    return;
+                }
+                Err(e) => println!("Reconnection attempt {} failed: {}", attempt, e),
+            }
+        }
+    }
+
+    // Open a fresh socket, repeat the handshake and resend what was in flight
+    async fn restore(self: Arc<Self>, had_session: bool) -> Result<(), RadiologyError> {
+        let (writer, reader) = open(&self.url).await?;
+        *self.writer.lock().await = Some(writer);
+        let reading = self.spawn(read_responses(reader, Arc::downgrade(&self)));
+
+        // A socket given up on must not have its reader report it lost and start another reconnect
+        if let Err(e) = self.resume(had_session).await {
+            reading.abort();
+            self.writer.lock().await.take();
+            return Err(e);
+        }
+        self.state.send_replace(ConnectionState::Connected);
+        Ok(())
+    }
+
+    // The tools may have changed while the connection was down, and with them what may be replayed
+    async fn resume(self: &Arc<Self>, had_session: bool) -> Result<(), RadiologyError> {
+        if had_session {
+            self.handshake().await?;
+            self.list_tools(false).await?;
+        }
+
+        let frames: Vec<String> = {
+            let pending = self.pending.lock().unwrap();
+            pending.requests.values().map(|request| request.frame.clone()).collect()
+        };
+        for frame in frames {
+            self.send(frame).await?;
+        }
+        Ok(())
+    }
+
+    // Fail everything in flight and refuse new requests
+    fn close(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.closed = true;
+        pending.requests.clear();
+        self.state.send_replace(ConnectionState::Closed);
+    }
+}
+
+// Dropped with the request future; while armed, the request was abandoned before its
+// response arrived, so it is forgotten and the server asked to stop working on it
+struct Cancellation<'a> {
+    inner: &'a Arc<Inner>,
+    id: RequestId,
+    armed: bool,
+}
+
+impl Drop for Cancellation<'_> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Nothing to cancel if the connection already dropped the request
+        if self.inner.pending.lock().unwrap().requests.remove(&self.id).is_none() {
+            return;
+        }
+        if tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let inner = self.inner.clone();
+        let params = json!({ "requestId": self.id, "reason": "request abandoned by the client" });
+        self.inner.spawn(async move {
+            let _ = inner.notify(protocol::CANCELLED, Some(params)).await;
+        });
+    }
+}
+
+async fn open(url: &str) -> Result<(WsSink, SplitStream<WsStream>), RadiologyError> {
+    let (stream, _) = connect_async(url)
+        .await
+        .map_err(|e| RadiologyError::Transport(Box::new(e)))?;
+    Ok(stream.split())
+}
+
+fn parse<T: serde::de::DeserializeOwned>(value: Value, what: &str) -> Result<T, RadiologyError> {
+    serde_json::from_value(value).map_err(|e| RadiologyError::Protocol {
+        message: format!("invalid {}", what),
+        source: Some(Box::new(e)),
+    })
+}
+
+fn connection_closed() -> RadiologyError {
+    RadiologyError::Transport("connection closed".into())
+}
+
+async fn read_responses(mut reader: SplitStream<WsStream>, inner: Weak<Inner>) {
+    while let Some(Ok(message)) = reader.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        // Server-initiated requests and notifications aren't handled yet, so they are dropped
+        let frame = serde_json::from_str::<Value>(&text);
+        if frame.as_ref().is_ok_and(|frame| frame.get("method").is_some()) {
+            continue;
+        }
+        let id = frame.as_ref().ok().and_then(|frame| serde_json::from_value(frame.get("id")?.clone()).ok());
+        let response = frame.and_then(serde_json::from_value::<Response>);
+
+        let mut pending = inner.pending.lock().unwrap();
+        match response {
+            Ok(response) => {
+                if let Some(request) = pending.requests.remove(&response.id) {
+                    let _ = request.waiter.send(Ok(response));
+                }
+            }
+            // A malformed answer fails its request only when the answer is JSON with that
+            // request's id at the top level. Anything else is dropped, and the caller's
+            // deadline ends the wait
+            Err(err) => match id.and_then(|id| pending.requests.remove(&id)) {
+                Some(request) => {
+                    let _ = request.waiter.send(Err(RadiologyError::Protocol {
+                        message: "malformed response".to_string(),
+                        source: Some(Box::new(err)),
+                    }));
+                }
+                None => println!("Dropping unreadable response: {}", err),
+            },
+        }
+    }
+
+    if let Some(inner) = inner.upgrade() {
+        inner.connection_lost().await;
+    }
+}
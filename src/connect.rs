@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use crate::client::McpClient;
+use crate::error::RadiologyError;
+use crate::retry::{self, Backoff, RetryPolicy};
+
+/// How a supervised `McpClient` reconnects after its connection drops.
+///
+/// Attempt `n` (counting from zero) waits `initial_delay * multiplier^n`,
+/// capped at `max_delay`; with `jitter` the wait is drawn uniformly from the
+/// upper half of that range so clients dropped together don't reconnect in step.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    pub jitter: bool,
+    /// Attempts before giving up and failing everything in flight; `None` retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Pause before reconnection attempt `attempt`, counting from zero
+    pub fn delay(&self, attempt: u32) -> Duration {
+        retry::exponential_delay(self.initial_delay, self.max_delay, self.multiplier, self.jitter, attempt)
+    }
+}
+
+/// Establishes `McpClient` connections, retrying failed attempts under a `RetryPolicy`.
+///
+/// The default policy makes three attempts two seconds apart.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), mcp::RadiologyError> {
+/// use std::time::Duration;
+/// use mcp::connect::{Connector, ReconnectPolicy};
+/// use mcp::retry::{Backoff, RetryPolicy};
+///
+/// let client = Connector::new()
+///     .retry(RetryPolicy::new(Backoff::Fixed(Duration::from_millis(500))).max_attempts(5))
+///     .reconnect(ReconnectPolicy::default())
+///     .connect("ws://localhost:8080")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Connector {
+    retry: RetryPolicy,
+    reconnect: Option<ReconnectPolicy>,
+}
+
+impl Default for Connector {
+    fn default() -> Self {
+        Connector {
+            retry: RetryPolicy::new(Backoff::Fixed(Duration::from_secs(2))).max_attempts(3),
+            reconnect: None,
+        }
+    }
+}
+
+impl Connector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry failed connection attempts under `policy`
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Total number of connection attempts before giving up (at least one is always made)
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.retry = self.retry.max_attempts(max_retries);
+        self
+    }
+
+    /// Wait the same `delay` between failed attempts
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.retry = self.retry.backoff(Backoff::Fixed(delay));
+        self
+    }
+
+    /// Supervise the connection once established, reconnecting under `policy` when it drops
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Connect to `url`, returning the last error once the retry policy gives up
+    pub async fn connect(&self, url: &str) -> Result<McpClient, RadiologyError> {
+        self.retry
+            .run(|attempt| {
+                println!("Connection attempt {}", attempt);
+                McpClient::connect_with(url, self.reconnect.clone())
+            })
+            .await
+    }
+}
+
+/// Connect to `url`, making up to `max_retries` attempts spaced `delay` apart
+pub async fn connect_with_retry(url: &str, max_retries: u32, delay: Duration) -> Result<McpClient, RadiologyError> {
+    Connector::new()
+        .max_retries(max_retries)
+        .delay(delay)
+        .connect(url)
+        .await
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::backend::DEFAULT_BACKEND;
+use crate::conversation::DEFAULT_HISTORY_TOKENS;
+use crate::deid::{DeidPolicy, Deidentifier};
+use crate::prompt::PromptSource;
+
+/// How a context analyzes its images.
+///
+/// ```
+/// use std::time::Duration;
+/// use mcp::context::ContextConfig;
+///
+/// let config = ContextConfig::new("ct-model")
+///     .backend("ct")
+///     .timeout(Duration::from_secs(30))
+///     .ttl(Duration::from_secs(3600));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ContextConfig {
+    pub model: String,
+    /// Name of the registered backend submissions go to
+    pub backend: String,
+    /// `DEFAULT_PROMPT_TEMPLATE` unless set
+    pub prompt: PromptSource,
+    /// Deadline for submissions; `None` uses the cluster-wide default
+    pub timeout: Option<Duration>,
+    /// The context expires once no submission has run for this long; `None` keeps it until closed
+    pub ttl: Option<Duration>,
+    /// Replaces the cluster's de-identification policy for this context
+    pub deid_policy: Option<DeidPolicy>,
+    /// Most conversation history, in estimated tokens, sent with a follow-up question
+    pub history_tokens: usize,
+}
+
+impl ContextConfig {
+    /// `model` on the default backend, with the cluster's defaults for everything else
+    pub fn new(model: &str) -> Self {
+        ContextConfig {
+            model: model.to_string(),
+            backend: DEFAULT_BACKEND.to_string(),
+            prompt: PromptSource::default(),
+            timeout: None,
+            ttl: None,
+            deid_policy: None,
+            history_tokens: DEFAULT_HISTORY_TOKENS,
+        }
+    }
+
+    pub fn backend(mut self, backend: &str) -> Self {
+        self.backend = backend.to_string();
+        self
+    }
+
+    pub fn prompt(mut self, prompt: PromptSource) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    /// Use this template text as the prompt
+    pub fn prompt_template(mut self, template: &str) -> Self {
+        self.prompt = PromptSource::Inline(template.to_string());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn deid_policy(mut self, policy: DeidPolicy) -> Self {
+        self.deid_policy = Some(policy);
+        self
+    }
+
+    pub fn history_tokens(mut self, tokens: usize) -> Self {
+        self.history_tokens = tokens;
+        self
+    }
+}
+
+/// A snapshot of one context, as returned by `RadiologyCluster::context`
+#[derive(Clone, Debug)]
+pub struct ContextInfo {
+    pub id: String,
+    pub config: ContextConfig,
+    pub created_at: DateTime<Utc>,
+    /// When a submission last started or finished
+    pub last_used: DateTime<Utc>,
+    /// Submissions that haven't finished
+    pub in_flight: usize,
+    /// `close_context` is waiting for in-flight submissions; new ones are refused
+    pub closing: bool,
+}
+
+// What the cluster keeps for one context
+pub(crate) struct Context {
+    pub(crate) config: ContextConfig,
+    // Set when the context has a de-identification policy of its own
+    pub(crate) deid: Option<Arc<Deidentifier>>,
+    created_at: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+    pub(crate) in_flight: usize,
+    pub(crate) closing: bool,
+}
+
+impl Context {
+    pub(crate) fn new(config: ContextConfig) -> Self {
+        let now = Utc::now();
+        Context {
+            deid: deidentifier(&config),
+            config,
+            created_at: now,
+            last_used: now,
+            in_flight: 0,
+            closing: false,
+        }
+    }
+
+    // Keeps the usage history; a new de-identification policy starts a new mapping
+    pub(crate) fn reconfigure(&mut self, config: ContextConfig) {
+        self.deid = deidentifier(&config);
+        self.config = config;
+    }
+
+    pub(crate) fn touch(&mut self) {
+        self.last_used = Utc::now();
+    }
+
+    // Idle past its TTL; a context with submissions in flight is never idle
+    pub(crate) fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        let Some(ttl) = self.config.ttl else {
+            return false;
+        };
+        let idle = (now - self.last_used).to_std().unwrap_or_default();
+        self.in_flight == 0 && idle >= ttl
+    }
+
+    pub(crate) fn info(&self, id: &str) -> ContextInfo {
+        ContextInfo {
+            id: id.to_string(),
+            config: self.config.clone(),
+            created_at: self.created_at,
+            last_used: self.last_used,
+            in_flight: self.in_flight,
+            closing: self.closing,
+        }
+    }
+}
+
+fn deidentifier(config: &ContextConfig) -> Option<Arc<Deidentifier>> {
+    config.deid_policy.clone().map(|policy| Arc::new(Deidentifier::new(policy)))
+}
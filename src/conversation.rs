@@ -0,0 +1,37 @@
+use crate::AnalysisMessage;
+
+/// Tokens of conversation history sent with a follow-up unless the context sets another budget
+pub const DEFAULT_HISTORY_TOKENS: usize = 4096;
+
+pub const USER: &str = "user";
+pub const ASSISTANT: &str = "assistant";
+
+// Rough per-message cost of the role and framing
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Approximate token count of a message: about four characters per token, plus framing.
+///
+/// Close enough for budgeting without a model-specific tokenizer.
+pub fn estimate_tokens(message: &AnalysisMessage) -> usize {
+    message.content.chars().count().div_ceil(4) + MESSAGE_OVERHEAD
+}
+
+/// The messages to send when the whole history doesn't fit in `budget` tokens.
+///
+/// The first exchange, which holds the original read of the image, and the
+/// last message, the question being asked, are always kept. Messages in
+/// between are dropped oldest first until the rest fits.
+pub fn truncate(messages: &[AnalysisMessage], budget: usize) -> Vec<AnalysisMessage> {
+    let head = messages.len().min(2);
+    let last = messages.len().saturating_sub(1);
+    let head_tokens: usize = messages[..head].iter().map(estimate_tokens).sum();
+
+    let mut start = head;
+    let mut tail_tokens: usize = messages[head..].iter().map(estimate_tokens).sum();
+    while start < last && head_tokens + tail_tokens > budget {
+        tail_tokens -= estimate_tokens(&messages[start]);
+        start += 1;
+    }
+
+    messages[..head].iter().chain(&messages[start..]).cloned().collect()
+}
@@ -0,0 +1,264 @@
+use serde_json::Value;
+
+use crate::findings::{Code, Finding, FindingsReport, Laterality, Measurement, Severity};
+use crate::RadiologyResult;
+
+/// Reasons a server response could not be turned into a `RadiologyResult`
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("response is missing required field '{0}'")]
+    MissingField(&'static str),
+    #[error("response field '{field}' has the wrong type, expected {expected}")]
+    InvalidType {
+        field: &'static str,
+        expected: &'static str,
+    },
+    #[error("confidence score {0} is outside the range 0..=1")]
+    ConfidenceOutOfRange(f64),
+    #[error("finding {index} is invalid: {reason}")]
+    InvalidFinding { index: usize, reason: String },
+}
+
+/// Converts the raw JSON returned by the server into a typed result.
+///
+/// Plain functions and closures with the same signature implement this trait,
+/// so a one-off decoder can be passed straight to `RadiologyCluster::with_decoder`.
+pub trait ResponseDecoder: Send + Sync {
+    fn decode(&self, image_id: &str, response: &Value) -> Result<RadiologyResult, DecodeError>;
+}
+
+impl<F> ResponseDecoder for F
+where
+    F: Fn(&str, &Value) -> Result<RadiologyResult, DecodeError> + Send + Sync,
+{
+    fn decode(&self, image_id: &str, response: &Value) -> Result<RadiologyResult, DecodeError> {
+        self(image_id, response)
+    }
+}
+
+/// Decoder understanding both response shapes seen in the wild:
+///
+/// - nested: `{"status": ..., "results": {"findings": ..., "confidence": ...}}`
+/// - flat: `{"status": ..., "findings": ..., "confidence": ..., "analysis_date": ...}`
+///
+/// `confidence_score` is accepted as an alias for `confidence`. When the server
+/// doesn't report an analysis date the time of decoding is used.
+///
+/// `findings` is either free text or a list of structured findings:
+///
+/// ```json
+/// {"findings": [{"description": "Solid nodule", "location": "upper lobe", "laterality": "right",
+///                "size_mm": 8, "severity": "mild", "radlex": "RID3875", "confidence": 0.8}],
+///  "impression": "Indeterminate pulmonary nodule"}
+/// ```
+///
+/// A list fills `RadiologyResult::report`, and `findings` becomes its text
+/// summary. Without an overall confidence the lowest per-finding one is used.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultResponseDecoder;
+
+impl ResponseDecoder for DefaultResponseDecoder {
+    fn decode(&self, image_id: &str, response: &Value) -> Result<RadiologyResult, DecodeError> {
+        let body = match response.get("results") {
+            Some(results) if results.is_object() => results,
+            _ => response,
+        };
+
+        let impression = match lookup(body, response, &["impression"]) {
+            Some(impression) => Some(
+                impression
+                    .as_str()
+                    .ok_or(DecodeError::InvalidType { field: "impression", expected: "a string" })?
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let findings = lookup(body, response, &["findings"]).ok_or(DecodeError::MissingField("findings"))?;
+        let (findings, report) = match findings {
+            Value::String(text) => (text.clone(), impression.map(|impression| FindingsReport {
+                findings: Vec::new(),
+                impression: Some(impression),
+            })),
+            Value::Array(items) => {
+                let findings = items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| finding(item).map_err(|reason| DecodeError::InvalidFinding { index, reason }))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let report = FindingsReport { findings, impression };
+                (report.summary(), Some(report))
+            }
+            _ => {
+                return Err(DecodeError::InvalidType {
+                    field: "findings",
+                    expected: "a string or a list of findings",
+                })
+            }
+        };
+
+        let lowest_finding_confidence = report
+            .iter()
+            .flat_map(|report| &report.findings)
+            .filter_map(|finding| finding.confidence)
+            .reduce(f32::min);
+        let confidence = match lookup(body, response, &["confidence", "confidence_score"]) {
+            Some(confidence) => confidence
+                .as_f64()
+                .ok_or(DecodeError::InvalidType { field: "confidence", expected: "a number" })?,
+            None => lowest_finding_confidence.ok_or(DecodeError::MissingField("confidence"))? as f64,
+        };
+        if !(0.0..=1.0).contains(&confidence) {
+            return Err(DecodeError::ConfidenceOutOfRange(confidence));
+        }
+
+        let analysis_date = match lookup(body, response, &["analysis_date"]) {
+            Some(date) => date
+                .as_str()
+                .ok_or(DecodeError::InvalidType { field: "analysis_date", expected: "a string" })?
+                .to_string(),
+            None => chrono::Utc::now().to_rfc3339(),
+        };
+
+        Ok(RadiologyResult {
+            image_id: image_id.to_string(),
+            findings,
+            confidence_score: confidence as f32,
+            analysis_date,
+            study_instance_uid: None,
+            series_instance_uid: None,
+            report,
+        })
+    }
+}
+
+// Look for the first present key in the result body, then fall back to the top-level response
+fn lookup<'a>(body: &'a Value, response: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter()
+        .find_map(|key| body.get(key))
+        .or_else(|| keys.iter().find_map(|key| response.get(key)))
+}
+
+// One entry of a structured findings list; a bare string is just a description
+fn finding(item: &Value) -> Result<Finding, String> {
+    if let Some(description) = item.as_str() {
+        return Ok(Finding {
+            description: description.to_string(),
+            ..Finding::default()
+        });
+    }
+    if !item.is_object() {
+        return Err("expected an object or a string".to_string());
+    }
+
+    let description = text(item, &["description", "finding"])?.ok_or("missing 'description'")?;
+    let laterality = match text(item, &["laterality"])? {
+        Some(laterality) => Some(parse_laterality(&laterality)?),
+        None => None,
+    };
+    let severity = match text(item, &["severity"])? {
+        Some(severity) => Some(parse_severity(&severity)?),
+        None => None,
+    };
+
+    let mut measurements = match item.get("measurements") {
+        Some(Value::Array(measurements)) => measurements.iter().map(measurement).collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("'measurements' should be a list".to_string()),
+        None => Vec::new(),
+    };
+    if let Some(size) = item.get("size_mm") {
+        let value = size.as_f64().ok_or("'size_mm' should be a number")?;
+        measurements.push(Measurement {
+            value,
+            unit: "mm".to_string(),
+            dimension: None,
+        });
+    }
+
+    let mut codes = match item.get("codes") {
+        Some(Value::Array(codes)) => codes.iter().map(code).collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("'codes' should be a list".to_string()),
+        None => Vec::new(),
+    };
+    for scheme in ["radlex", "snomed"] {
+        if let Some(value) = text(item, &[scheme])? {
+            codes.push(Code::new(scheme, &value, None));
+        }
+    }
+
+    let confidence = match item.get("confidence") {
+        Some(confidence) => {
+            let confidence = confidence.as_f64().ok_or("'confidence' should be a number")?;
+            if !(0.0..=1.0).contains(&confidence) {
+                return Err(format!("confidence {} is outside the range 0..=1", confidence));
+            }
+            Some(confidence as f32)
+        }
+        None => None,
+    };
+
+    Ok(Finding {
+        description,
+        location: text(item, &["location", "anatomical_location"])?,
+        laterality,
+        measurements,
+        severity,
+        codes,
+        confidence,
+    })
+}
+
+fn measurement(value: &Value) -> Result<Measurement, String> {
+    if let Some(value) = value.as_f64() {
+        return Ok(Measurement {
+            value,
+            unit: "mm".to_string(),
+            dimension: None,
+        });
+    }
+    Ok(Measurement {
+        value: value
+            .get("value")
+            .and_then(Value::as_f64)
+            .ok_or("a measurement needs a numeric 'value'")?,
+        unit: text(value, &["unit"])?.unwrap_or_else(|| "mm".to_string()),
+        dimension: text(value, &["dimension"])?,
+    })
+}
+
+fn code(value: &Value) -> Result<Code, String> {
+    let scheme = text(value, &["scheme", "system"])?.ok_or("a code needs a 'scheme'")?;
+    let code = text(value, &["code"])?.ok_or("a code needs a 'code'")?;
+    let meaning = text(value, &["meaning", "display"])?;
+    Ok(Code::new(&scheme, &code, meaning.as_deref()))
+}
+
+fn parse_laterality(value: &str) -> Result<Laterality, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "left" | "l" => Ok(Laterality::Left),
+        "right" | "r" => Ok(Laterality::Right),
+        "bilateral" | "both" | "b" => Ok(Laterality::Bilateral),
+        "midline" | "m" => Ok(Laterality::Midline),
+        _ => Err(format!("unknown laterality '{}'", value)),
+    }
+}
+
+fn parse_severity(value: &str) -> Result<Severity, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "normal" | "none" => Ok(Severity::Normal),
+        "mild" => Ok(Severity::Mild),
+        "moderate" => Ok(Severity::Moderate),
+        "severe" => Ok(Severity::Severe),
+        "critical" => Ok(Severity::Critical),
+        _ => Err(format!("unknown severity '{}'", value)),
+    }
+}
+
+// The first of `keys` present in an object, which must be a string
+fn text(value: &Value, keys: &[&str]) -> Result<Option<String>, String> {
+    match keys.iter().find_map(|key| Some((*key, value.get(key)?))) {
+        Some((_, Value::String(text))) => Ok(Some(text.clone())),
+        Some((_, Value::Null)) | None => Ok(None),
+        Some((key, _)) => Err(format!("'{}' should be a string", key)),
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, NaiveDate};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::dicom::DicomTags;
+use crate::RadiologyImage;
+
+/// Key under which the policy looks up the action for an image's id
+pub const IMAGE_ID_KEY: &str = "image_id";
+
+/// What happens to one metadata field before it leaves the process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldAction {
+    /// Send the value unchanged
+    Keep,
+    /// Remove the field entirely
+    Drop,
+    /// Replace the value with a salted HMAC-SHA256 digest
+    Hash,
+    /// Move a date by the policy's day offset; unparseable dates are dropped
+    DateShift,
+    /// Replace the value with a sequential pseudonym such as `ANON-000001`
+    Pseudonymize,
+}
+
+/// Per-field de-identification rules.
+///
+/// The default policy is an allowlist: it pseudonymizes the patient id and
+/// instance UIDs, shifts study dates, keeps the modality, body part and the
+/// image geometry fields `DicomTags::to_metadata` produces, and drops every
+/// other field, so a key nobody reviewed never reaches the server. Fields not
+/// listed use `default_action`; `keep_all` is the explicit opt-out.
+///
+/// Unless set explicitly, the salt and date offset are random per policy, so
+/// hashes and shifted dates can't be correlated across processes.
+#[derive(Clone, Debug)]
+pub struct DeidPolicy {
+    actions: HashMap<String, FieldAction>,
+    default_action: FieldAction,
+    salt: Vec<u8>,
+    date_shift_days: i64,
+}
+
+impl Default for DeidPolicy {
+    fn default() -> Self {
+        let mut policy = DeidPolicy::keep_all()
+            .default_action(FieldAction::Drop)
+            .field("patient_id", FieldAction::Pseudonymize)
+            .field(IMAGE_ID_KEY, FieldAction::Pseudonymize)
+            .field("study_instance_uid", FieldAction::Pseudonymize)
+            .field("series_instance_uid", FieldAction::Pseudonymize)
+            .field("sop_instance_uid", FieldAction::Pseudonymize);
+        for key in [
+            "patient_name",
+            "patient_birth_date",
+            "patient_address",
+            "accession_number",
+            "referring_physician",
+            "institution_name",
+        ] {
+            policy = policy.field(key, FieldAction::Drop);
+        }
+        for key in ["study_date", "series_date", "acquisition_date", "content_date"] {
+            policy = policy.field(key, FieldAction::DateShift);
+        }
+        for key in [
+            "modality",
+            "body_part",
+            "sop_class_uid",
+            "transfer_syntax_uid",
+            "rows",
+            "columns",
+            "bits_allocated",
+            "samples_per_pixel",
+            "photometric_interpretation",
+            crate::transfer::MIME_TYPE_KEY,
+        ] {
+            policy = policy.field(key, FieldAction::Keep);
+        }
+        policy
+    }
+}
+
+impl DeidPolicy {
+    /// A policy that sends every field unchanged, for data that is already de-identified
+    pub fn keep_all() -> Self {
+        let mut rng = rand::thread_rng();
+        DeidPolicy {
+            actions: HashMap::new(),
+            default_action: FieldAction::Keep,
+            salt: rng.gen::<[u8; 32]>().to_vec(),
+            date_shift_days: -rng.gen_range(1..=365),
+        }
+    }
+
+    /// Set the action for one metadata key
+    pub fn field(mut self, key: &str, action: FieldAction) -> Self {
+        self.actions.insert(key.to_string(), action);
+        self
+    }
+
+    /// Set the action for keys without an explicit rule
+    pub fn default_action(mut self, action: FieldAction) -> Self {
+        self.default_action = action;
+        self
+    }
+
+    /// Use a fixed salt, so hashes stay stable across runs
+    pub fn salt(mut self, salt: impl AsRef<[u8]>) -> Self {
+        self.salt = salt.as_ref().to_vec();
+        self
+    }
+
+    /// Use a fixed date offset in days
+    pub fn date_shift_days(mut self, days: i64) -> Self {
+        self.date_shift_days = days;
+        self
+    }
+
+    pub fn action(&self, key: &str) -> FieldAction {
+        self.actions.get(key).copied().unwrap_or(self.default_action)
+    }
+}
+
+// Replacement values handed out so far, in both directions. Originals are keyed by
+// field and replacement, so a value is only restored in the field it was issued for.
+#[derive(Default)]
+struct Mapping {
+    pseudonyms: HashMap<String, String>,
+    originals: HashMap<(String, String), String>,
+}
+
+/// Applies a `DeidPolicy` and remembers every replacement it makes.
+///
+/// The mapping from replacement to original value never leaves the process;
+/// `reidentify` uses it to tie values echoed back by the server to the real
+/// patient. Pseudonyms are stable: the same original always gets the same one.
+pub struct Deidentifier {
+    policy: DeidPolicy,
+    mapping: Mutex<Mapping>,
+}
+
+impl Deidentifier {
+    pub fn new(policy: DeidPolicy) -> Self {
+        Deidentifier {
+            policy,
+            mapping: Mutex::new(Mapping::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &DeidPolicy {
+        &self.policy
+    }
+
+    /// De-identify a single value stored under `key`; `None` means it is dropped
+    pub fn deidentify_value(&self, key: &str, value: &str) -> Option<String> {
+        let replacement = match self.policy.action(key) {
+            FieldAction::Keep => return Some(value.to_string()),
+            FieldAction::Drop => return None,
+            FieldAction::Hash => self.hash(value),
+            FieldAction::DateShift => shift_date(value, self.policy.date_shift_days)?,
+            FieldAction::Pseudonymize => self.pseudonymize(value),
+        };
+        self.remember(key, &replacement, value);
+        Some(replacement)
+    }
+
+    pub fn deidentify_metadata(&self, metadata: &HashMap<String, String>) -> HashMap<String, String> {
+        metadata
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), self.deidentify_value(key, value)?)))
+            .collect()
+    }
+
+    /// De-identify the identifying DICOM tags, using the same keys as `DicomTags::to_metadata`
+    pub fn deidentify_tags(&self, tags: &DicomTags) -> DicomTags {
+        let apply = |key: &str, value: &Option<String>| {
+            value.as_deref().and_then(|value| self.deidentify_value(key, value))
+        };
+        DicomTags {
+            patient_id: apply("patient_id", &tags.patient_id),
+            study_instance_uid: apply("study_instance_uid", &tags.study_instance_uid),
+            series_instance_uid: apply("series_instance_uid", &tags.series_instance_uid),
+            sop_instance_uid: apply("sop_instance_uid", &tags.sop_instance_uid),
+            ..tags.clone()
+        }
+    }
+
+    /// A copy of the image with its id, metadata and DICOM tags de-identified.
+    ///
+    /// Pixel data is passed through untouched; burned-in annotations are not detected.
+    pub fn deidentify_image(&self, image: &RadiologyImage) -> RadiologyImage {
+        RadiologyImage {
+            image_id: self.deidentify_value(IMAGE_ID_KEY, &image.image_id).unwrap_or_default(),
+            data: image.data.clone(),
+            metadata: self.deidentify_metadata(&image.metadata),
+            dicom: image.dicom.as_ref().map(|tags| self.deidentify_tags(tags)),
+        }
+    }
+
+    /// The original value behind a pseudonym, hash or shifted date handed out earlier for `key`
+    pub fn reidentify(&self, key: &str, value: &str) -> Option<String> {
+        let mapping = self.mapping.lock().unwrap();
+        mapping.originals.get(&(key.to_string(), value.to_string())).cloned()
+    }
+
+    /// Replace every value in `metadata` that was handed out for its key with the original
+    pub fn reidentify_metadata(&self, metadata: &HashMap<String, String>) -> HashMap<String, String> {
+        let mapping = self.mapping.lock().unwrap();
+        metadata
+            .iter()
+            .map(|(key, value)| {
+                let original = mapping.originals.get(&(key.clone(), value.clone())).unwrap_or(value);
+                (key.clone(), original.clone())
+            })
+            .collect()
+    }
+
+    /// Snapshot of every replacement made so far, keyed by field and replacement value
+    pub fn mapping(&self) -> HashMap<(String, String), String> {
+        self.mapping.lock().unwrap().originals.clone()
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.policy.salt).expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn pseudonymize(&self, value: &str) -> String {
+        let mut mapping = self.mapping.lock().unwrap();
+        if let Some(pseudonym) = mapping.pseudonyms.get(value) {
+            return pseudonym.clone();
+        }
+        let pseudonym = format!("ANON-{:06}", mapping.pseudonyms.len() + 1);
+        mapping.pseudonyms.insert(value.to_string(), pseudonym.clone());
+        pseudonym
+    }
+
+    fn remember(&self, key: &str, replacement: &str, original: &str) {
+        self.mapping
+            .lock()
+            .unwrap()
+            .originals
+            .insert((key.to_string(), replacement.to_string()), original.to_string());
+    }
+}
+
+// Shift a DICOM DA (YYYYMMDD), ISO date or RFC 3339 timestamp, keeping its format
+fn shift_date(value: &str, days: i64) -> Option<String> {
+    let offset = Duration::days(days);
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some((date + offset).format("%Y%m%d").to_string());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some((date + offset).format("%Y-%m-%d").to_string());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|timestamp| (timestamp + offset).to_rfc3339())
+}
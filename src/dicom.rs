@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::RadiologyImage;
+
+/// Transfer syntax UIDs the loader distinguishes
+pub const IMPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+pub const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+pub const DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1.99";
+pub const EXPLICIT_VR_BIG_ENDIAN: &str = "1.2.840.10008.1.2.2";
+
+const PREAMBLE_LEN: usize = 128;
+const MAGIC: &[u8; 4] = b"DICM";
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+
+type Tag = (u16, u16);
+
+const TRANSFER_SYNTAX_UID: Tag = (0x0002, 0x0010);
+const SOP_CLASS_UID: Tag = (0x0008, 0x0016);
+const SOP_INSTANCE_UID: Tag = (0x0008, 0x0018);
+const MODALITY: Tag = (0x0008, 0x0060);
+const PATIENT_ID: Tag = (0x0010, 0x0020);
+const BODY_PART_EXAMINED: Tag = (0x0018, 0x0015);
+const STUDY_INSTANCE_UID: Tag = (0x0020, 0x000D);
+const SERIES_INSTANCE_UID: Tag = (0x0020, 0x000E);
+const SAMPLES_PER_PIXEL: Tag = (0x0028, 0x0002);
+const PHOTOMETRIC_INTERPRETATION: Tag = (0x0028, 0x0004);
+const ROWS: Tag = (0x0028, 0x0010);
+const COLUMNS: Tag = (0x0028, 0x0011);
+const BITS_ALLOCATED: Tag = (0x0028, 0x0100);
+const PIXEL_DATA: Tag = (0x7FE0, 0x0010);
+
+const ITEM: Tag = (0xFFFE, 0xE000);
+const ITEM_DELIMITER: Tag = (0xFFFE, 0xE00D);
+const SEQUENCE_DELIMITER: Tag = (0xFFFE, 0xE0DD);
+// Deeper nesting is refused rather than recursed into; real files stay far below it
+const MAX_SEQUENCE_DEPTH: usize = 64;
+
+/// Errors raised while reading a DICOM Part 10 file
+#[derive(Debug, thiserror::Error)]
+pub enum DicomError {
+    #[error("failed to read DICOM file")]
+    Io(#[from] std::io::Error),
+    #[error("not a DICOM Part 10 file (missing 'DICM' prefix)")]
+    NotDicom,
+    #[error("unsupported transfer syntax {0}")]
+    UnsupportedTransferSyntax(String),
+    #[error("DICOM data ends unexpectedly at offset {0}")]
+    Truncated(usize),
+    #[error("DICOM file contains no pixel data")]
+    MissingPixelData,
+    #[error("DICOM sequences nested too deeply at offset {0}")]
+    TooDeep(usize),
+}
+
+/// Standard identifying and image-description tags read from a DICOM file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DicomTags {
+    pub patient_id: Option<String>,
+    pub modality: Option<String>,
+    pub body_part_examined: Option<String>,
+    pub study_instance_uid: Option<String>,
+    pub series_instance_uid: Option<String>,
+    pub sop_class_uid: Option<String>,
+    pub sop_instance_uid: Option<String>,
+    pub transfer_syntax_uid: String,
+    pub rows: Option<u16>,
+    pub columns: Option<u16>,
+    pub bits_allocated: Option<u16>,
+    pub samples_per_pixel: Option<u16>,
+    pub photometric_interpretation: Option<String>,
+}
+
+impl DicomTags {
+    /// The tags as `RadiologyImage` metadata entries, using the same keys as
+    /// hand-built images (`patient_id`, `modality`, `body_part`, ...)
+    pub fn to_metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        let mut insert = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value);
+            }
+        };
+
+        insert("patient_id", self.patient_id.clone());
+        insert("modality", self.modality.clone());
+        insert("body_part", self.body_part_examined.clone());
+        insert("study_instance_uid", self.study_instance_uid.clone());
+        insert("series_instance_uid", self.series_instance_uid.clone());
+        insert("sop_class_uid", self.sop_class_uid.clone());
+        insert("sop_instance_uid", self.sop_instance_uid.clone());
+        insert("transfer_syntax_uid", Some(self.transfer_syntax_uid.clone()));
+        insert("rows", self.rows.map(|v| v.to_string()));
+        insert("columns", self.columns.map(|v| v.to_string()));
+        insert("bits_allocated", self.bits_allocated.map(|v| v.to_string()));
+        insert("samples_per_pixel", self.samples_per_pixel.map(|v| v.to_string()));
+        insert("photometric_interpretation", self.photometric_interpretation.clone());
+        insert(crate::transfer::MIME_TYPE_KEY, Some(pixel_mime_type(&self.transfer_syntax_uid).to_string()));
+        metadata
+    }
+}
+
+/// Read a DICOM Part 10 file into a `RadiologyImage`.
+///
+/// The image id is the SOP Instance UID, falling back to the file name when
+/// the tag is absent.
+pub fn load_dicom(path: impl AsRef<Path>) -> Result<RadiologyImage, DicomError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let mut image = parse_dicom(&bytes)?;
+    if image.image_id.is_empty() {
+        image.image_id = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+    }
+    Ok(image)
+}
+
+/// Parse the bytes of a DICOM Part 10 file into a `RadiologyImage`.
+///
+/// `data` holds the pixel data only; for encapsulated (compressed) transfer
+/// syntaxes the fragments are concatenated without decompression. The image
+/// id is empty when the file has no SOP Instance UID.
+pub fn parse_dicom(bytes: &[u8]) -> Result<RadiologyImage, DicomError> {
+    if bytes.len() < PREAMBLE_LEN + MAGIC.len() || &bytes[PREAMBLE_LEN..PREAMBLE_LEN + MAGIC.len()] != MAGIC {
+        return Err(DicomError::NotDicom);
+    }
+
+    let mut tags = DicomTags::default();
+    let mut pixel_data = None;
+
+    // The file meta group is always explicit VR little endian
+    let mut reader = Reader::new(bytes, PREAMBLE_LEN + MAGIC.len(), true);
+    while reader.peek_group()? == Some(0x0002) {
+        let element = reader.element(0)?;
+        if element.tag == TRANSFER_SYNTAX_UID {
+            tags.transfer_syntax_uid = text(element.value);
+        }
+    }
+
+    match tags.transfer_syntax_uid.as_str() {
+        IMPLICIT_VR_LITTLE_ENDIAN => reader.explicit = false,
+        DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN | EXPLICIT_VR_BIG_ENDIAN => {
+            return Err(DicomError::UnsupportedTransferSyntax(tags.transfer_syntax_uid));
+        }
+        // Explicit VR little endian, and every encapsulated syntax built on it
+        _ => reader.explicit = true,
+    }
+
+    while reader.peek_group()?.is_some() {
+        let element = reader.element(0)?;
+        match element.tag {
+            PATIENT_ID => tags.patient_id = Some(text(element.value)),
+            MODALITY => tags.modality = Some(text(element.value)),
+            BODY_PART_EXAMINED => tags.body_part_examined = Some(text(element.value)),
+            STUDY_INSTANCE_UID => tags.study_instance_uid = Some(text(element.value)),
+            SERIES_INSTANCE_UID => tags.series_instance_uid = Some(text(element.value)),
+            SOP_CLASS_UID => tags.sop_class_uid = Some(text(element.value)),
+            SOP_INSTANCE_UID => tags.sop_instance_uid = Some(text(element.value)),
+            PHOTOMETRIC_INTERPRETATION => tags.photometric_interpretation = Some(text(element.value)),
+            ROWS => tags.rows = us(element.value),
+            COLUMNS => tags.columns = us(element.value),
+            BITS_ALLOCATED => tags.bits_allocated = us(element.value),
+            SAMPLES_PER_PIXEL => tags.samples_per_pixel = us(element.value),
+            PIXEL_DATA => pixel_data = Some(element.value.to_vec()),
+            _ => {}
+        }
+    }
+
+    let data = pixel_data.ok_or(DicomError::MissingPixelData)?;
+    Ok(RadiologyImage {
+        image_id: tags.sop_instance_uid.clone().unwrap_or_default(),
+        data,
+        metadata: tags.to_metadata(),
+        dicom: Some(tags),
+    })
+}
+
+/// MIME type describing the pixel data stored under a transfer syntax
+pub fn pixel_mime_type(transfer_syntax_uid: &str) -> &'static str {
+    match transfer_syntax_uid {
+        "1.2.840.10008.1.2.4.50" | "1.2.840.10008.1.2.4.51" => "image/jpeg",
+        "1.2.840.10008.1.2.4.90" | "1.2.840.10008.1.2.4.91" => "image/jp2",
+        _ => "application/octet-stream",
+    }
+}
+
+struct Element<'a> {
+    tag: Tag,
+    value: std::borrow::Cow<'a, [u8]>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    explicit: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], pos: usize, explicit: bool) -> Self {
+        Reader { bytes, pos, explicit }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DicomError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or(DicomError::Truncated(self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, DicomError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DicomError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn tag(&mut self) -> Result<Tag, DicomError> {
+        Ok((self.u16()?, self.u16()?))
+    }
+
+    fn peek_group(&self) -> Result<Option<u16>, DicomError> {
+        match self.bytes.len() - self.pos {
+            0 => Ok(None),
+            1 => Err(DicomError::Truncated(self.pos)),
+            _ => Ok(Some(u16::from_le_bytes([self.bytes[self.pos], self.bytes[self.pos + 1]]))),
+        }
+    }
+
+    // Tag, VR (explicit syntaxes only) and value length
+    fn header(&mut self) -> Result<(Tag, Option<[u8; 2]>, u32), DicomError> {
+        let tag = self.tag()?;
+        // Item and delimiter tags never carry a VR, even in explicit syntaxes
+        if !self.explicit || tag.0 == 0xFFFE {
+            return Ok((tag, None, self.u32()?));
+        }
+
+        let vr = self.take(2)?;
+        let vr = [vr[0], vr[1]];
+        let len = match &vr {
+            b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV" => {
+                self.take(2)?;
+                self.u32()?
+            }
+            _ => u32::from(self.u16()?),
+        };
+        Ok((tag, Some(vr), len))
+    }
+
+    // `depth` counts the sequences the element is nested in
+    fn element(&mut self, depth: usize) -> Result<Element<'a>, DicomError> {
+        let (tag, vr, len) = self.header()?;
+        if len != UNDEFINED_LENGTH {
+            let value = self.take(len as usize)?;
+            return Ok(Element { tag, value: value.into() });
+        }
+
+        if tag == PIXEL_DATA {
+            return Ok(Element { tag, value: self.encapsulated_pixel_data()?.into() });
+        }
+
+        // Any other undefined length value is a sequence; UN sequences are always implicit VR
+        let explicit = self.explicit && vr.as_ref() != Some(b"UN");
+        self.skip_sequence(explicit, depth + 1)?;
+        Ok(Element { tag, value: (&[][..]).into() })
+    }
+
+    // Fragments following the basic offset table, concatenated in order
+    fn encapsulated_pixel_data(&mut self) -> Result<Vec<u8>, DicomError> {
+        let mut data = Vec::new();
+        let mut first = true;
+        loop {
+            let tag = self.tag()?;
+            let len = self.u32()?;
+            match tag {
+                SEQUENCE_DELIMITER => return Ok(data),
+                ITEM => {
+                    let fragment = self.take(len as usize)?;
+                    if !first {
+                        data.extend_from_slice(fragment);
+                    }
+                    first = false;
+                }
+                _ => return Err(DicomError::Truncated(self.pos)),
+            }
+        }
+    }
+
+    fn skip_sequence(&mut self, explicit: bool, depth: usize) -> Result<(), DicomError> {
+        if depth > MAX_SEQUENCE_DEPTH {
+            return Err(DicomError::TooDeep(self.pos));
+        }
+        let outer = std::mem::replace(&mut self.explicit, explicit);
+        let result = self.skip_items(depth);
+        self.explicit = outer;
+        result
+    }
+
+    fn skip_items(&mut self, depth: usize) -> Result<(), DicomError> {
+        loop {
+            let tag = self.tag()?;
+            let len = self.u32()?;
+            match tag {
+                SEQUENCE_DELIMITER => return Ok(()),
+                ITEM if len == UNDEFINED_LENGTH => loop {
+                    if self.peek_tag()? == ITEM_DELIMITER {
+                        self.take(8)?;
+                        break;
+                    }
+                    self.element(depth)?;
+                },
+                ITEM => {
+                    self.take(len as usize)?;
+                }
+                _ => return Err(DicomError::Truncated(self.pos)),
+            }
+        }
+    }
+
+    fn peek_tag(&self) -> Result<Tag, DicomError> {
+        let b = self.bytes.get(self.pos..self.pos + 4).ok_or(DicomError::Truncated(self.pos))?;
+        Ok((u16::from_le_bytes([b[0], b[1]]), u16::from_le_bytes([b[2], b[3]])))
+    }
+}
+
+// String values are padded to even length with spaces (or NUL for UIDs)
+fn text(value: std::borrow::Cow<'_, [u8]>) -> String {
+    String::from_utf8_lossy(&value)
+        .trim_end_matches(['\0', ' '])
+        .trim_start()
+        .to_string()
+}
+
+fn us(value: std::borrow::Cow<'_, [u8]>) -> Option<u16> {
+    (value.len() >= 2).then(|| u16::from_le_bytes([value[0], value[1]]))
+}
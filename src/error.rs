@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use crate::decode::DecodeError;
+use crate::prompt::PromptError;
+use crate::store::StoreError;
+
+/// Boxed error used as the source of protocol failures that don't have a dedicated type
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Error returned by every fallible `RadiologyCluster` operation
+#[derive(Debug, thiserror::Error)]
+pub enum RadiologyError {
+    /// The context was never initialized on this cluster
+    #[error("context '{0}' not found")]
+    UnknownContext(String),
+
+    /// A context with this id is already initialized
+    #[error("context '{0}' already exists")]
+    ContextExists(String),
+
+    /// The context is being closed and accepts no new submissions
+    #[error("context '{0}' is closing")]
+    ContextClosed(String),
+
+    /// The context has no conversation about this image to follow up on
+    #[error("image '{image_id}' has not been analyzed in context '{context_id}'")]
+    UnknownImage { context_id: String, image_id: String },
+
+    /// No backend is registered under this name
+    #[error("backend '{0}' not found")]
+    UnknownBackend(String),
+
+    /// Every member of the backend's pool is currently ejected
+    #[error("no healthy server in backend '{0}'")]
+    Unavailable(String),
+
+    /// The connection to the MCP server, or to an MLLP listener, failed or was lost
+    #[error("transport failure")]
+    Transport(#[source] BoxError),
+
+    /// A message could not be built or understood at the MCP/JSON-RPC level
+    #[error("protocol error: {message}")]
+    Protocol {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// The server answered, but its response isn't a valid analysis result
+    #[error("failed to decode server response")]
+    Decode(#[from] DecodeError),
+
+    /// No response arrived within the allowed time
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// The server explicitly refused or failed the request
+    #[error("server rejected the request: {message}")]
+    Rejected { code: Option<i32>, message: String },
+
+    /// The image exceeds the configured transfer size limit
+    #[error("image of {size} bytes exceeds the {limit} byte limit")]
+    ImageTooLarge { size: u64, limit: u64 },
+
+    /// The job queue's buffer is full and it is set to reject new jobs
+    #[error("job queue is full ({capacity} jobs waiting)")]
+    QueueFull { capacity: usize },
+
+    /// Reading image data failed
+    #[error("failed to read image data")]
+    Io(#[from] std::io::Error),
+
+    /// Results could not be recorded or read back
+    #[error("result storage failure")]
+    Storage(#[from] StoreError),
+
+    /// The context's prompt template is invalid or missing
+    #[error("prompt template error")]
+    Prompt(#[from] PromptError),
+}
+
+impl RadiologyError {
+    pub fn protocol(message: impl Into<String>) -> Self {
+        RadiologyError::Protocol {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Whether the same request might succeed if attempted again.
+    ///
+    /// True for lost connections, timeouts, JSON-RPC internal errors and pools
+    /// without a healthy member; a tool
+    /// that reported failure or a request the server refused stays failed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RadiologyError::Transport(_) | RadiologyError::Timeout(_) | RadiologyError::Unavailable(_) => true,
+            RadiologyError::Rejected { code: Some(code), .. } => *code == INTERNAL_ERROR,
+            _ => false,
+        }
+    }
+}
+
+// JSON-RPC code for a failure inside the server rather than in the request
+const INTERNAL_ERROR: i32 = -32603;
+
+impl From<mcp_rust_sdk::Error> for RadiologyError {
+    fn from(err: mcp_rust_sdk::Error) -> Self {
+        match err {
+            mcp_rust_sdk::Error::Protocol { code, message, .. } => RadiologyError::Rejected {
+                code: Some(code.into()),
+                message,
+            },
+            mcp_rust_sdk::Error::Serialization(_) => RadiologyError::Protocol {
+                message: "malformed message".to_string(),
+                source: Some(Box::new(err)),
+            },
+            err => RadiologyError::Transport(Box::new(err)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for RadiologyError {
+    fn from(err: serde_json::Error) -> Self {
+        RadiologyError::Protocol {
+            message: "failed to encode request".to_string(),
+            source: Some(Box::new(err)),
+        }
+    }
+}
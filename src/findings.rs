@@ -0,0 +1,162 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Coding scheme designators as used in DICOM structured reports
+pub const RADLEX: &str = "RADLEX";
+pub const SNOMED_CT: &str = "SCT";
+
+/// Structured form of a read: individual findings and the overall impression
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FindingsReport {
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impression: Option<String>,
+}
+
+impl FindingsReport {
+    /// Plain-text rendering, one finding per line followed by the impression;
+    /// this is what `RadiologyResult::findings` holds for a structured read
+    pub fn summary(&self) -> String {
+        let mut lines: Vec<String> = self.findings.iter().map(Finding::to_string).collect();
+        if let Some(impression) = &self.impression {
+            lines.push(format!("Impression: {}", impression));
+        }
+        lines.join("\n")
+    }
+}
+
+/// One observation in a read
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub description: String,
+    /// Anatomical location, such as "right upper lobe"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub laterality: Option<Laterality>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub measurements: Vec<Measurement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// RadLex, SNOMED CT or other codes for the finding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codes: Vec<Code>,
+    /// The model's confidence in this finding, between 0 and 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let place = [self.laterality.map(|l| l.to_string()), self.location.clone()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !place.is_empty() {
+            write!(f, "{}: ", place)?;
+        }
+        write!(f, "{}", self.description)?;
+
+        let mut details: Vec<String> = self.measurements.iter().map(Measurement::to_string).collect();
+        details.extend(self.severity.map(|severity| severity.to_string()));
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Laterality {
+    Left,
+    Right,
+    Bilateral,
+    Midline,
+}
+
+impl fmt::Display for Laterality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Laterality::Left => "left",
+            Laterality::Right => "right",
+            Laterality::Bilateral => "bilateral",
+            Laterality::Midline => "midline",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Normal,
+    Mild,
+    Moderate,
+    Severe,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Normal => "normal",
+            Severity::Mild => "mild",
+            Severity::Moderate => "moderate",
+            Severity::Severe => "severe",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+/// A size or other quantity, e.g. 8 mm along the long axis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub value: f64,
+    pub unit: String,
+    /// What was measured, such as "long axis"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<String>,
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit)?;
+        if let Some(dimension) = &self.dimension {
+            write!(f, " {}", dimension)?;
+        }
+        Ok(())
+    }
+}
+
+/// A coded concept, e.g. RadLex `RID3875` "nodule"
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Code {
+    /// Coding scheme designator: `RADLEX`, `SCT` or another
+    pub scheme: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meaning: Option<String>,
+}
+
+impl Code {
+    /// A code whose scheme name is normalized, so "RadLex" and "SNOMED-CT" match the constants
+    pub fn new(scheme: &str, code: &str, meaning: Option<&str>) -> Self {
+        Code {
+            scheme: scheme_designator(scheme),
+            code: code.to_string(),
+            meaning: meaning.map(str::to_string),
+        }
+    }
+}
+
+// The DICOM designator for the common spellings of RadLex and SNOMED CT
+pub(crate) fn scheme_designator(scheme: &str) -> String {
+    match scheme.to_ascii_uppercase().replace(['-', '_', ' '], "").as_str() {
+        "RADLEX" => RADLEX.to_string(),
+        "SCT" | "SNOMED" | "SNOMEDCT" => SNOMED_CT.to_string(),
+        _ => scheme.to_string(),
+    }
+}
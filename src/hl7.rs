@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::findings::{scheme_designator, Finding};
+use crate::retry::RetryPolicy;
+use crate::{RadiologyError, RadiologyResult, RecordedResult};
+
+/// HL7 version the messages declare
+pub const VERSION: &str = "2.5.1";
+
+/// MLLP framing bytes around each message
+pub const START_BLOCK: u8 = 0x0B;
+pub const END_BLOCK: u8 = 0x1C;
+pub const CARRIAGE_RETURN: u8 = 0x0D;
+
+/// How long `MllpSender` waits for a connection and an acknowledgement unless told otherwise
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SEGMENT_SEPARATOR: &str = "\r";
+const ENCODING_CHARACTERS: &str = "^~\\&";
+// Coding systems of the observation identifiers
+const LOINC: &str = "LN";
+const LOCAL_CODES: &str = "99MCP";
+
+/// Escape text for use inside a field, so separators and line breaks in
+/// findings can't break the message structure
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\E\\"),
+            '|' => out.push_str("\\F\\"),
+            '^' => out.push_str("\\S\\"),
+            '&' => out.push_str("\\T\\"),
+            '~' => out.push_str("\\R\\"),
+            '\r' | '\n' => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                out.push_str("\\.br\\");
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether the results in a message are final, or preliminary until a radiologist confirms them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResultStatus {
+    #[default]
+    Preliminary,
+    Final,
+}
+
+impl ResultStatus {
+    fn code(self) -> &'static str {
+        match self {
+            ResultStatus::Preliminary => "P",
+            ResultStatus::Final => "F",
+        }
+    }
+}
+
+/// Renders a `RadiologyResult` as an HL7 v2 ORU^R01 message.
+///
+/// The message has MSH, PID and one OBR segment, followed by OBX segments:
+/// the study and series UIDs, the findings, the impression and the overall
+/// confidence. Structured findings get a sub-id each, grouping their text,
+/// codes, measurements and confidence; free-text findings go in a single OBX.
+///
+/// ```
+/// use mcp::hl7::OruMessage;
+/// # fn render(result: &mcp::RadiologyResult) -> String {
+/// OruMessage::new(result)
+///     .sending_application("RADIOLOGY-AI")
+///     .receiving_application("RIS")
+///     .patient_id("P12345")
+///     .accession_number("ACC-1")
+///     .render()
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct OruMessage {
+    result: RadiologyResult,
+    control_id: String,
+    sending_application: String,
+    sending_facility: String,
+    receiving_application: String,
+    receiving_facility: String,
+    patient_id: String,
+    patient_name: String,
+    accession_number: String,
+    procedure: String,
+    status: ResultStatus,
+}
+
+impl OruMessage {
+    /// A message for `result` with a new control id and preliminary status
+    pub fn new(result: &RadiologyResult) -> Self {
+        OruMessage {
+            result: result.clone(),
+            control_id: generate_control_id(),
+            sending_application: String::new(),
+            sending_facility: String::new(),
+            receiving_application: String::new(),
+            receiving_facility: String::new(),
+            patient_id: String::new(),
+            patient_name: String::new(),
+            accession_number: String::new(),
+            procedure: String::new(),
+            status: ResultStatus::default(),
+        }
+    }
+
+    /// Patient, accession number and procedure from image metadata: `patient_id`,
+    /// `patient_name`, `accession_number`, `modality` and `body_part`
+    pub fn metadata(mut self, metadata: &HashMap<String, String>) -> Self {
+        let get = |key: &str| metadata.get(key).cloned().unwrap_or_default();
+        self.patient_id = get("patient_id");
+        self.patient_name = get("patient_name");
+        self.accession_number = get("accession_number");
+        let procedure = [get("modality"), get("body_part")];
+        self.procedure = procedure.iter().filter(|part| !part.is_empty()).cloned().collect::<Vec<_>>().join(" ");
+        self
+    }
+
+    pub fn control_id(mut self, control_id: &str) -> Self {
+        self.control_id = control_id.to_string();
+        self
+    }
+
+    pub fn sending_application(mut self, application: &str) -> Self {
+        self.sending_application = application.to_string();
+        self
+    }
+
+    pub fn sending_facility(mut self, facility: &str) -> Self {
+        self.sending_facility = facility.to_string();
+        self
+    }
+
+    pub fn receiving_application(mut self, application: &str) -> Self {
+        self.receiving_application = application.to_string();
+        self
+    }
+
+    pub fn receiving_facility(mut self, facility: &str) -> Self {
+        self.receiving_facility = facility.to_string();
+        self
+    }
+
+    pub fn patient_id(mut self, id: &str) -> Self {
+        self.patient_id = id.to_string();
+        self
+    }
+
+    /// In HL7 name form, e.g. `Doe^Jane`
+    pub fn patient_name(mut self, name: &str) -> Self {
+        self.patient_name = name.to_string();
+        self
+    }
+
+    /// Sent as the filler order number, which the RIS matches to its order
+    pub fn accession_number(mut self, number: &str) -> Self {
+        self.accession_number = number.to_string();
+        self
+    }
+
+    /// Description of the procedure in OBR-4, such as "CT CHEST"
+    pub fn procedure(mut self, procedure: &str) -> Self {
+        self.procedure = procedure.to_string();
+        self
+    }
+
+    pub fn status(mut self, status: ResultStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// MSH-10, which the receiver's acknowledgement refers to
+    pub fn id(&self) -> &str {
+        &self.control_id
+    }
+
+    /// The message with segments separated by carriage returns, without MLLP framing
+    pub fn render(&self) -> String {
+        let now = Utc::now().format("%Y%m%d%H%M%S%z").to_string();
+        let observed = DateTime::parse_from_rfc3339(&self.result.analysis_date)
+            .map(|date| date.with_timezone(&Utc).format("%Y%m%d%H%M%S%z").to_string())
+            .unwrap_or_else(|_| now.clone());
+        let status = self.status.code();
+
+        // MSH-1 is the field separator itself, so the encoding characters are the first field
+        let mut segments = vec![
+            [
+                "MSH",
+                ENCODING_CHARACTERS,
+                &escape(&self.sending_application),
+                &escape(&self.sending_facility),
+                &escape(&self.receiving_application),
+                &escape(&self.receiving_facility),
+                &now,
+                "",
+                "ORU^R01^ORU_R01",
+                &escape(&self.control_id),
+                "P",
+                VERSION,
+            ]
+            .join("|"),
+            ["PID", "1", "", &escape(&self.patient_id), "", &components(&self.patient_name)].join("|"),
+        ];
+
+        let procedure = if self.procedure.is_empty() { "Imaging study" } else { &self.procedure };
+        let mut obr = vec![String::new(); 26];
+        obr[0] = "OBR".to_string();
+        obr[1] = "1".to_string();
+        obr[3] = escape(&self.accession_number);
+        obr[4] = format!("^{}", escape(procedure));
+        obr[7] = observed.clone();
+        obr[22] = observed.clone();
+        obr[24] = "RAD".to_string();
+        obr[25] = status.to_string();
+        segments.push(obr.join("|"));
+
+        let mut observations = Observations {
+            segments: Vec::new(),
+            status,
+            observed: &observed,
+        };
+        let uids = [
+            ("STUDY_UID", "Study Instance UID", &self.result.study_instance_uid),
+            ("SERIES_UID", "Series Instance UID", &self.result.series_instance_uid),
+        ];
+        for (code, name, uid) in uids {
+            if let Some(uid) = uid {
+                observations.push("ST", &local(code, name), "", &escape(uid), "");
+            }
+        }
+
+        let findings = self.result.report.iter().flat_map(|report| &report.findings);
+        let mut structured = false;
+        for (index, finding) in findings.enumerate() {
+            structured = true;
+            observations.finding(&(index + 1).to_string(), finding);
+        }
+        if !structured {
+            observations.push("TX", &findings_code(), "", &escape(&self.result.findings), "");
+        }
+        if let Some(impression) = self.result.report.as_ref().and_then(|report| report.impression.as_deref()) {
+            let code = format!("19005-8^Radiology Imaging study [Impression] (narrative)^{}", LOINC);
+            observations.push("TX", &code, "", &escape(impression), "");
+        }
+        observations.confidence("", self.result.confidence_score);
+
+        segments.extend(observations.segments);
+        segments.join(SEGMENT_SEPARATOR)
+    }
+}
+
+// OBX segments, numbered in order
+struct Observations<'a> {
+    segments: Vec<String>,
+    status: &'static str,
+    observed: &'a str,
+}
+
+impl Observations<'_> {
+    fn push(&mut self, value_type: &str, code: &str, sub_id: &str, value: &str, units: &str) {
+        let set_id = (self.segments.len() + 1).to_string();
+        let fields = [
+            "OBX", &set_id, value_type, code, sub_id, value, units, "", "", "", "", self.status, "", "", self.observed,
+        ];
+        self.segments.push(fields.join("|"));
+    }
+
+    fn finding(&mut self, sub_id: &str, finding: &Finding) {
+        self.push("TX", &findings_code(), sub_id, &escape(&finding.to_string()), "");
+        for code in &finding.codes {
+            let meaning = code.meaning.as_deref().unwrap_or(&finding.description);
+            let scheme = scheme_designator(&code.scheme);
+            let value = format!("{}^{}^{}", escape(&code.code), escape(meaning), escape(&scheme));
+            self.push("CWE", "121071^Finding^DCM", sub_id, &value, "");
+        }
+        for measurement in &finding.measurements {
+            let name = match &measurement.dimension {
+                Some(dimension) => local(&escape(dimension), &escape(dimension)),
+                None => "410668003^Length^SCT".to_string(),
+            };
+            let unit = escape(&measurement.unit);
+            let units = format!("{}^{}^UCUM", unit, unit);
+            self.push("NM", &name, sub_id, &measurement.value.to_string(), &units);
+        }
+        if let Some(confidence) = finding.confidence {
+            self.confidence(sub_id, confidence);
+        }
+    }
+
+    fn confidence(&mut self, sub_id: &str, confidence: f32) {
+        self.push("NM", &local("CONFIDENCE", "Confidence"), sub_id, &confidence.to_string(), "");
+    }
+}
+
+fn findings_code() -> String {
+    format!("18782-3^Radiology Study observation (narrative)^{}", LOINC)
+}
+
+fn local(code: &str, name: &str) -> String {
+    format!("{}^{}^{}", code, name, LOCAL_CODES)
+}
+
+// A value already split into components with `^`, each escaped on its own
+fn components(value: &str) -> String {
+    value.split('^').map(escape).collect::<Vec<_>>().join("^")
+}
+
+/// A unique message control id: the current time followed by six random digits
+pub fn generate_control_id() -> String {
+    let random: u32 = rand::thread_rng().gen_range(0..1_000_000);
+    format!("{}{:06}", Utc::now().format("%Y%m%d%H%M%S"), random)
+}
+
+/// A positive acknowledgement from the receiver
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ack {
+    /// `AA` or `CA`
+    pub code: String,
+    /// The control id of the acknowledged message
+    pub control_id: String,
+    pub text: Option<String>,
+}
+
+/// What `MllpSender::forward` did with the results it was given
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Forwarded {
+    /// Results the receiver acknowledged
+    pub delivered: usize,
+    /// Results still not delivered after retrying
+    pub failed: usize,
+    /// Results recorded while `forward` had fallen too far behind to see them
+    pub missed: u64,
+}
+
+/// Sends HL7 messages over MLLP to a listener such as a RIS interface engine.
+///
+/// The connection is opened on first use and kept for later messages; after
+/// any failure the next message opens a new one. Each message waits for its
+/// acknowledgement: no answer in time is `RadiologyError::Timeout`, a lost
+/// connection `Transport`, and an `AE` / `AR` (or `CE` / `CR`) acknowledgement
+/// `Rejected` with the receiver's text.
+pub struct MllpSender {
+    address: String,
+    timeout: Duration,
+    connection: Mutex<Option<TcpStream>>,
+}
+
+impl MllpSender {
+    /// A sender for `host:port`
+    pub fn new(address: &str) -> Self {
+        MllpSender {
+            address: address.to_string(),
+            timeout: DEFAULT_ACK_TIMEOUT,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Deadline for connecting, sending and receiving the acknowledgement
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send one message and wait for its acknowledgement
+    pub async fn send(&self, message: &str) -> Result<Ack, RadiologyError> {
+        let mut connection = self.connection.lock().await;
+        let exchange = tokio::time::timeout(self.timeout, exchange(&self.address, &mut connection, message)).await;
+        let reply = match exchange {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(err)) => {
+                *connection = None;
+                return Err(RadiologyError::Transport(Box::new(err)));
+            }
+            Err(_) => {
+                *connection = None;
+                return Err(RadiologyError::Timeout(self.timeout));
+            }
+        };
+        drop(connection);
+
+        let ack = parse_ack(&reply)?;
+        let control_id = message.split(SEGMENT_SEPARATOR).next().and_then(|msh| msh.split('|').nth(9));
+        if control_id.is_some_and(|id| id != ack.control_id) {
+            return Err(RadiologyError::protocol(format!(
+                "acknowledgement is for message '{}', not '{}'",
+                ack.control_id,
+                control_id.unwrap_or_default()
+            )));
+        }
+        Ok(ack)
+    }
+
+    /// Send an ORU^R01 for every result the cluster records until it is dropped.
+    ///
+    /// `message` builds the message for each result, for instance
+    /// `|recorded| OruMessage::new(&recorded.result).metadata(&recorded.metadata)`.
+    /// Failed deliveries are retried per `retry`, then logged and skipped.
+    ///
+    /// Delivery is at most once: every result `forward` sees is sent until it
+    /// is acknowledged or the retries run out, and never again. Results that
+    /// failed, and those it missed by falling more than 256 results behind
+    /// the cluster, are only counted in the returned `Forwarded`; to catch up,
+    /// query them from the result store with `RadiologyCluster::query_results`.
+    pub async fn forward<F>(
+        &self,
+        mut results: broadcast::Receiver<RecordedResult>,
+        retry: &RetryPolicy,
+        message: F,
+    ) -> Forwarded
+    where
+        F: Fn(&RecordedResult) -> OruMessage,
+    {
+        let mut forwarded = Forwarded::default();
+        loop {
+            let recorded = match results.recv().await {
+                Ok(recorded) => recorded,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    println!("HL7 forwarding fell behind and skipped {} results", missed);
+                    forwarded.missed += missed;
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return forwarded,
+            };
+
+            let text = message(&recorded).render();
+            match retry.run(|_| self.send(&text)).await {
+                Ok(_) => forwarded.delivered += 1,
+                Err(err) => {
+                    println!("Failed to send HL7 result for image {}: {}", recorded.result.image_id, err);
+                    forwarded.failed += 1;
+                }
+            }
+        }
+    }
+}
+
+// Write one framed message and read the framed reply
+async fn exchange(address: &str, connection: &mut Option<TcpStream>, message: &str) -> std::io::Result<Vec<u8>> {
+    let stream = match connection.take() {
+        Some(stream) => stream,
+        None => TcpStream::connect(address).await?,
+    };
+    let stream = connection.insert(stream);
+
+    let mut frame = Vec::with_capacity(message.len() + 3);
+    frame.push(START_BLOCK);
+    frame.extend_from_slice(message.as_bytes());
+    frame.extend_from_slice(&[END_BLOCK, CARRIAGE_RETURN]);
+    stream.write_all(&frame).await?;
+
+    let mut reply = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        reply.extend_from_slice(&buffer[..read]);
+        if reply.ends_with(&[END_BLOCK, CARRIAGE_RETURN]) {
+            return Ok(reply);
+        }
+    }
+}
+
+fn parse_ack(reply: &[u8]) -> Result<Ack, RadiologyError> {
+    let body = reply
+        .strip_prefix(&[START_BLOCK])
+        .and_then(|body| body.strip_suffix(&[END_BLOCK, CARRIAGE_RETURN]))
+        .ok_or_else(|| RadiologyError::protocol("acknowledgement is not MLLP framed"))?;
+    let body = String::from_utf8_lossy(body);
+    let msa = body
+        .split(['\r', '\n'])
+        .find(|segment| segment.starts_with("MSA|"))
+        .ok_or_else(|| RadiologyError::protocol("acknowledgement has no MSA segment"))?;
+
+    let fields: Vec<&str> = msa.split('|').collect();
+    let field = |n: usize| fields.get(n).copied().unwrap_or_default();
+    let ack = Ack {
+        code: field(1).to_string(),
+        control_id: field(2).to_string(),
+        text: Some(field(3)).filter(|text| !text.is_empty()).map(str::to_string),
+    };
+    match ack.code.as_str() {
+        "AA" | "CA" => Ok(ack),
+        "AE" | "AR" | "CE" | "CR" => Err(RadiologyError::Rejected {
+            code: None,
+            message: format!("{}: {}", ack.code, ack.text.as_deref().unwrap_or("message not accepted")),
+        }),
+        code => Err(RadiologyError::protocol(format!("unknown acknowledgement code '{}'", code))),
+    }
+}
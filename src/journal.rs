@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dicom::DicomTags;
+use crate::queue::{JobId, Priority};
+use crate::store::StoreError;
+use crate::RadiologyImage;
+
+/// Journalled state of a job that hasn't finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalState {
+    Queued,
+    /// Submitted to the server; after a crash it is unknown whether the analysis happened
+    InFlight,
+}
+
+/// A job as written to the journal
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: JobId,
+    pub context_id: String,
+    pub priority: Priority,
+    pub image: RadiologyImage,
+    pub state: JournalState,
+}
+
+/// Write-ahead log of unfinished `JobQueue` jobs, backed by sled.
+///
+/// A job is written and flushed before `JobQueue::submit` returns its id, and
+/// removed once its result is recorded, so the entries left after a crash are
+/// exactly the jobs that still need to run.
+///
+/// Each job is stored under its id as up to three records: the job itself
+/// without the pixel data, the raw image bytes, and an empty marker once the
+/// job is in flight.
+#[derive(Clone)]
+pub struct JobJournal {
+    db: sled::Db,
+    jobs: sled::Tree,
+}
+
+// Suffixes after the job id; sled keeps a job's records together, in this order
+const JOB: u8 = 0;
+const IMAGE: u8 = 1;
+const IN_FLIGHT: u8 = 2;
+
+// The journalled job, less the image bytes
+#[derive(Serialize)]
+struct JobRecord<'a> {
+    context_id: &'a str,
+    priority: Priority,
+    image_id: &'a str,
+    metadata: &'a HashMap<String, String>,
+    dicom: &'a Option<DicomTags>,
+}
+
+#[derive(Deserialize)]
+struct StoredJob {
+    context_id: String,
+    priority: Priority,
+    image_id: String,
+    metadata: HashMap<String, String>,
+    dicom: Option<DicomTags>,
+}
+
+impl JobJournal {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_db(sled::open(path)?)
+    }
+
+    /// Keep the journal in an already opened database, e.g. the one holding results
+    pub fn from_db(db: sled::Db) -> Result<Self, StoreError> {
+        let jobs = db.open_tree("jobs")?;
+        Ok(JobJournal { db, jobs })
+    }
+
+    /// A job id never handed out before, across restarts
+    pub fn next_id(&self) -> Result<JobId, StoreError> {
+        Ok(JobId(self.db.generate_id()? + 1))
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> Result<(), StoreError> {
+        let record = JobRecord {
+            context_id: &entry.context_id,
+            priority: entry.priority,
+            image_id: &entry.image.image_id,
+            metadata: &entry.image.metadata,
+            dicom: &entry.image.dicom,
+        };
+        let mut batch = sled::Batch::default();
+        batch.insert(&key(entry.id, JOB), serde_json::to_vec(&record)?);
+        batch.insert(&key(entry.id, IMAGE), entry.image.data.as_slice());
+        match entry.state {
+            JournalState::InFlight => batch.insert(&key(entry.id, IN_FLIGHT), &[]),
+            JournalState::Queued => batch.remove(&key(entry.id, IN_FLIGHT)),
+        }
+        self.jobs.apply_batch(batch)?;
+        self.jobs.flush()?;
+        Ok(())
+    }
+
+    pub fn mark_in_flight(&self, id: JobId) -> Result<(), StoreError> {
+        if !self.jobs.contains_key(key(id, JOB))? {
+            return Ok(());
+        }
+        self.jobs.insert(key(id, IN_FLIGHT), &[])?;
+        self.jobs.flush()?;
+        Ok(())
+    }
+
+    /// Forget a job whose outcome has been recorded
+    pub fn finish(&self, id: JobId) -> Result<(), StoreError> {
+        let mut batch = sled::Batch::default();
+        for suffix in [JOB, IMAGE, IN_FLIGHT] {
+            batch.remove(&key(id, suffix));
+        }
+        self.jobs.apply_batch(batch)?;
+        self.jobs.flush()?;
+        Ok(())
+    }
+
+    /// Every unfinished job, oldest first
+    pub fn pending(&self) -> Result<Vec<JournalEntry>, StoreError> {
+        let mut entries: Vec<JournalEntry> = Vec::new();
+        for record in self.jobs.iter() {
+            let (key, value) = record?;
+            let (id, suffix) = split(&key)?;
+            if suffix == JOB {
+                let job: StoredJob = serde_json::from_slice(&value)?;
+                entries.push(JournalEntry {
+                    id,
+                    context_id: job.context_id,
+                    priority: job.priority,
+                    image: RadiologyImage {
+                        image_id: job.image_id,
+                        data: Vec::new(),
+                        metadata: job.metadata,
+                        dicom: job.dicom,
+                    },
+                    state: JournalState::Queued,
+                });
+                continue;
+            }
+            // The other records follow their job's; one without a job is left over and skipped
+            let Some(entry) = entries.last_mut().filter(|entry| entry.id == id) else {
+                continue;
+            };
+            match suffix {
+                IMAGE => entry.image.data = value.to_vec(),
+                IN_FLIGHT => entry.state = JournalState::InFlight,
+                _ => return Err(StoreError::Corrupt(format!("unknown journal record {} for {}", suffix, id))),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+// Big-endian so sled's key order is job order
+fn key(id: JobId, suffix: u8) -> [u8; 9] {
+    let mut key = [suffix; 9];
+    key[..8].copy_from_slice(&id.0.to_be_bytes());
+    key
+}
+
+fn split(key: &[u8]) -> Result<(JobId, u8), StoreError> {
+    match key {
+        [id @ .., suffix] if id.len() == 8 => Ok((JobId(u64::from_be_bytes(id.try_into().unwrap())), *suffix)),
+        _ => Err(StoreError::Corrupt(format!("malformed journal key {:?}", key))),
+    }
+}
@@ -1,92 +1,897 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 use serde::{Serialize, Deserialize};
-use mcp_rust_sdk::client::Client;
 use serde_json::Value;
 
+pub mod backend;
+pub mod client;
+pub mod connect;
+pub mod context;
+pub mod conversation;
+pub mod decode;
+pub mod deid;
+pub mod dicom;
+pub mod error;
+pub mod findings;
+pub mod hl7;
+pub mod journal;
+pub mod mock;
+pub mod pool;
+pub mod prompt;
+pub mod protocol;
+pub mod queue;
+pub mod retry;
+pub mod sr;
+pub mod store;
+pub mod study;
+pub mod transfer;
+
+pub use client::McpClient;
+pub use error::RadiologyError;
+pub use study::{RadiologySeries, RadiologyStudy};
+
+use backend::{Backend, BackendRegistry, DEFAULT_BACKEND};
+use chrono::Utc;
+use context::{Context, ContextConfig, ContextInfo};
+use pool::{BackendPool, Lease};
+use prompt::{PromptLibrary, PromptSource, PromptTemplate};
+use decode::{DefaultResponseDecoder, ResponseDecoder};
+use deid::{DeidPolicy, Deidentifier, IMAGE_ID_KEY};
+use futures_util::future::try_join_all;
+use retry::RetryPolicy;
+use store::{MemoryResultStore, ResultQuery, ResultStore};
+use study::{SeriesResult, StudyResult, StudySubmission, SERIES_UID_KEY, STUDY_UID_KEY};
+use tokio::io::AsyncRead;
+use tokio::sync::{broadcast, Notify};
+use transfer::TransferConfig;
+
+/// MCP tool invoked for analysis unless `RadiologyCluster::with_analysis_tool` says otherwise
+pub const DEFAULT_ANALYSIS_TOOL: &str = "analyze_image";
+
+/// Deadline for each submission unless `RadiologyCluster::with_default_timeout` or the context sets another
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Results a slow subscriber can fall behind by before it misses some
+const RECORDED_RESULTS_CAPACITY: usize = 256;
+
 // Publicly export structs for testing
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RadiologyImage {
     pub image_id: String,
     pub data: Vec<u8>,
     pub metadata: HashMap<String, String>,
+    /// Typed tags when the image was loaded from a DICOM file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dicom: Option<dicom::DicomTags>,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct RadiologyResult {
     pub image_id: String,
     pub findings: String,
     pub confidence_score: f32,
     pub analysis_date: String,
+    /// Study and series the analyzed image belongs to, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub study_instance_uid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_instance_uid: Option<String>,
+    /// Structured findings, when the server returned them; `findings` then holds their summary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<findings::FindingsReport>,
 }
 
-// Define our own simplified message types to use for the analysis
-#[derive(Clone, Serialize, Deserialize)]
+/// A result just written to the result store, as seen by `RadiologyCluster::subscribe_results`
+#[derive(Clone, Debug)]
+pub struct RecordedResult {
+    pub context_id: String,
+    pub result: RadiologyResult,
+    /// Metadata of the analyzed image, or of the series for series results, before de-identification
+    pub metadata: HashMap<String, String>,
+}
+
+/// One turn of the conversation about an analyzed image; `role` is `user` or `assistant`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AnalysisMessage {
     pub role: String,
     pub content: String,
 }
 
-// The RadiologyCluster for managing radiology processing through MCP
+impl AnalysisMessage {
+    pub fn user(content: &str) -> Self {
+        AnalysisMessage {
+            role: conversation::USER.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        AnalysisMessage {
+            role: conversation::ASSISTANT.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+// Where a submission goes: the context's model on the pool member leased for it,
+// with the prompt and de-identification the context asks for
+struct Route {
+    model: String,
+    lease: Lease,
+    prompt: PromptSource,
+    deid: Arc<Deidentifier>,
+}
+
+impl Route {
+    // The image id as the server sees it
+    fn outgoing_id(&self, image_id: &str) -> String {
+        self.deid.deidentify_value(IMAGE_ID_KEY, image_id).unwrap_or_default()
+    }
+}
+
+// Counts a submission as in flight for its context until dropped, so
+// `close_context` can wait for it
+struct Activity<'a> {
+    cluster: &'a RadiologyCluster,
+    context_id: String,
+}
+
+impl Drop for Activity<'_> {
+    fn drop(&mut self) {
+        if let Some(context) = self.cluster.contexts.write().unwrap().get_mut(&self.context_id) {
+            context.in_flight -= 1;
+            context.touch();
+        }
+        self.cluster.drained.notify_waiters();
+    }
+}
+
+// How an analysis result is written to the result store
+#[derive(Clone, Copy)]
+enum Recording {
+    Append,
+    // Keep the first result per image id; a repeated analysis returns the stored one
+    OncePerImage,
+}
+
+// The RadiologyCluster for managing radiology processing through MCP.
+// It is Send + Sync and never holds a lock across an await, so submissions
+// can be driven concurrently from spawned tasks. Each context is routed to a
+// named backend; the client given to the constructor is the default one.
 pub struct RadiologyCluster {
-    client: Arc<Client>,
-    contexts: Mutex<HashMap<String, String>>, // Store context IDs
+    backends: BackendRegistry,
+    contexts: RwLock<HashMap<String, Context>>,
+    // Signalled whenever a submission finishes, for contexts being closed
+    drained: Notify,
+    results: Arc<dyn ResultStore>,
+    decoder: Arc<dyn ResponseDecoder>,
+    transfer: TransferConfig,
+    deid: Arc<Deidentifier>,
+    analysis_tool: String,
+    prompts: Arc<PromptLibrary>,
+    retry: RetryPolicy,
+    timeout: Option<Duration>,
+    recorded: broadcast::Sender<RecordedResult>,
 }
 
 impl RadiologyCluster {
-    pub fn new(client: Arc<Client>) -> Self {
+    pub fn new(client: Arc<McpClient>) -> Self {
+        Self::with_store(client, Arc::new(MemoryResultStore::new()))
+    }
+
+    pub fn with_store(client: Arc<McpClient>, results: Arc<dyn ResultStore>) -> Self {
+        let backends = BackendRegistry::new();
+        backends.register(DEFAULT_BACKEND, client);
         RadiologyCluster {
-            client,
-            contexts: Mutex::new(HashMap::new()),
+            backends,
+            contexts: RwLock::new(HashMap::new()),
+            drained: Notify::new(),
+            results,
+            decoder: Arc::new(DefaultResponseDecoder),
+            transfer: TransferConfig::default(),
+            deid: Arc::new(Deidentifier::new(DeidPolicy::default())),
+            analysis_tool: DEFAULT_ANALYSIS_TOOL.to_string(),
+            prompts: Arc::new(PromptLibrary::new()),
+            retry: RetryPolicy::none(),
+            timeout: Some(DEFAULT_TIMEOUT),
+            recorded: broadcast::Sender::new(RECORDED_RESULTS_CAPACITY),
         }
     }
 
-    pub async fn initialize_context(&self, context_id: &str, model_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // Store the mapping of our logical context ID to the model name
-        self.contexts.lock().unwrap().insert(context_id.to_string(), model_name.to_string());
-        println!("Initialized mapping for context '{}' to model '{}'", context_id, model_name);
-        
-        Ok(())
+    /// Replace the decoder used to turn server responses into results
+    pub fn with_decoder(mut self, decoder: Arc<dyn ResponseDecoder>) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Replace the size limits used when sending image bytes
+    pub fn with_transfer_config(mut self, transfer: TransferConfig) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    /// Replace the de-identification rules applied before anything is sent to the server
+    pub fn with_deid_policy(mut self, policy: DeidPolicy) -> Self {
+        self.deid = Arc::new(Deidentifier::new(policy));
+        self
+    }
+
+    /// Name of the MCP tool invoked to analyze images
+    pub fn with_analysis_tool(mut self, name: &str) -> Self {
+        self.analysis_tool = name.to_string();
+        self
+    }
+
+    /// Share a library of prompt templates, e.g. between clusters
+    pub fn with_prompt_library(mut self, prompts: Arc<PromptLibrary>) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Retry failed `submit_image` calls under `policy`; by default each is attempted once
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Deadline for submissions to contexts without one of their own; `DEFAULT_TIMEOUT` by default
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Let submissions to contexts without a deadline of their own wait for an answer indefinitely
+    pub fn without_default_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// The de-identifier holding the local mapping back to real identifiers
+    pub fn deidentifier(&self) -> &Deidentifier {
+        &self.deid
+    }
+
+    /// The de-identifier a context's submissions go through: its own if it has a policy, else the cluster's
+    pub fn context_deidentifier(&self, context_id: &str) -> Result<Arc<Deidentifier>, RadiologyError> {
+        self.with_context(context_id, |context| context.deid.clone().unwrap_or_else(|| self.deid.clone()))
+    }
+
+    /// Register `client` as the backend called `name`, replacing any backend of that name
+    pub fn register_backend(&self, name: &str, client: Arc<McpClient>) {
+        self.backends.register(name, client);
+        println!("Registered backend '{}'", name);
     }
 
-    pub async fn submit_image(&self, context_id: &str, image: RadiologyImage) -> Result<String, Box<dyn std::error::Error>> {
-        let contexts = self.contexts.lock().unwrap();
-        let model_name = contexts.get(context_id).ok_or("Context not found")?;
+    /// Register a pool of interchangeable backends under the pool's name
+    pub fn register_pool(&self, pool: BackendPool) {
+        let name = pool.name().to_string();
+        self.backends.register_pool(pool);
+        println!("Registered backend pool '{}'", name);
+    }
+
+    /// The prompt templates contexts can refer to by name
+    pub fn prompts(&self) -> &PromptLibrary {
+        &self.prompts
+    }
 
-        // Create a message to send via the client
-        let prompt = format!(
-            "You are a radiology analysis system. Analyze the following medical image:\n\n{}",
-            serde_json::to_string(&image.metadata)?
+    /// The registry of backends contexts can be routed to
+    pub fn backends(&self) -> &BackendRegistry {
+        &self.backends
+    }
+
+    /// Bind a context to `model_name` on the default backend
+    pub async fn initialize_context(&self, context_id: &str, model_name: &str) -> Result<(), RadiologyError> {
+        self.initialize_context_on(context_id, model_name, DEFAULT_BACKEND).await
+    }
+
+    /// Bind a context to `model_name` on the backend registered as `backend`
+    pub async fn initialize_context_on(&self, context_id: &str, model_name: &str, backend: &str) -> Result<(), RadiologyError> {
+        self.create_context(context_id, ContextConfig::new(model_name).backend(backend))
+    }
+
+    /// Create a context; fails with `ContextExists` if one with this id is live
+    pub fn create_context(&self, context_id: &str, config: ContextConfig) -> Result<(), RadiologyError> {
+        validate(&config, &self.backends)?;
+
+        let mut contexts = self.contexts.write().unwrap();
+        if contexts.get(context_id).is_some_and(|context| !context.is_expired(Utc::now())) {
+            return Err(RadiologyError::ContextExists(context_id.to_string()));
+        }
+        println!(
+            "Initialized mapping for context '{}' to model '{}' on backend '{}'",
+            context_id, config.model, config.backend
         );
-        
-        // Create the message payload as a JSON string
-        let message_data = serde_json::json!({
-            "model": model_name,
+        contexts.insert(context_id.to_string(), Context::new(config));
+        Ok(())
+    }
+
+    /// A snapshot of a context's configuration and usage
+    pub fn context(&self, context_id: &str) -> Result<ContextInfo, RadiologyError> {
+        self.with_context(context_id, |context| context.info(context_id))
+    }
+
+    /// Every live context, ordered by id
+    pub fn list_contexts(&self) -> Vec<ContextInfo> {
+        self.expire_contexts();
+        let contexts = self.contexts.read().unwrap();
+        let mut infos: Vec<ContextInfo> = contexts.iter().map(|(id, context)| context.info(id)).collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        infos
+    }
+
+    /// Replace a context's configuration. Submissions already in flight finish
+    /// with the old one; a new de-identification policy starts a fresh mapping.
+    pub fn update_context(&self, context_id: &str, config: ContextConfig) -> Result<(), RadiologyError> {
+        validate(&config, &self.backends)?;
+        self.with_context_mut(context_id, |context| context.reconfigure(config))?;
+        println!("Updated context '{}'", context_id);
+        Ok(())
+    }
+
+    /// Point a context at another model
+    pub fn set_context_model(&self, context_id: &str, model_name: &str) -> Result<(), RadiologyError> {
+        self.with_context_mut(context_id, |context| context.config.model = model_name.to_string())
+    }
+
+    /// Set the default deadline for submissions to a context; `None` uses the cluster-wide default
+    pub fn set_context_timeout(&self, context_id: &str, timeout: Option<Duration>) -> Result<(), RadiologyError> {
+        self.with_context_mut(context_id, |context| context.config.timeout = timeout)
+    }
+
+    /// Close a context once its in-flight submissions have finished.
+    ///
+    /// New submissions are refused with `ContextClosed` straight away. The
+    /// context's stored results are kept. Jobs still waiting in a `JobQueue`
+    /// are drained by closing through `JobQueue::close_context` instead.
+    pub async fn close_context(&self, context_id: &str) -> Result<ContextInfo, RadiologyError> {
+        self.with_context_mut(context_id, |context| context.closing = true)?;
+        println!("Closing context '{}'", context_id);
+        loop {
+            // Register for a wakeup before checking, so a submission finishing in between isn't missed
+            let drained = self.drained.notified();
+            {
+                let mut contexts = self.contexts.write().unwrap();
+                match contexts.get(context_id) {
+                    Some(context) if context.in_flight == 0 => {
+                        let context = contexts.remove(context_id).expect("context was just found");
+                        println!("Closed context '{}'", context_id);
+                        return Ok(context.info(context_id));
+                    }
+                    Some(_) => {}
+                    // Closed by a concurrent call
+                    None => return Err(RadiologyError::UnknownContext(context_id.to_string())),
+                }
+            }
+            drained.await;
+        }
+    }
+
+    /// Remove contexts that have been idle longer than their TTL and return their ids.
+    ///
+    /// Expired contexts already behave as unknown; this frees them.
+    pub fn expire_contexts(&self) -> Vec<String> {
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        self.contexts.write().unwrap().retain(|id, context| {
+            if context.is_expired(now) {
+                println!("Context '{}' expired", id);
+                expired.push(id.clone());
+                return false;
+            }
+            true
+        });
+        expired.sort();
+        expired
+    }
+
+    /// Analyze an image within the context's deadline, if it has one.
+    ///
+    /// A submission that runs out of time fails with `RadiologyError::Timeout`
+    /// and the server is told to cancel the outstanding request. With a retry
+    /// policy, the deadline applies to each attempt.
+    pub async fn submit_image(&self, context_id: &str, image: RadiologyImage) -> Result<RadiologyResult, RadiologyError> {
+        let _activity = self.activity(context_id)?;
+        let timeout = self.timeout_for(context_id)?;
+        self.submit_image_within(context_id, image, timeout, Recording::Append).await
+    }
+
+    /// Like `submit_image`, but with `timeout` in place of the context's deadline
+    pub async fn submit_image_with_timeout(
+        &self,
+        context_id: &str,
+        image: RadiologyImage,
+        timeout: Duration,
+    ) -> Result<RadiologyResult, RadiologyError> {
+        let _activity = self.activity(context_id)?;
+        self.submit_image_within(context_id, image, Some(timeout), Recording::Append).await
+    }
+
+    // Submit on behalf of a durable job queue, which may run a job again after a crash
+    pub(crate) async fn submit_image_recorded_once(&self, context_id: &str, image: RadiologyImage) -> Result<RadiologyResult, RadiologyError> {
+        let _activity = self.activity(context_id)?;
+        let timeout = self.timeout_for(context_id)?;
+        self.submit_image_within(context_id, image, timeout, Recording::OncePerImage).await
+    }
+
+    // Every attempt leases a pool member of its own, so a retry can land on a different server
+    async fn submit_image_within(
+        &self,
+        context_id: &str,
+        image: RadiologyImage,
+        timeout: Option<Duration>,
+        recording: Recording,
+    ) -> Result<RadiologyResult, RadiologyError> {
+        self.retry
+            .run(|_| async {
+                let route = self.route(context_id)?;
+                let result = within(timeout, self.submit_image_once(context_id, &route, &image, recording)).await;
+                route.lease.record(&result);
+                result
+            })
+            .await
+    }
+
+    async fn submit_image_once(
+        &self,
+        context_id: &str,
+        route: &Route,
+        image: &RadiologyImage,
+        recording: Recording,
+    ) -> Result<RadiologyResult, RadiologyError> {
+        // DICOM images carry their study/series links in typed tags; make sure they reach the result
+        let mut metadata = image.metadata.clone();
+        for key in [STUDY_UID_KEY, SERIES_UID_KEY] {
+            if let Some(uid) = study::image_uid(image, key) {
+                metadata.insert(key.to_string(), uid);
+            }
+        }
+
+        let mut budget = self.transfer.inline_limit;
+        let content = self.image_content(route, image, &mut budget).await?;
+        let params = serde_json::json!({ "image": content });
+        self.analyze(context_id, route, &image.image_id, &metadata, params, recording).await
+    }
+
+    /// Analyze every series of a study and link the results to its StudyInstanceUID.
+    ///
+    /// Series-level results are stored with the series UID as their image id, so
+    /// `get_study_results` returns them alongside any per-image results.
+    pub async fn submit_study(
+        &self,
+        context_id: &str,
+        study: &RadiologyStudy,
+        submission: StudySubmission,
+    ) -> Result<StudyResult, RadiologyError> {
+        let _activity = self.activity(context_id)?;
+        let timeout = self.timeout_for(context_id)?;
+        let study_uid = &study.study_instance_uid;
+        let mut series_results = Vec::new();
+
+        for series in &study.series {
+            let series_uid = &series.series_instance_uid;
+            let series_result = match submission {
+                StudySubmission::PerSeries => {
+                    let result = self
+                        .retry
+                        .run(|_| async {
+                            let route = self.route(context_id)?;
+                            let result = within(timeout, self.submit_series_once(context_id, &route, study_uid, series)).await;
+                            route.lease.record(&result);
+                            result
+                        })
+                        .await?;
+                    SeriesResult {
+                        series_instance_uid: series_uid.clone(),
+                        result,
+                        image_results: Vec::new(),
+                    }
+                }
+                StudySubmission::FanOut => {
+                    let submissions = series.images.iter().map(|image| {
+                        let mut image = image.clone();
+                        image.metadata.insert(STUDY_UID_KEY.to_string(), study_uid.clone());
+                        image.metadata.insert(SERIES_UID_KEY.to_string(), series_uid.clone());
+                        self.submit_image_within(context_id, image, timeout, Recording::Append)
+                    });
+                    let image_results = try_join_all(submissions).await?;
+                    let result = study::aggregate(study_uid, series_uid, &image_results);
+                    self.results.insert(context_id, &result)?;
+                    self.publish(context_id, &result, &series.metadata(study_uid));
+                    SeriesResult {
+                        series_instance_uid: series_uid.clone(),
+                        result,
+                        image_results,
+                    }
+                }
+            };
+            series_results.push(series_result);
+        }
+
+        Ok(StudyResult {
+            study_instance_uid: study_uid.clone(),
+            series: series_results,
+        })
+    }
+
+    // The whole series in one analysis request. Its images share the inline limit,
+    // so past it they are uploaded and the request stays small
+    async fn submit_series_once(
+        &self,
+        context_id: &str,
+        route: &Route,
+        study_uid: &str,
+        series: &RadiologySeries,
+    ) -> Result<RadiologyResult, RadiologyError> {
+        let mut inline_budget = self.transfer.inline_limit;
+        let mut images = Vec::new();
+        for image in &series.images {
+            images.push(self.image_content(route, image, &mut inline_budget).await?);
+        }
+        let params = serde_json::json!({ "images": images });
+        let metadata = series.metadata(study_uid);
+        let series_uid = &series.series_instance_uid;
+        self.analyze(context_id, route, series_uid, &metadata, params, Recording::Append).await
+    }
+
+    // Small images travel inline while `inline_budget` lasts, which they use up; anything
+    // larger goes through a chunked upload first
+    async fn image_content(&self, route: &Route, image: &RadiologyImage, inline_budget: &mut usize) -> Result<Value, RadiologyError> {
+        let size = image.data.len() as u64;
+        if size > self.transfer.max_image_bytes {
+            return Err(RadiologyError::ImageTooLarge {
+                size,
+                limit: self.transfer.max_image_bytes,
+            });
+        }
+
+        let mime_type = transfer::mime_type(&image.metadata);
+        if image.data.len() <= *inline_budget {
+            *inline_budget -= image.data.len();
+            return Ok(transfer::inline_content(&image.data, mime_type));
+        }
+
+        let upload_id = transfer::new_upload_id(&route.outgoing_id(&image.image_id));
+        transfer::upload(route.lease.backend().client(), &upload_id, image.data.as_slice(), &self.transfer).await?;
+        Ok(transfer::upload_content(&upload_id, mime_type, size))
+    }
+
+    /// Submit an image whose bytes are read from `reader` rather than held in memory.
+    ///
+    /// The data is always sent as a chunked upload, so multi-hundred-megabyte
+    /// series can be analyzed without buffering them first.
+    pub async fn submit_image_stream<R>(
+        &self,
+        context_id: &str,
+        image_id: &str,
+        metadata: HashMap<String, String>,
+        reader: R,
+    ) -> Result<RadiologyResult, RadiologyError>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let _activity = self.activity(context_id)?;
+        let route = self.route(context_id)?;
+
+        let submission = async {
+            let upload_id = transfer::new_upload_id(&route.outgoing_id(image_id));
+            let size = transfer::upload(route.lease.backend().client(), &upload_id, reader, &self.transfer).await?;
+            let content = transfer::upload_content(&upload_id, transfer::mime_type(&metadata), size);
+
+            let params = serde_json::json!({ "image": content });
+            self.analyze(context_id, &route, image_id, &metadata, params, Recording::Append).await
+        };
+        let result = within(self.timeout_for(context_id)?, submission).await;
+        route.lease.record(&result);
+        result
+    }
+
+    // Look up a live context; one idle past its TTL counts as gone
+    fn with_context<T>(&self, context_id: &str, f: impl FnOnce(&Context) -> T) -> Result<T, RadiologyError> {
+        let contexts = self.contexts.read().unwrap();
+        match contexts.get(context_id) {
+            Some(context) if !context.is_expired(Utc::now()) => Ok(f(context)),
+            _ => Err(RadiologyError::UnknownContext(context_id.to_string())),
+        }
+    }
+
+    fn with_context_mut<T>(&self, context_id: &str, f: impl FnOnce(&mut Context) -> T) -> Result<T, RadiologyError> {
+        let mut contexts = self.contexts.write().unwrap();
+        match contexts.get_mut(context_id) {
+            Some(context) if !context.is_expired(Utc::now()) => Ok(f(context)),
+            _ => Err(RadiologyError::UnknownContext(context_id.to_string())),
+        }
+    }
+
+    // Start a submission, unless the context is closing
+    fn activity(&self, context_id: &str) -> Result<Activity<'_>, RadiologyError> {
+        self.with_context_mut(context_id, |context| {
+            if context.closing {
+                return Err(RadiologyError::ContextClosed(context_id.to_string()));
+            }
+            context.in_flight += 1;
+            context.touch();
+            Ok(())
+        })??;
+        Ok(Activity {
+            cluster: self,
+            context_id: context_id.to_string(),
+        })
+    }
+
+    // Resolve the backend on every submission so re-registering a backend takes effect,
+    // and lease a pool member that counts the submission as outstanding until it finishes
+    fn route(&self, context_id: &str) -> Result<Route, RadiologyError> {
+        let (model, backend, prompt, deid) = self.with_context(context_id, |context| {
+            (
+                context.config.model.clone(),
+                context.config.backend.clone(),
+                context.config.prompt.clone(),
+                context.deid.clone().unwrap_or_else(|| self.deid.clone()),
+            )
+        })?;
+        Ok(Route {
+            model,
+            lease: self.backends.get(&backend)?.acquire()?,
+            prompt,
+            deid,
+        })
+    }
+
+    // Name of the backend a context is bound to; closing contexts take no new work
+    pub(crate) fn backend_of(&self, context_id: &str) -> Result<String, RadiologyError> {
+        self.with_context(context_id, |context| {
+            if context.closing {
+                return Err(RadiologyError::ContextClosed(context_id.to_string()));
+            }
+            Ok(context.config.backend.clone())
+        })?
+    }
+
+    fn timeout_for(&self, context_id: &str) -> Result<Option<Duration>, RadiologyError> {
+        self.with_context(context_id, |context| context.config.timeout.or(self.timeout))
+    }
+
+    // Check that the backend's server offers the analysis tool before the first call
+    async fn ensure_analysis_tool(&self, backend: &Backend) -> Result<(), RadiologyError> {
+        let session = backend.client().initialize().await?;
+        if session.capabilities.tools.is_none() {
+            return Err(RadiologyError::protocol(format!(
+                "server '{}' does not support tools",
+                session.server_info.name
+            )));
+        }
+
+        let tools = backend.tools().await?;
+        if !tools.iter().any(|tool| tool.name == self.analysis_tool) {
+            return Err(RadiologyError::protocol(format!(
+                "backend '{}' does not offer the '{}' tool",
+                backend.name(),
+                self.analysis_tool
+            )));
+        }
+        Ok(())
+    }
+
+    // Render the context's prompt for one image from its de-identified metadata
+    async fn prompt(&self, route: &Route, metadata: &HashMap<String, String>) -> Result<String, RadiologyError> {
+        let variables = prompt::variables(metadata)?;
+        match &route.prompt {
+            PromptSource::Inline(text) => Ok(PromptTemplate::parse("inline", 0, text)?.render(&variables)),
+            PromptSource::Library { name, version } => Ok(self.prompts.get(name, *version)?.render(&variables)),
+            PromptSource::Server { name } => {
+                let client = route.lease.backend().client();
+                let session = client.initialize().await?;
+                if session.capabilities.prompts.is_none() {
+                    return Err(RadiologyError::protocol(format!(
+                        "server '{}' does not support prompts",
+                        session.server_info.name
+                    )));
+                }
+                let arguments = variables.into_iter().filter(|(_, value)| !value.is_empty()).collect();
+                let text = client.get_prompt(name, &arguments).await?.text();
+                if text.is_empty() {
+                    return Err(RadiologyError::protocol(format!("prompt '{}' has no text", name)));
+                }
+                Ok(text)
+            }
+        }
+    }
+
+    async fn analyze(
+        &self,
+        context_id: &str,
+        route: &Route,
+        image_id: &str,
+        metadata: &HashMap<String, String>,
+        image_arguments: Value,
+        recording: Recording,
+    ) -> Result<RadiologyResult, RadiologyError> {
+        self.ensure_analysis_tool(route.lease.backend()).await?;
+
+        // Only de-identified metadata and ids leave the process
+        let metadata_out = route.deid.deidentify_metadata(metadata);
+        let prompt = self.prompt(route, &metadata_out).await?;
+
+        // The image data itself travels as MCP content blocks under `image` or `images`
+        let mut arguments = serde_json::json!({
+            "model": route.model,
             "prompt": prompt,
-            "image_id": image.image_id
+            "image_id": route.outgoing_id(image_id),
+            "metadata": metadata_out,
         });
-        
-        // Convert to string - the client.request expects a &str
-        let message_str = message_data.to_string();
-        
-        // The request method requires an Option<Value> as second parameter
-        let options: Option<Value> = None;
-        
-        // Pass the message string directly to request
-        let response = self.client.request(&message_str, options).await?;
-        
-        // Convert response to string
-        let response_str = response.to_string();
-        println!("Processed image {}: {}", image.image_id, response_str);
-        
-        Ok(response_str)
-    }
-
-    pub async fn get_results(&self, context_id: &str) -> Result<Vec<RadiologyResult>, Box<dyn std::error::Error>> {
-        // In a real implementation, you would retrieve stored results
+        if let (Some(arguments), Value::Object(images)) = (arguments.as_object_mut(), image_arguments) {
+            arguments.extend(images);
+        }
+
+        let output = route.lease.backend().client().call_tool(&self.analysis_tool, arguments).await?;
+        let response = output
+            .structured()
+            .ok_or_else(|| RadiologyError::protocol("analysis tool returned no structured result"))?;
+
+        println!("Processed image {}: {}", image_id, response);
+
+        check_status(&response)?;
+        let mut result = self.decoder.decode(image_id, &response)?;
+        result.study_instance_uid = metadata.get(STUDY_UID_KEY).cloned();
+        result.series_instance_uid = metadata.get(SERIES_UID_KEY).cloned();
+        match recording {
+            Recording::Append => self.results.insert(context_id, &result)?,
+            Recording::OncePerImage => {
+                if !self.results.insert_once(context_id, &result)? {
+                    let query = ResultQuery::for_context(context_id).image_id(image_id);
+                    if let Some(stored) = self.results.query(&query)?.into_iter().next() {
+                        return Ok(stored);
+                    }
+                }
+            }
+        }
+        self.publish(context_id, &result, metadata);
+
+        // The read opens the conversation that follow-up questions continue
+        let exchange = [AnalysisMessage::user(&prompt), AnalysisMessage::assistant(&result.findings)];
+        self.results.append_messages(context_id, image_id, &exchange)?;
+
+        Ok(result)
+    }
+
+    /// Ask a follow-up question about an image the context has already analyzed.
+    ///
+    /// The question goes to the analysis tool as `prompt`, together with the
+    /// conversation so far under `messages`, trimmed to the context's history
+    /// budget. The image itself isn't sent again. The question and the answer
+    /// are added to the conversation.
+    pub async fn ask(&self, context_id: &str, image_id: &str, question: &str) -> Result<AnalysisMessage, RadiologyError> {
+        let _activity = self.activity(context_id)?;
+        let timeout = self.timeout_for(context_id)?;
+        let mut messages = self.results.conversation(context_id, image_id)?;
+        if messages.is_empty() {
+            return Err(RadiologyError::UnknownImage {
+                context_id: context_id.to_string(),
+                image_id: image_id.to_string(),
+            });
+        }
+        let question = AnalysisMessage::user(question);
+        messages.push(question.clone());
+        let budget = self.with_context(context_id, |context| context.config.history_tokens)?;
+        let messages = conversation::truncate(&messages, budget);
+
+        let answer = self
+            .retry
+            .run(|_| async {
+                let route = self.route(context_id)?;
+                let result = within(timeout, self.follow_up(&route, image_id, &messages)).await;
+                route.lease.record(&result);
+                result
+            })
+            .await?;
+        self.results.append_messages(context_id, image_id, &[question, answer.clone()])?;
+        Ok(answer)
+    }
+
+    /// The conversation about an image, starting with the prompt and findings of its analysis
+    pub async fn conversation(&self, context_id: &str, image_id: &str) -> Result<Vec<AnalysisMessage>, RadiologyError> {
+        Ok(self.results.conversation(context_id, image_id)?)
+    }
+
+    // The answer is the tool's `answer` or `findings` field, or its text output
+    async fn follow_up(&self, route: &Route, image_id: &str, messages: &[AnalysisMessage]) -> Result<AnalysisMessage, RadiologyError> {
+        self.ensure_analysis_tool(route.lease.backend()).await?;
+
+        let question = messages.last().map(|message| message.content.as_str()).unwrap_or_default();
+        let arguments = serde_json::json!({
+            "model": route.model,
+            "prompt": question,
+            "image_id": route.outgoing_id(image_id),
+            "messages": messages,
+        });
+        let output = route.lease.backend().client().call_tool(&self.analysis_tool, arguments).await?;
+        let answer = match output.structured() {
+            Some(response) => {
+                check_status(&response)?;
+                ["answer", "findings"]
+                    .iter()
+                    .find_map(|key| response.get(key).and_then(Value::as_str))
+                    .map(str::to_string)
+                    .ok_or_else(|| RadiologyError::protocol("follow-up answer has no 'answer' or 'findings'"))?
+            }
+            None => output.text(),
+        };
+
+        println!("Answered follow-up on image {}: {}", image_id, answer);
+        Ok(AnalysisMessage::assistant(&answer))
+    }
+
+    pub async fn get_results(&self, context_id: &str) -> Result<Vec<RadiologyResult>, RadiologyError> {
         println!("Retrieving results for context '{}'", context_id);
-        
-        // Simulate retrieving results
-        Ok(vec![])
+        self.query_results(&ResultQuery::for_context(context_id)).await
+    }
+
+    /// Every stored result for a study: per-image and series-level
+    pub async fn get_study_results(&self, context_id: &str, study_instance_uid: &str) -> Result<Vec<RadiologyResult>, RadiologyError> {
+        self.query_results(&ResultQuery::for_context(context_id).study(study_instance_uid)).await
+    }
+
+    pub async fn query_results(&self, query: &ResultQuery) -> Result<Vec<RadiologyResult>, RadiologyError> {
+        Ok(self.results.query(query)?)
+    }
+
+    /// Receive every result from now on as soon as it has been stored, e.g. to
+    /// forward it with `hl7::MllpSender::forward`.
+    ///
+    /// A subscriber that falls more than 256 results behind misses the oldest
+    /// ones and is told how many through `RecvError::Lagged`.
+    pub fn subscribe_results(&self) -> broadcast::Receiver<RecordedResult> {
+        self.recorded.subscribe()
+    }
+
+    fn publish(&self, context_id: &str, result: &RadiologyResult, metadata: &HashMap<String, String>) {
+        if self.recorded.receiver_count() > 0 {
+            // Nobody listening is not an error
+            let _ = self.recorded.send(RecordedResult {
+                context_id: context_id.to_string(),
+                result: result.clone(),
+                metadata: metadata.clone(),
+            });
+        }
+    }
+}
+
+// Check what a context configuration refers to before accepting it
+fn validate(config: &ContextConfig, backends: &BackendRegistry) -> Result<(), RadiologyError> {
+    if !backends.contains(&config.backend) {
+        return Err(RadiologyError::UnknownBackend(config.backend.clone()));
+    }
+    if let PromptSource::Inline(text) = &config.prompt {
+        PromptTemplate::parse("inline", 0, text)?;
+    }
+    Ok(())
+}
+
+// Some servers report failures as a successful JSON-RPC response with an error status
+fn check_status(response: &Value) -> Result<(), RadiologyError> {
+    match response.get("status").and_then(Value::as_str) {
+        Some(status) if status != "success" => {
+            let message = response.get("message").and_then(Value::as_str).unwrap_or(status);
+            Err(RadiologyError::Rejected {
+                code: None,
+                message: message.to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+// Run `future` under an optional deadline. Dropping it on expiry cancels any request it has in flight
+async fn within<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = Result<T, RadiologyError>>,
+) -> Result<T, RadiologyError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| RadiologyError::Timeout(timeout))?,
+        None => future.await,
     }
 }
@@ -1,174 +1,93 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::env;
-use mcp_rust_sdk::client::Client;
-use mcp_rust_sdk::transport::websocket::WebSocketTransport;
-use tokio_tungstenite::MaybeTlsStream;
-use tokio::net::TcpStream;
-use serde_json::Value;
 use std::collections::HashMap;
-use serde::{Serialize, Deserialize};
-use std::time::Duration;
 
-// Structure to represent a radiology image
-#[derive(Clone, Serialize, Deserialize)]
-struct RadiologyImage {
-    image_id: String,
-    data: Vec<u8>,
-    metadata: HashMap<String, String>,
-}
-
-// Structure to represent a radiological analysis result
-#[derive(Clone, Serialize, Deserialize)]
-struct RadiologyResult {
-    image_id: String,
-    findings: String,
-    confidence_score: f32,
-    analysis_date: String,
-}
-
-// Define our own simplified message types to use for the analysis
-#[derive(Clone, Serialize, Deserialize)]
-struct AnalysisMessage {
-    role: String,
-    content: String,
-}
-
-// The RadiologyCluster for managing radiology processing through MCP
-struct RadiologyCluster {
-    client: Arc<Client>,
-    contexts: Mutex<HashMap<String, String>>, // Store context IDs
-}
-
-impl RadiologyCluster {
-    fn new(client: Arc<Client>) -> Self {
-        RadiologyCluster {
-            client,
-            contexts: Mutex::new(HashMap::new()),
-        }
-    }
-
-    async fn initialize_context(&self, context_id: &str, model_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // Store the mapping of our logical context ID to the model name
-        self.contexts.lock().unwrap().insert(context_id.to_string(), model_name.to_string());
-        println!("Initialized mapping for context '{}' to model '{}'", context_id, model_name);
-        
-        Ok(())
-    }
-
-    async fn submit_image(&self, context_id: &str, image: RadiologyImage) -> Result<String, Box<dyn std::error::Error>> {
-        let contexts = self.contexts.lock().unwrap();
-        let model_name = contexts.get(context_id).ok_or("Context not found")?;
-
-        // Create a message to send via the client
-        let prompt = format!(
-            "You are a radiology analysis system. Analyze the following medical image:\n\n{}",
-            serde_json::to_string(&image.metadata)?
-        );
-        
-        // Create the message payload as a JSON string
-        let message_data = serde_json::json!({
-            "model": model_name,
-            "prompt": prompt,
-            "image_id": image.image_id
-        });
-        
-        // Convert to string - the client.request expects a &str
-        let message_str = message_data.to_string();
-        
-        // The request method requires an Option<Value> as second parameter
-        let options: Option<Value> = None;  // You might need to customize this based on your SDK
-        
-        // Pass the message string directly to request
-        let response = self.client.request(&message_str, options).await?;
-        
-        // Convert response to string
-        let response_str = response.to_string();
-        println!("Processed image {}: {}", image.image_id, response_str);
-        
-        Ok(response_str)
-    }
-
-    async fn get_results(&self, context_id: &str) -> Result<Vec<RadiologyResult>, Box<dyn std::error::Error>> {
-        // In a real implementation, you would retrieve stored results
-        println!("Retrieving results for context '{}'", context_id);
-        
-        // Simulate retrieving results
-        Ok(vec![])
-    }
-}
-
-// Define the connection retry function only in main.rs
-async fn connect_with_retry(url: &str, max_retries: u32, delay: Duration) 
-    -> Result<WebSocketTransport<MaybeTlsStream<TcpStream>>, Box<dyn std::error::Error>> {
-    let mut attempts = 0;
-    
-    loop {
-        attempts += 1;
-        println!("Connection attempt {}/{}", attempts, max_retries);
-        
-        match WebSocketTransport::new(url).await {
-            Ok(transport) => return Ok(transport),
-            Err(e) => {
-                if attempts >= max_retries {
-                    return Err(format!("Failed to connect after {} attempts: {}", max_retries, e).into());
-                }
-                
-                println!("Connection failed: {}. Retrying in {:?}...", e, delay);
-                tokio::time::sleep(delay).await;
-            }
-        }
-    }
-}
+use mcp::connect::{Connector, ReconnectPolicy};
+use mcp::dicom::load_dicom;
+use mcp::retry::{RetryConfig, RetryPolicy};
+use mcp::{RadiologyCluster, RadiologyImage};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Get WebSocket URL from environment variable or use default
     let ws_url = env::var("MCP_WEBSOCKET_URL").unwrap_or_else(|_| "ws://localhost:8080".to_string());
-    
+
     println!("Connecting to MCP server at: {}", ws_url);
-    
-    // Use the local connect_with_retry function
-    let transport = match connect_with_retry(&ws_url, 3, Duration::from_secs(2)).await {
-        Ok(transport) => {
+
+    // Retry behaviour comes from MCP_CONNECT_RETRY_* / MCP_SUBMIT_RETRY_* variables,
+    // or the JSON files named by MCP_CONNECT_RETRY_CONFIG / MCP_SUBMIT_RETRY_CONFIG
+    let connect_retry: RetryPolicy = RetryConfig::from_env("MCP_CONNECT_RETRY")?.into();
+    let submit_retry: RetryPolicy = RetryConfig::from_env("MCP_SUBMIT_RETRY")?.into();
+
+    // Keep the session alive across dropped connections once established
+    let connector = Connector::new()
+        .retry(connect_retry)
+        .reconnect(ReconnectPolicy::default());
+
+    let client = match connector.connect(&ws_url).await {
+        Ok(client) => {
             println!("Successfully connected to MCP server");
-            transport
+            client
         },
         Err(e) => {
-            eprintln!("Could not connect to MCP server: {}", e);
+            // The transport error's cause carries the detail, such as a refused connection
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(cause) = source {
+                message.push_str(&format!(": {}", cause));
+                source = cause.source();
+            }
+            eprintln!("Could not connect to MCP server: {}", message);
             eprintln!("Make sure the MCP server is running at {}", ws_url);
             eprintln!("You can set MCP_WEBSOCKET_URL environment variable to change the server address");
             return Err(e.into());
         }
     };
-    
-    // Create the client with Arc-wrapped transport
-    let client = Arc::new(Client::new(Arc::new(transport)));
-     
+
+    // Run the MCP handshake up front so version or capability problems surface early
+    let session = client.initialize().await?;
+    println!(
+        "Server {} {} speaks MCP {}",
+        session.server_info.name, session.server_info.version, session.protocol_version
+    );
+
     // Initialize the RadiologyCluster
-    let radiology_cluster = Arc::new(RadiologyCluster::new(client.clone()));
-    
+    let radiology_cluster = Arc::new(RadiologyCluster::new(Arc::new(client)).with_retry_policy(submit_retry));
+
     // Initialize a context for CT scan analysis
     radiology_cluster.initialize_context("ct-scan-context", "medical-imaging-model").await?;
-    
-    // Create a sample radiology image
-    let mut metadata = HashMap::new();
-    metadata.insert("patient_id".to_string(), "P12345".to_string());
-    metadata.insert("modality".to_string(), "CT".to_string());
-    metadata.insert("body_part".to_string(), "CHEST".to_string());
-    
-    let sample_image = RadiologyImage {
-        image_id: "IMG001".to_string(),
-        data: vec![0; 10], // Placeholder for actual image data
-        metadata,
+
+    // Analyze the DICOM file given on the command line, or a sample image without one
+    let sample_image = match env::args().nth(1) {
+        Some(path) => {
+            println!("Loading DICOM file {}", path);
+            load_dicom(&path)?
+        }
+        None => {
+            let mut metadata = HashMap::new();
+            metadata.insert("patient_id".to_string(), "P12345".to_string());
+            metadata.insert("modality".to_string(), "CT".to_string());
+            metadata.insert("body_part".to_string(), "CHEST".to_string());
+
+            RadiologyImage {
+                image_id: "IMG001".to_string(),
+                data: vec![0; 10], // Placeholder for actual image data
+                metadata,
+                dicom: None,
+            }
+        }
     };
-    
+
     // Submit the image for analysis
     let analysis_result = radiology_cluster.submit_image("ct-scan-context", sample_image).await?;
-    println!("Analysis result: {}", analysis_result);
-    
+    println!(
+        "Analysis result: {} (confidence {:.2})",
+        analysis_result.findings, analysis_result.confidence_score
+    );
+
     // Get all results for the context
     let results = radiology_cluster.get_results("ct-scan-context").await?;
     println!("Retrieved {} results", results.len());
-     
+
     Ok(())
-}
\ No newline at end of file
+}
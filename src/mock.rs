@@ -0,0 +1,689 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::stream::SplitSink;
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{accept_async, WebSocketStream};
+
+use crate::prompt::PromptTemplate;
+use crate::protocol::{self, Implementation, Tool, ToolAnnotations, SUPPORTED_PROTOCOL_VERSIONS};
+
+/// Standard JSON-RPC 2.0 error codes, plus the one MCP servers use before `initialize`
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+pub const SERVER_NOT_INITIALIZED: i32 = -32002;
+
+/// A JSON-RPC error object returned by a scripted method
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Computes a tool's structured output from its arguments; `Err` becomes an `isError` result
+pub type ToolHandler = Arc<dyn Fn(&Value) -> Result<Value, String> + Send + Sync>;
+
+/// Computes the result of a custom method from its params
+pub type MethodHandler = Arc<dyn Fn(&Value) -> Result<Value, RpcError> + Send + Sync>;
+
+/// How long the server waits before answering a request
+#[derive(Clone, Debug, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    /// Uniformly distributed between `min` and `max`
+    Uniform { min: Duration, max: Duration },
+    /// Exponentially distributed around `mean`, giving the occasional long tail
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            Latency::Fixed(delay) => *delay,
+            Latency::Uniform { min, max } if min >= max => *min,
+            Latency::Uniform { min, max } => rng.gen_range(*min..=*max),
+            Latency::Exponential { mean } => mean.mul_f64(-(1.0 - rng.gen::<f64>()).ln()),
+        }
+    }
+}
+
+/// Misbehavior injected in place of, or before, a normal answer
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Answer normally, but only after a delay
+    Delay(Latency),
+    /// Close the connection without answering
+    Disconnect,
+    /// Answer with a frame that isn't valid JSON
+    MalformedJson,
+    /// Answer with this JSON-RPC error object instead of the result
+    Error(RpcError),
+}
+
+/// When a `Fault` fires: which method, which occurrences and how often.
+///
+/// Occurrences are counted across all connections to the server.
+#[derive(Debug)]
+pub struct FaultRule {
+    method: Option<String>,
+    fault: Fault,
+    after: usize,
+    times: Option<usize>,
+    probability: f64,
+    seen: AtomicUsize,
+}
+
+impl FaultRule {
+    /// Fire on every message for `method`
+    pub fn on(method: &str, fault: Fault) -> Self {
+        FaultRule {
+            method: Some(method.to_string()),
+            ..FaultRule::any(fault)
+        }
+    }
+
+    /// Fire on every message, whatever its method
+    pub fn any(fault: Fault) -> Self {
+        FaultRule {
+            method: None,
+            fault,
+            after: 0,
+            times: None,
+            probability: 1.0,
+            seen: AtomicUsize::new(0),
+        }
+    }
+
+    /// Let the first `count` matching messages through untouched
+    pub fn after(mut self, count: usize) -> Self {
+        self.after = count;
+        self
+    }
+
+    /// Stop firing after `count` occurrences
+    pub fn times(mut self, count: usize) -> Self {
+        self.times = Some(count);
+        self
+    }
+
+    /// Fire on each eligible message with this chance, between 0 and 1
+    pub fn probability(mut self, probability: f64) -> Self {
+        self.probability = probability;
+        self
+    }
+
+    fn fires(&self, method: &str, rng: &mut impl Rng) -> bool {
+        if self.method.as_deref().is_some_and(|m| m != method) {
+            return false;
+        }
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed);
+        let in_window = seen >= self.after && self.times.is_none_or(|times| seen < self.after + times);
+        in_window && rng.gen_bool(self.probability.clamp(0.0, 1.0))
+    }
+}
+
+/// Builder for a `MockServer`
+pub struct MockServerBuilder {
+    addr: String,
+    server_info: Implementation,
+    tools: Vec<(Tool, ToolHandler)>,
+    resources: Vec<Value>,
+    prompts: Vec<Value>,
+    // Template text `prompts/get` renders, by prompt name
+    prompt_texts: HashMap<String, String>,
+    methods: HashMap<String, MethodHandler>,
+    faults: Vec<FaultRule>,
+    handshake_refusal: Option<RpcError>,
+    reorder_window: usize,
+}
+
+impl MockServerBuilder {
+    /// Listen on `addr` instead of a free port on 127.0.0.1
+    pub fn bind(mut self, addr: &str) -> Self {
+        self.addr = addr.to_string();
+        self
+    }
+
+    /// Name and version reported in the `initialize` result
+    pub fn server_info(mut self, name: &str, version: &str) -> Self {
+        self.server_info = Implementation {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+        self
+    }
+
+    /// Offer a tool whose output is computed from the call's arguments
+    pub fn tool<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        let tool = Tool {
+            name: name.to_string(),
+            description: None,
+            input_schema: json!({ "type": "object" }),
+            annotations: None,
+        };
+        self.tools.retain(|(existing, _)| existing.name != name);
+        self.tools.push((tool, Arc::new(handler)));
+        self
+    }
+
+    /// Offer a tool that always returns the same structured output
+    pub fn tool_response(self, name: &str, output: Value) -> Self {
+        self.tool(name, move |_| Ok(output.clone()))
+    }
+
+    /// Attach behavior hints to a tool added earlier
+    pub fn annotate(mut self, name: &str, annotations: ToolAnnotations) -> Self {
+        if let Some((tool, _)) = self.tools.iter_mut().find(|(tool, _)| tool.name == name) {
+            tool.annotations = Some(annotations);
+        }
+        self
+    }
+
+    /// List a resource in `resources/list`
+    pub fn resource(mut self, uri: &str, name: &str, mime_type: &str) -> Self {
+        self.resources.push(json!({ "uri": uri, "name": name, "mimeType": mime_type }));
+        self
+    }
+
+    /// List a prompt and its argument names in `prompts/list`
+    pub fn prompt(mut self, name: &str, description: &str, arguments: &[&str]) -> Self {
+        let arguments: Vec<Value> = arguments.iter().map(|arg| json!({ "name": arg })).collect();
+        self.prompts.push(json!({ "name": name, "description": description, "arguments": arguments }));
+        self
+    }
+
+    /// Text `prompts/get` renders for a prompt listed earlier, in `mcp::prompt` template syntax;
+    /// without one it answers with the prompt's description
+    pub fn prompt_text(mut self, name: &str, template: &str) -> Self {
+        self.prompt_texts.insert(name.to_string(), template.to_string());
+        self
+    }
+
+    /// Answer a method outside the MCP core, such as `images/upload`
+    pub fn method<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&Value) -> Result<Value, RpcError> + Send + Sync + 'static,
+    {
+        self.methods.insert(name.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Inject a fault; every rule matching a message fires, delays adding up
+    pub fn fault(mut self, rule: FaultRule) -> Self {
+        self.faults.push(rule);
+        self
+    }
+
+    /// Delay every answer by a latency drawn from `latency`
+    pub fn latency(self, latency: Latency) -> Self {
+        self.fault(FaultRule::any(Fault::Delay(latency)))
+    }
+
+    /// Answer `initialize` with this error, leaving the connection unusable
+    pub fn refuse_handshake(mut self, code: i32, message: &str) -> Self {
+        self.handshake_refusal = Some(RpcError::new(code, message));
+        self
+    }
+
+    /// Hold answers back until `window` are ready, then send them newest first.
+    ///
+    /// Answers still held when no new one arrives for a short while are flushed
+    /// in reverse too, so a lone request is never stuck.
+    pub fn reorder(mut self, window: usize) -> Self {
+        self.reorder_window = window;
+        self
+    }
+
+    /// Bind the listener and start accepting connections in the background
+    pub async fn start(self) -> std::io::Result<MockServer> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let addr = listener.local_addr()?;
+        let requests: Recorded = Arc::new(Mutex::new(Vec::new()));
+
+        let script = Arc::new(Script {
+            server_info: self.server_info,
+            tools: self.tools,
+            resources: self.resources,
+            prompts: self.prompts,
+            prompt_texts: self.prompt_texts,
+            methods: self.methods,
+            faults: self.faults,
+            handshake_refusal: self.handshake_refusal,
+            reorder_window: self.reorder_window,
+        });
+        let task = tokio::spawn(accept_connections(listener, script, requests.clone()));
+
+        Ok(MockServer { addr, requests, task })
+    }
+}
+
+/// In-process MCP server speaking JSON-RPC 2.0 over WebSocket, for tests and local runs.
+///
+/// It implements `initialize` (with protocol version negotiation), `ping`,
+/// `tools/list`, `tools/call`, `resources/list`, `prompts/list` and `prompts/get`, echoes
+/// request ids and answers malformed frames with the matching JSON-RPC error.
+/// Tools and extra methods are scripted through `MockServer::builder()`:
+///
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use mcp::mock::MockServer;
+/// use serde_json::json;
+///
+/// let server = MockServer::builder()
+///     .tool_response("analyze_image", json!({ "findings": "Normal", "confidence": 0.9 }))
+///     .start()
+///     .await?;
+/// let client = mcp::McpClient::connect(&server.url()).await.unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Faults such as latency, dropped connections, malformed frames, error
+/// objects, reordered answers and handshake refusal are injected with
+/// `fault`, `latency`, `reorder` and `refuse_handshake` on the builder.
+///
+/// Every frame received is recorded for inspection. Dropping the server
+/// closes the listener and all open connections.
+pub struct MockServer {
+    addr: SocketAddr,
+    requests: Recorded,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder {
+            addr: "127.0.0.1:0".to_string(),
+            server_info: Implementation {
+                name: "mock-mcp-server".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            prompt_texts: HashMap::new(),
+            methods: HashMap::new(),
+            faults: Vec::new(),
+            handshake_refusal: None,
+            reorder_window: 1,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// WebSocket URL clients connect to
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    /// Every JSON message received so far, in arrival order
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The received requests and notifications for one method
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.requests().into_iter().filter(|r| r["method"] == method).collect()
+    }
+
+    /// Serve until the task is aborted, e.g. from a standalone binary
+    pub async fn run(mut self) {
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type Recorded = Arc<Mutex<Vec<Value>>>;
+
+// The server's fixed behavior, shared by every connection
+struct Script {
+    server_info: Implementation,
+    tools: Vec<(Tool, ToolHandler)>,
+    resources: Vec<Value>,
+    prompts: Vec<Value>,
+    // Template text `prompts/get` renders, by prompt name
+    prompt_texts: HashMap<String, String>,
+    methods: HashMap<String, MethodHandler>,
+    faults: Vec<FaultRule>,
+    handshake_refusal: Option<RpcError>,
+    reorder_window: usize,
+}
+
+// What the faults decided for one frame
+#[derive(Default)]
+struct FaultPlan {
+    delay: Duration,
+    terminal: Option<Fault>,
+}
+
+impl Script {
+    fn plan(&self, methods: &[String]) -> FaultPlan {
+        let mut rng = rand::thread_rng();
+        let mut plan = FaultPlan::default();
+        for method in methods {
+            for rule in &self.faults {
+                if !rule.fires(method, &mut rng) {
+                    continue;
+                }
+                match &rule.fault {
+                    Fault::Delay(latency) => plan.delay += latency.sample(&mut rng),
+                    fault => {
+                        plan.terminal.get_or_insert_with(|| fault.clone());
+                    }
+                }
+            }
+        }
+        plan
+    }
+}
+
+async fn accept_connections(listener: TcpListener, script: Arc<Script>, requests: Recorded) {
+    // Connections live in the set, so aborting this task closes them too
+    let mut connections = JoinSet::new();
+    while let Ok((stream, _)) = listener.accept().await {
+        while connections.try_join_next().is_some() {}
+        connections.spawn(serve_connection(stream, script.clone(), requests.clone()));
+    }
+}
+
+async fn serve_connection(stream: TcpStream, script: Arc<Script>, requests: Recorded) {
+    let Ok(ws_stream) = accept_async(stream).await else {
+        return;
+    };
+    let (write, mut read) = ws_stream.split();
+
+    // Responses go through a channel so the writer never blocks request handling
+    let (tx, rx) = mpsc::unbounded_channel::<String>();
+    let writer = tokio::spawn(write_responses(write, rx, script.reorder_window));
+
+    let mut session = Session::default();
+    while let Some(Ok(message)) = read.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Binary(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let (reply, methods) = session.handle_frame(&text, &script, &requests);
+        let plan = script.plan(&methods);
+        let reply = match (plan.terminal, reply) {
+            (Some(Fault::Disconnect), _) => {
+                // Dropping both halves of the socket closes it without a goodbye
+                writer.abort();
+                return;
+            }
+            (_, None) => continue,
+            (Some(Fault::MalformedJson), Some(reply)) => {
+                // Cut at a character boundary; replies may hold multibyte text
+                let text = reply.to_string();
+                let half = text.char_indices().map(|(at, _)| at).take_while(|&at| at <= text.len() / 2).last().unwrap_or(0);
+                text[..half].to_string()
+            }
+            (Some(Fault::Error(error)), Some(reply)) => with_error(reply, &error).to_string(),
+            (_, Some(reply)) => reply.to_string(),
+        };
+
+        if plan.delay.is_zero() {
+            let _ = tx.send(reply);
+        } else {
+            let tx = tx.clone();
+            let cancelled = session.cancelled.clone();
+            let id = serde_json::from_str::<Value>(&reply).map(|reply| reply["id"].to_string());
+            tokio::spawn(async move {
+                tokio::time::sleep(plan.delay).await;
+                if id.is_ok_and(|id| cancelled.lock().unwrap().contains(&id)) {
+                    return;
+                }
+                let _ = tx.send(reply);
+            });
+        }
+    }
+
+    drop(tx);
+    let _ = writer.await;
+}
+
+// How long held answers wait for the reorder window to fill
+const REORDER_FLUSH: Duration = Duration::from_millis(100);
+
+async fn write_responses(
+    mut write: SplitSink<WebSocketStream<TcpStream>, Message>,
+    mut rx: mpsc::UnboundedReceiver<String>,
+    reorder_window: usize,
+) {
+    let mut held: Vec<String> = Vec::new();
+    loop {
+        let next = if held.is_empty() {
+            rx.recv().await
+        } else {
+            match tokio::time::timeout(REORDER_FLUSH, rx.recv()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    if flush(&mut write, &mut held).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            }
+        };
+
+        let Some(text) = next else {
+            let _ = flush(&mut write, &mut held).await;
+            return;
+        };
+        held.push(text);
+        if held.len() >= reorder_window && flush(&mut write, &mut held).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Send held answers newest first
+async fn flush(
+    write: &mut SplitSink<WebSocketStream<TcpStream>, Message>,
+    held: &mut Vec<String>,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    while let Some(text) = held.pop() {
+        write.send(Message::Text(text)).await?;
+    }
+    Ok(())
+}
+
+// Replace the result of every response in a reply with `error`
+fn with_error(reply: Value, error: &RpcError) -> Value {
+    match reply {
+        Value::Array(batch) => Value::Array(batch.into_iter().map(|r| with_error(r, error)).collect()),
+        reply => error_response(reply["id"].clone(), error.code, &error.message),
+    }
+}
+
+// Per-connection protocol state
+#[derive(Default)]
+struct Session {
+    initialized: bool,
+    // Ids the client cancelled; answers to them that are still delayed are dropped
+    cancelled: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Session {
+    // Answer one WebSocket frame, a single message or a batch, and report the methods it held
+    fn handle_frame(&mut self, text: &str, script: &Script, requests: &Recorded) -> (Option<Value>, Vec<String>) {
+        let frame: Value = match serde_json::from_str(text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                let reply = error_response(Value::Null, PARSE_ERROR, &format!("parse error: {}", e));
+                return (Some(reply), Vec::new());
+            }
+        };
+
+        let messages = match frame {
+            Value::Array(batch) if batch.is_empty() => {
+                return (Some(error_response(Value::Null, INVALID_REQUEST, "empty batch")), Vec::new());
+            }
+            Value::Array(batch) => batch,
+            message => vec![message],
+        };
+        let methods = messages
+            .iter()
+            .filter_map(|message| message["method"].as_str().map(str::to_string))
+            .collect();
+
+        let mut replies: Vec<Value> = messages
+            .iter()
+            .filter_map(|message| self.handle_message(message, script, requests))
+            .collect();
+        let reply = if text.trim_start().starts_with('[') {
+            (!replies.is_empty()).then_some(Value::Array(replies))
+        } else {
+            replies.pop()
+        };
+        (reply, methods)
+    }
+
+    // Answer one JSON-RPC message; notifications get no reply
+    fn handle_message(&mut self, message: &Value, script: &Script, requests: &Recorded) -> Option<Value> {
+        requests.lock().unwrap().push(message.clone());
+
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Value::as_str);
+        let (Some(method), true) = (method, message["jsonrpc"] == "2.0") else {
+            return Some(error_response(id.unwrap_or(Value::Null), INVALID_REQUEST, "invalid request"));
+        };
+        if method == protocol::CANCELLED {
+            self.cancelled.lock().unwrap().insert(message["params"]["requestId"].to_string());
+        }
+        let id = id?;
+
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.dispatch(method, &params, script);
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error.code, &error.message),
+        })
+    }
+
+    fn dispatch(&mut self, method: &str, params: &Value, script: &Script) -> Result<Value, RpcError> {
+        match method {
+            protocol::INITIALIZE => {
+                if let Some(refusal) = &script.handshake_refusal {
+                    return Err(refusal.clone());
+                }
+                self.initialized = true;
+                Ok(initialize_result(params, script))
+            }
+            protocol::PING => Ok(json!({})),
+            _ if !self.initialized => Err(RpcError::new(SERVER_NOT_INITIALIZED, "server not initialized")),
+            protocol::TOOLS_LIST => {
+                let tools: Vec<&Tool> = script.tools.iter().map(|(tool, _)| tool).collect();
+                Ok(json!({ "tools": tools }))
+            }
+            protocol::TOOLS_CALL => call_tool(params, script),
+            "resources/list" => Ok(json!({ "resources": script.resources })),
+            protocol::PROMPTS_LIST => Ok(json!({ "prompts": script.prompts })),
+            protocol::PROMPTS_GET => get_prompt(params, script),
+            _ => match script.methods.get(method) {
+                Some(handler) => handler(params),
+                None => Err(RpcError::new(METHOD_NOT_FOUND, format!("method not found: {}", method))),
+            },
+        }
+    }
+}
+
+// Agree on the client's version when supported, otherwise offer the latest
+fn initialize_result(params: &Value, script: &Script) -> Value {
+    let requested = params["protocolVersion"].as_str().unwrap_or_default();
+    let version = if SUPPORTED_PROTOCOL_VERSIONS.contains(&requested) {
+        requested
+    } else {
+        protocol::LATEST_PROTOCOL_VERSION
+    };
+
+    json!({
+        "protocolVersion": version,
+        "capabilities": {
+            "tools": { "listChanged": false },
+            "resources": { "listChanged": false },
+            "prompts": { "listChanged": false },
+        },
+        "serverInfo": script.server_info,
+    })
+}
+
+fn call_tool(params: &Value, script: &Script) -> Result<Value, RpcError> {
+    let name = params["name"].as_str().unwrap_or_default();
+    let (_, handler) = script
+        .tools
+        .iter()
+        .find(|(tool, _)| tool.name == name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown tool: {}", name)))?;
+
+    let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+    Ok(match handler(&arguments) {
+        Ok(output) => json!({
+            "content": [{ "type": "text", "text": output.to_string() }],
+            "structuredContent": output,
+        }),
+        Err(message) => json!({
+            "content": [{ "type": "text", "text": message }],
+            "isError": true,
+        }),
+    })
+}
+
+fn get_prompt(params: &Value, script: &Script) -> Result<Value, RpcError> {
+    let name = params["name"].as_str().unwrap_or_default();
+    let prompt = script
+        .prompts
+        .iter()
+        .find(|prompt| prompt["name"] == name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown prompt: {}", name)))?;
+
+    let text = match script.prompt_texts.get(name) {
+        Some(text) => {
+            let template = PromptTemplate::parse(name, 1, text).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+            let arguments: HashMap<String, String> = serde_json::from_value(params["arguments"].clone()).unwrap_or_default();
+            template.render(&arguments)
+        }
+        None => prompt["description"].as_str().unwrap_or_default().to_string(),
+    };
+    Ok(json!({
+        "description": prompt["description"],
+        "messages": [{ "role": "user", "content": { "type": "text", "text": text } }],
+    }))
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
@@ -0,0 +1,309 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+
+use crate::backend::Backend;
+use crate::client::McpClient;
+use crate::error::RadiologyError;
+use crate::protocol;
+
+/// How a pool picks the member that serves the next submission
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Balance {
+    /// Each healthy member in turn
+    #[default]
+    RoundRobin,
+    /// The healthy member with the fewest submissions in flight
+    LeastOutstanding,
+    /// Each healthy member in turn, as many times in a row as its weight
+    Weighted,
+}
+
+/// When pool members are taken out of rotation and brought back.
+///
+/// A member is ejected after `failure_threshold` consecutive failed submissions
+/// or health checks. Every `interval` each member is pinged; an ejected member
+/// that answers within `timeout` is readmitted.
+#[derive(Clone, Debug)]
+pub struct HealthPolicy {
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub failure_threshold: u32,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        HealthPolicy {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(2),
+            failure_threshold: 3,
+        }
+    }
+}
+
+/// Snapshot of one pool member
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemberStatus {
+    pub name: String,
+    pub weight: u32,
+    pub healthy: bool,
+    pub outstanding: usize,
+}
+
+struct Member {
+    backend: Arc<Backend>,
+    weight: u32,
+    healthy: AtomicBool,
+    outstanding: AtomicUsize,
+    failures: AtomicU32,
+}
+
+/// Interchangeable MCP servers registered under one backend name.
+///
+/// Submissions to a context bound to the pool are spread over its healthy
+/// members according to `Balance`; see `HealthPolicy` for ejection and
+/// readmission. A backend registered with a single client is a pool of one.
+///
+/// ```no_run
+/// # async fn run(a: std::sync::Arc<mcp::McpClient>, b: std::sync::Arc<mcp::McpClient>) {
+/// use mcp::pool::{Balance, BackendPool, HealthPolicy};
+///
+/// let pool = BackendPool::new("ct", Balance::LeastOutstanding)
+///     .member("ct-1", a)
+///     .weighted_member("ct-2", b, 2)
+///     .health_checks(HealthPolicy::default());
+/// # }
+/// ```
+pub struct BackendPool {
+    name: String,
+    balance: Balance,
+    members: Vec<Member>,
+    health: Option<HealthPolicy>,
+    next: AtomicUsize,
+    checker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BackendPool {
+    pub fn new(name: &str, balance: Balance) -> Self {
+        BackendPool {
+            name: name.to_string(),
+            balance,
+            members: Vec::new(),
+            health: None,
+            next: AtomicUsize::new(0),
+            checker: Mutex::new(None),
+        }
+    }
+
+    /// A pool holding just `client`, named after the pool itself
+    pub fn single(name: &str, client: Arc<McpClient>) -> Self {
+        Self::new(name, Balance::RoundRobin).member(name, client)
+    }
+
+    pub fn member(self, name: &str, client: Arc<McpClient>) -> Self {
+        self.weighted_member(name, client, 1)
+    }
+
+    /// Add a member that `Balance::Weighted` picks `weight` times per round (at least once)
+    pub fn weighted_member(mut self, name: &str, client: Arc<McpClient>, weight: u32) -> Self {
+        self.members.push(Member {
+            backend: Arc::new(Backend::new(name, client)),
+            weight: weight.max(1),
+            healthy: AtomicBool::new(true),
+            outstanding: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+        });
+        self
+    }
+
+    /// Ping members periodically once the pool is registered, ejecting and readmitting them.
+    ///
+    /// A pool without health checks keeps every member in rotation.
+    pub fn health_checks(mut self, policy: HealthPolicy) -> Self {
+        self.health = Some(policy);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn balance(&self) -> Balance {
+        self.balance
+    }
+
+    /// The pool's members, healthy or not
+    pub fn backends(&self) -> Vec<Arc<Backend>> {
+        self.members.iter().map(|member| member.backend.clone()).collect()
+    }
+
+    pub fn status(&self) -> Vec<MemberStatus> {
+        self.members
+            .iter()
+            .map(|member| MemberStatus {
+                name: member.backend.name().to_string(),
+                weight: member.weight,
+                healthy: member.healthy.load(Ordering::SeqCst),
+                outstanding: member.outstanding.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+
+    /// Pick a healthy member for one submission; it counts as outstanding until the lease drops
+    pub fn acquire(self: &Arc<Self>) -> Result<Lease, RadiologyError> {
+        self.start();
+        let healthy: Vec<usize> = (0..self.members.len())
+            .filter(|&index| self.members[index].healthy.load(Ordering::SeqCst))
+            .collect();
+        if healthy.is_empty() {
+            return Err(RadiologyError::Unavailable(self.name.clone()));
+        }
+
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        let index = match self.balance {
+            Balance::RoundRobin => healthy[turn % healthy.len()],
+            // Start the scan at a rotating offset so ties don't always go to the first member
+            Balance::LeastOutstanding => (0..healthy.len())
+                .map(|offset| healthy[(turn + offset) % healthy.len()])
+                .min_by_key(|&index| self.members[index].outstanding.load(Ordering::SeqCst))
+                .unwrap_or(healthy[0]),
+            Balance::Weighted => {
+                let total: usize = healthy.iter().map(|&index| self.members[index].weight as usize).sum();
+                let mut slot = turn % total;
+                let mut chosen = healthy[0];
+                for &index in &healthy {
+                    let weight = self.members[index].weight as usize;
+                    if slot < weight {
+                        chosen = index;
+                        break;
+                    }
+                    slot -= weight;
+                }
+                chosen
+            }
+        };
+
+        self.members[index].outstanding.fetch_add(1, Ordering::SeqCst);
+        Ok(Lease {
+            pool: self.clone(),
+            index,
+        })
+    }
+
+    /// Start the health check task if the pool has a `HealthPolicy` and none is running.
+    ///
+    /// Called on registration and on every `acquire`, so a pool registered
+    /// outside a tokio runtime starts checking once it is used inside one.
+    pub(crate) fn start(self: &Arc<Self>) {
+        let Some(policy) = self.health.clone() else {
+            return;
+        };
+        let mut checker = self.checker.lock().unwrap();
+        if checker.as_ref().is_some_and(|task| !task.is_finished()) {
+            return;
+        }
+        if tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        *checker = Some(tokio::spawn(check_health(Arc::downgrade(self), policy)));
+    }
+
+    fn checking(&self) -> bool {
+        self.checker.lock().unwrap().as_ref().is_some_and(|task| !task.is_finished())
+    }
+
+    fn record_success(&self, index: usize) {
+        let member = &self.members[index];
+        member.failures.store(0, Ordering::SeqCst);
+        if !member.healthy.swap(true, Ordering::SeqCst) {
+            println!("Backend '{}' readmitted to pool '{}'", member.backend.name(), self.name);
+        }
+    }
+
+    // Without a running health check nothing would readmit a member, so none is ejected
+    fn record_failure(&self, index: usize) {
+        let Some(health) = &self.health else {
+            return;
+        };
+        if !self.checking() {
+            return;
+        }
+        let member = &self.members[index];
+        let failures = member.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= health.failure_threshold && member.healthy.swap(false, Ordering::SeqCst) {
+            println!(
+                "Backend '{}' ejected from pool '{}' after {} consecutive failures",
+                member.backend.name(),
+                self.name,
+                failures
+            );
+        }
+    }
+}
+
+impl Drop for BackendPool {
+    fn drop(&mut self) {
+        if let Some(checker) = self.checker.lock().unwrap().take() {
+            checker.abort();
+        }
+    }
+}
+
+/// A pool member handed out for one submission
+pub struct Lease {
+    pool: Arc<BackendPool>,
+    index: usize,
+}
+
+impl Lease {
+    pub fn backend(&self) -> &Arc<Backend> {
+        &self.pool.members[self.index].backend
+    }
+
+    /// Feed a submission's outcome into the member's health.
+    ///
+    /// Only failures that point at the server, those that are
+    /// `RadiologyError::is_retryable`, count against it.
+    pub fn record<T>(&self, outcome: &Result<T, RadiologyError>) {
+        match outcome {
+            Ok(_) => self.pool.record_success(self.index),
+            Err(err) if err.is_retryable() => self.pool.record_failure(self.index),
+            Err(_) => {}
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.pool.members[self.index].outstanding.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn check_health(pool: Weak<BackendPool>, policy: HealthPolicy) {
+    let mut ticks = tokio::time::interval(policy.interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+
+        let checks = pool.members.iter().map(|member| {
+            let client = member.backend.client().clone();
+            async move {
+                let ping = client.request(protocol::PING, None);
+                matches!(tokio::time::timeout(policy.timeout, ping).await, Ok(Ok(_)))
+            }
+        });
+        let results = futures_util::future::join_all(checks).await;
+        for (index, alive) in results.into_iter().enumerate() {
+            if alive {
+                pool.record_success(index);
+            } else {
+                pool.record_failure(index);
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Prompt sent with each analysis unless the context picks another
+pub const DEFAULT_PROMPT_TEMPLATE: &str =
+    "You are a radiology analysis system. Analyze the following medical image:\n\n{metadata}";
+
+/// Variables every template can use. All but `metadata` come from the image
+/// metadata entry of the same name, after de-identification.
+pub const MODALITY: &str = "modality";
+pub const BODY_PART: &str = "body_part";
+pub const CLINICAL_INDICATION: &str = "clinical_indication";
+pub const PRIOR_FINDINGS: &str = "prior_findings";
+/// The whole de-identified metadata as JSON
+pub const METADATA: &str = "metadata";
+
+/// Error loading, parsing or finding a prompt template
+#[derive(Debug, thiserror::Error)]
+pub enum PromptError {
+    #[error("failed to read prompt template")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid prompt template: {0}")]
+    Syntax(String),
+
+    #[error("prompt template file '{0}' is not named <name>.v<version>.txt")]
+    FileName(String),
+
+    #[error("prompt template '{name}' not found")]
+    NotFound { name: String, version: Option<u32> },
+}
+
+/// Where a context's prompt comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum PromptSource {
+    /// Template text given directly
+    Inline(String),
+    /// A template from the cluster's `PromptLibrary`; without a version, the latest one
+    Library { name: String, version: Option<u32> },
+    /// An MCP prompt the context's backend renders through `prompts/get`
+    Server { name: String },
+}
+
+impl Default for PromptSource {
+    fn default() -> Self {
+        PromptSource::Inline(DEFAULT_PROMPT_TEMPLATE.to_string())
+    }
+}
+
+/// A parsed prompt template.
+///
+/// `{name}` is replaced by the variable's value, or nothing if it isn't set.
+/// `{#name}...{/name}` is kept only when the variable is set and not empty.
+/// `{{` and `}}` stand for literal braces.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use mcp::prompt::PromptTemplate;
+///
+/// let template = PromptTemplate::parse("chest", 1, "Read this {modality}.{#prior_findings} Prior: {prior_findings}{/prior_findings}").unwrap();
+/// let variables = HashMap::from([("modality".to_string(), "CT".to_string())]);
+/// assert_eq!(template.render(&variables), "Read this CT.");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PromptTemplate {
+    name: String,
+    version: u32,
+    text: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Variable(String),
+    Section(String, Vec<Part>),
+}
+
+impl PromptTemplate {
+    pub fn parse(name: &str, version: u32, text: &str) -> Result<Self, PromptError> {
+        Ok(PromptTemplate {
+            name: name.to_string(),
+            version,
+            text: text.to_string(),
+            parts: parse(text)?,
+        })
+    }
+
+    /// Load a template from a file named `<name>.v<version>.txt`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, PromptError> {
+        let path = path.as_ref();
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let (name, version) = file_name
+            .strip_suffix(".txt")
+            .and_then(|stem| stem.rsplit_once(".v"))
+            .and_then(|(name, version)| Some((name, version.parse().ok()?)))
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| PromptError::FileName(file_name.to_string()))?;
+        Self::parse(name, version, &std::fs::read_to_string(path)?)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The template as written
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Names of the variables the template refers to
+    pub fn variables(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        collect_variables(&self.parts, &mut names);
+        names
+    }
+
+    pub fn render(&self, variables: &HashMap<String, String>) -> String {
+        let mut out = String::new();
+        render(&self.parts, variables, &mut out);
+        out
+    }
+}
+
+/// Versioned prompt templates, shared by the contexts of a cluster
+#[derive(Default)]
+pub struct PromptLibrary {
+    templates: RwLock<HashMap<String, BTreeMap<u32, Arc<PromptTemplate>>>>,
+}
+
+impl PromptLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a template, returning the one it replaces with the same name and version
+    pub fn insert(&self, template: PromptTemplate) -> Option<Arc<PromptTemplate>> {
+        let mut templates = self.templates.write().unwrap();
+        let versions = templates.entry(template.name.clone()).or_default();
+        versions.insert(template.version, Arc::new(template))
+    }
+
+    /// Load one `<name>.v<version>.txt` file
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<Arc<PromptTemplate>, PromptError> {
+        let template = PromptTemplate::from_file(path)?;
+        let (name, version) = (template.name.clone(), template.version);
+        self.insert(template);
+        self.get(&name, Some(version))
+    }
+
+    /// Load every `.txt` file in a directory and return how many were loaded
+    pub fn load_dir(&self, dir: impl AsRef<Path>) -> Result<usize, PromptError> {
+        let mut loaded = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "txt") {
+                self.load_file(&path)?;
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// A template by name; without a version, its latest one
+    pub fn get(&self, name: &str, version: Option<u32>) -> Result<Arc<PromptTemplate>, PromptError> {
+        let templates = self.templates.read().unwrap();
+        let versions = templates.get(name);
+        let template = match version {
+            Some(version) => versions.and_then(|versions| versions.get(&version)),
+            None => versions.and_then(|versions| versions.values().next_back()),
+        };
+        template.cloned().ok_or_else(|| PromptError::NotFound {
+            name: name.to_string(),
+            version,
+        })
+    }
+
+    /// Versions held for a template, oldest first
+    pub fn versions(&self, name: &str) -> Vec<u32> {
+        let templates = self.templates.read().unwrap();
+        templates.get(name).map(|versions| versions.keys().copied().collect()).unwrap_or_default()
+    }
+
+    /// Names of every template, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.templates.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+/// The variables for an image, taken from its de-identified metadata
+pub fn variables(metadata: &HashMap<String, String>) -> Result<HashMap<String, String>, serde_json::Error> {
+    let mut variables: HashMap<String, String> = [MODALITY, BODY_PART, CLINICAL_INDICATION, PRIOR_FINDINGS]
+        .into_iter()
+        .filter_map(|key| Some((key.to_string(), metadata.get(key)?.clone())))
+        .collect();
+    variables.insert(METADATA.to_string(), serde_json::to_string(metadata)?);
+    Ok(variables)
+}
+
+// Parse into nested parts, checking that tags are well formed and sections balanced
+fn parse(text: &str) -> Result<Vec<Part>, PromptError> {
+    // Each open section: its name and the parts collected so far outside it
+    let mut open: Vec<(String, Vec<Part>)> = Vec::new();
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => return Err(PromptError::Syntax("unmatched '}'".to_string())),
+            '{' => {
+                let mut tag = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(PromptError::Syntax(format!("unclosed tag '{{{}'", tag))),
+                    }
+                }
+                if !literal.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut literal)));
+                }
+                if let Some(name) = tag.strip_prefix('#') {
+                    open.push((identifier(name)?, std::mem::take(&mut parts)));
+                } else if let Some(name) = tag.strip_prefix('/') {
+                    match open.pop() {
+                        Some((opened, outer)) if opened == name => {
+                            let section = std::mem::replace(&mut parts, outer);
+                            parts.push(Part::Section(opened, section));
+                        }
+                        Some((opened, _)) => {
+                            return Err(PromptError::Syntax(format!("'{{/{}}}' closes section '{}'", name, opened)))
+                        }
+                        None => return Err(PromptError::Syntax(format!("'{{/{}}}' has no open section", name))),
+                    }
+                } else {
+                    parts.push(Part::Variable(identifier(&tag)?));
+                }
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if let Some((name, _)) = open.pop() {
+        return Err(PromptError::Syntax(format!("section '{}' is never closed", name)));
+    }
+    if !literal.is_empty() {
+        parts.push(Part::Text(literal));
+    }
+    Ok(parts)
+}
+
+fn identifier(name: &str) -> Result<String, PromptError> {
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(PromptError::Syntax(format!("invalid variable name '{}'", name)));
+    }
+    Ok(name.to_string())
+}
+
+fn render(parts: &[Part], variables: &HashMap<String, String>, out: &mut String) {
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Variable(name) => out.push_str(variables.get(name).map(String::as_str).unwrap_or_default()),
+            Part::Section(name, parts) => {
+                if variables.get(name).is_some_and(|value| !value.is_empty()) {
+                    render(parts, variables, out);
+                }
+            }
+        }
+    }
+}
+
+fn collect_variables<'a>(parts: &'a [Part], names: &mut BTreeSet<&'a str>) {
+    for part in parts {
+        match part {
+            Part::Text(_) => {}
+            Part::Variable(name) => {
+                names.insert(name);
+            }
+            Part::Section(name, parts) => {
+                names.insert(name);
+                collect_variables(parts, names);
+            }
+        }
+    }
+}
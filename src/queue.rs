@@ -0,0 +1,590 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Notify};
+
+use crate::context::ContextInfo;
+use crate::error::RadiologyError;
+use crate::journal::{JobJournal, JournalEntry, JournalState};
+use crate::store::{ResultQuery, StoreError};
+use crate::{RadiologyCluster, RadiologyImage, RadiologyResult};
+
+/// Identifies a job for status lookups
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct JobId(pub u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "job-{}", self.0)
+    }
+}
+
+/// Urgency of a read; STAT jobs always start before routine ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Priority {
+    Stat,
+    #[default]
+    Routine,
+}
+
+/// Where a job is in its life
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed(RadiologyResult),
+    /// The submission failed; holds the error message
+    Failed(String),
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed(_) | JobStatus::Failed(_))
+    }
+}
+
+/// What `JobQueue::submit` does when the buffer is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until a queued job starts and frees a slot
+    #[default]
+    Wait,
+    /// Fail at once with `RadiologyError::QueueFull`
+    Reject,
+}
+
+/// Limits for a `JobQueue`
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    capacity: usize,
+    overflow: Overflow,
+    max_concurrent: usize,
+    context_limit: Option<usize>,
+    context_limits: HashMap<String, usize>,
+    backend_limit: Option<usize>,
+    backend_limits: HashMap<String, usize>,
+    retain_finished: usize,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capacity: 1024,
+            overflow: Overflow::Wait,
+            max_concurrent: 16,
+            context_limit: None,
+            context_limits: HashMap::new(),
+            backend_limit: None,
+            backend_limits: HashMap::new(),
+            retain_finished: 1024,
+        }
+    }
+}
+
+impl QueueConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Jobs that may wait to start; running jobs don't count (at least one)
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Jobs running at once across the whole queue (at least one)
+    pub fn max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    /// Running jobs allowed per context unless `context_limit` sets another
+    pub fn default_context_limit(mut self, limit: usize) -> Self {
+        self.context_limit = Some(limit.max(1));
+        self
+    }
+
+    pub fn context_limit(mut self, context_id: &str, limit: usize) -> Self {
+        self.context_limits.insert(context_id.to_string(), limit.max(1));
+        self
+    }
+
+    /// Running jobs allowed per backend unless `backend_limit` sets another
+    pub fn default_backend_limit(mut self, limit: usize) -> Self {
+        self.backend_limit = Some(limit.max(1));
+        self
+    }
+
+    pub fn backend_limit(mut self, backend: &str, limit: usize) -> Self {
+        self.backend_limits.insert(backend.to_string(), limit.max(1));
+        self
+    }
+
+    /// Finished jobs whose status is kept for `status` and `wait`; older ones are forgotten
+    pub fn retain_finished(mut self, count: usize) -> Self {
+        self.retain_finished = count;
+        self
+    }
+
+    fn limit_for_context(&self, context_id: &str) -> Option<usize> {
+        self.context_limits.get(context_id).copied().or(self.context_limit)
+    }
+
+    fn limit_for_backend(&self, backend: &str) -> Option<usize> {
+        self.backend_limits.get(backend).copied().or(self.backend_limit)
+    }
+}
+
+/// Bounded queue of image submissions run on a `RadiologyCluster`.
+///
+/// Jobs start in priority order, then in the order they were submitted, as
+/// long as the global, per-context and per-backend concurrency limits allow;
+/// a job held back by a busy context doesn't block jobs for other contexts.
+///
+/// A queue opened with `with_journal` is durable: every job is written ahead
+/// to a `JobJournal` and resumed when the queue is reopened after a restart,
+/// and each image's result is recorded only once, however often it runs.
+///
+/// ```no_run
+/// # async fn run(cluster: std::sync::Arc<mcp::RadiologyCluster>, image: mcp::RadiologyImage) -> Result<(), mcp::RadiologyError> {
+/// use mcp::queue::{JobQueue, Overflow, Priority, QueueConfig};
+///
+/// let queue = JobQueue::new(cluster, QueueConfig::new().capacity(500).overflow(Overflow::Reject).default_context_limit(4));
+/// let job = queue.submit("ct", image, Priority::Stat).await?;
+/// let status = queue.wait(job).await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct JobQueue {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    cluster: Arc<RadiologyCluster>,
+    config: QueueConfig,
+    state: Mutex<State>,
+    // Signalled whenever a queued job starts, freeing buffer space
+    space: Notify,
+    // Signalled whenever a job finishes, for contexts being closed
+    finished: Notify,
+    journal: Option<JobJournal>,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    // Ordered by priority, then submission order
+    queued: BTreeMap<(Priority, JobId), Job>,
+    running: usize,
+    running_per_context: HashMap<String, usize>,
+    running_per_backend: HashMap<String, usize>,
+    // Buffer slots claimed by jobs still being journalled
+    reserved: usize,
+    reserved_per_context: HashMap<String, usize>,
+    // Recovered jobs whose context hasn't been initialized yet
+    parked: Vec<JournalEntry>,
+    statuses: HashMap<JobId, watch::Sender<JobStatus>>,
+    // Finished jobs, oldest first, so their statuses can be pruned
+    finished: VecDeque<JobId>,
+    // Contexts being closed through the queue; they take no new jobs
+    closing: HashSet<String>,
+}
+
+impl State {
+    // Keep only the statuses of the most recently finished jobs
+    fn finish(&mut self, id: JobId, retain: usize) {
+        self.finished.push_back(id);
+        while self.finished.len() > retain {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+}
+
+struct Job {
+    id: JobId,
+    context_id: String,
+    // The context's backend when the job started; `None` while queued, or if the context is gone
+    backend: Option<String>,
+    priority: Priority,
+    image: RadiologyImage,
+}
+
+// Why a job couldn't be queued
+enum Refused {
+    // The buffer is full; the job is handed back
+    Full(Box<Job>),
+    Failed(RadiologyError),
+}
+
+impl JobQueue {
+    pub fn new(cluster: Arc<RadiologyCluster>, config: QueueConfig) -> Self {
+        Self::build(cluster, config, None)
+    }
+
+    /// A durable queue that resumes the jobs left in `journal` by an earlier run.
+    ///
+    /// Recovered jobs keep their ids and priorities and may exceed the capacity.
+    /// A job whose context isn't initialized on `cluster` yet stays journalled
+    /// and `Queued` until the context appears; see `resume`.
+    pub async fn with_journal(cluster: Arc<RadiologyCluster>, config: QueueConfig, journal: JobJournal) -> Result<Self, RadiologyError> {
+        let pending = blocking(&journal, JobJournal::pending).await?;
+        let queue = Self::build(cluster, config, Some(journal));
+        {
+            let mut state = queue.inner.state.lock().unwrap();
+            for entry in pending {
+                state.statuses.insert(entry.id, watch::Sender::new(JobStatus::Queued));
+                state.parked.push(entry);
+            }
+        }
+        queue.inner.dispatch();
+        let parked = queue.parked();
+        if parked > 0 {
+            println!("Holding {} recovered jobs until their contexts are initialized", parked);
+        }
+        Ok(queue)
+    }
+
+    fn build(cluster: Arc<RadiologyCluster>, config: QueueConfig, journal: Option<JobJournal>) -> Self {
+        JobQueue {
+            inner: Arc::new(Inner {
+                cluster,
+                config,
+                state: Mutex::new(State::default()),
+                space: Notify::new(),
+                finished: Notify::new(),
+                journal,
+            }),
+        }
+    }
+
+    /// Queue an image for analysis, waiting for space or failing when full as configured
+    pub async fn submit(&self, context_id: &str, image: RadiologyImage, priority: Priority) -> Result<JobId, RadiologyError> {
+        let mut job = self.inner.job(context_id, image, priority)?;
+        let job = loop {
+            // Register for a wakeup before checking, so a slot freed in between isn't missed
+            let space = self.inner.space.notified();
+            match self.inner.reserve(job) {
+                Ok(job) => break job,
+                Err(Refused::Full(rejected)) if self.inner.config.overflow == Overflow::Wait => {
+                    job = rejected;
+                    space.await;
+                }
+                Err(refused) => return Err(self.inner.refusal(refused)),
+            }
+        };
+        self.admit(job).await
+    }
+
+    /// Queue an image only if there is space right now; a durable queue still
+    /// waits for the job to be journalled
+    pub async fn try_submit(&self, context_id: &str, image: RadiologyImage, priority: Priority) -> Result<JobId, RadiologyError> {
+        let job = self.inner.job(context_id, image, priority)?;
+        let job = self.inner.reserve(job).map_err(|refused| self.inner.refusal(refused))?;
+        self.admit(job).await
+    }
+
+    async fn admit(&self, job: Box<Job>) -> Result<JobId, RadiologyError> {
+        if self.inner.journal.is_none() {
+            return self.inner.admit(job);
+        }
+        // Journalling blocks on disk, and runs to the end even if this future is dropped
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || inner.admit(job))
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+
+    /// Start the recovered jobs whose context has been initialized since the
+    /// queue was opened, and queued jobs that a context moved to a less busy
+    /// backend may now run. This also happens whenever a job is submitted or finishes.
+    pub fn resume(&self) {
+        self.inner.dispatch();
+    }
+
+    /// Recovered jobs waiting for their context to be initialized
+    pub fn parked(&self) -> usize {
+        self.inner.state.lock().unwrap().parked.len()
+    }
+
+    /// Current status of a job, or `None` for an id this queue never issued or
+    /// one that finished before the last `retain_finished` jobs
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        let state = self.inner.state.lock().unwrap();
+        state.statuses.get(&id).map(|status| status.borrow().clone())
+    }
+
+    /// Wait until a job completes or fails and return its final status; a waiter
+    /// gets it even if the status is pruned in the meantime
+    pub async fn wait(&self, id: JobId) -> Option<JobStatus> {
+        let mut status = self.inner.state.lock().unwrap().statuses.get(&id)?.subscribe();
+        let finished = status.wait_for(JobStatus::is_finished).await.ok()?;
+        Some(finished.clone())
+    }
+
+    /// Close a context once every job queued for it has finished.
+    ///
+    /// New jobs for the context are refused with `ContextClosed` straight away;
+    /// then the context is closed on the cluster, which also waits for
+    /// submissions made outside the queue.
+    pub async fn close_context(&self, context_id: &str) -> Result<ContextInfo, RadiologyError> {
+        self.inner.cluster.context(context_id)?;
+        self.inner.state.lock().unwrap().closing.insert(context_id.to_string());
+        // Recovered jobs for the context are run before it closes
+        self.inner.dispatch();
+        println!("Draining queued jobs for context '{}'", context_id);
+        loop {
+            // Register for a wakeup before checking, so a job finishing in between isn't missed
+            let finished = self.inner.finished.notified();
+            if !self.inner.has_jobs_for(context_id) {
+                break;
+            }
+            finished.await;
+        }
+        let closed = self.inner.cluster.close_context(context_id).await;
+        self.inner.state.lock().unwrap().closing.remove(context_id);
+        closed
+    }
+
+    /// Jobs waiting to start
+    pub fn queued(&self) -> usize {
+        self.inner.state.lock().unwrap().queued.len()
+    }
+
+    /// Jobs currently being analyzed
+    pub fn running(&self) -> usize {
+        self.inner.state.lock().unwrap().running
+    }
+}
+
+impl Inner {
+    // A job for an open context; its id is assigned once it is queued
+    fn job(&self, context_id: &str, image: RadiologyImage, priority: Priority) -> Result<Box<Job>, RadiologyError> {
+        self.cluster.backend_of(context_id)?;
+        Ok(Box::new(Job {
+            id: JobId(0),
+            context_id: context_id.to_string(),
+            backend: None,
+            priority,
+            image,
+        }))
+    }
+
+    // Claim a buffer slot, so the job can be journalled without holding the lock
+    fn reserve(&self, job: Box<Job>) -> Result<Box<Job>, Refused> {
+        let mut state = self.state.lock().unwrap();
+        if state.closing.contains(&job.context_id) {
+            return Err(Refused::Failed(RadiologyError::ContextClosed(job.context_id.clone())));
+        }
+        if state.queued.len() + state.reserved >= self.config.capacity {
+            return Err(Refused::Full(job));
+        }
+        state.reserved += 1;
+        *state.reserved_per_context.entry(job.context_id.clone()).or_default() += 1;
+        Ok(job)
+    }
+
+    // Queue a job in its reserved slot. A durable queue journals the job before
+    // it becomes visible, so an id is never handed out for a job that could be lost
+    fn admit(self: &Arc<Self>, mut job: Box<Job>) -> Result<JobId, RadiologyError> {
+        let journalled = match &self.journal {
+            Some(journal) => journal.next_id().and_then(|id| {
+                journal.append(&JournalEntry {
+                    id,
+                    context_id: job.context_id.clone(),
+                    priority: job.priority,
+                    image: job.image.clone(),
+                    state: JournalState::Queued,
+                })?;
+                Ok(Some(id))
+            }),
+            None => Ok(None),
+        };
+
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            state.reserved -= 1;
+            decrement(&mut state.reserved_per_context, &job.context_id);
+            let id = match journalled {
+                Ok(Some(id)) => id,
+                Ok(None) => {
+                    state.next_id += 1;
+                    JobId(state.next_id)
+                }
+                Err(err) => {
+                    drop(state);
+                    self.space.notify_waiters();
+                    return Err(err.into());
+                }
+            };
+            job.id = id;
+            state.queued.insert((job.priority, id), *job);
+            state.statuses.insert(id, watch::Sender::new(JobStatus::Queued));
+            id
+        };
+        self.dispatch();
+        Ok(id)
+    }
+
+    // Start every queued job the concurrency limits allow
+    fn dispatch(self: &Arc<Self>) {
+        let mut started = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            self.unpark(&mut state);
+            while state.running < self.config.max_concurrent {
+                // Look the backend up now: the context may have moved to another one while the job waited
+                let Some((key, backend)) = state.queued.iter().find_map(|(key, job)| {
+                    let backend = self.cluster.backend_of(&job.context_id).ok();
+                    self.may_start(&state, &job.context_id, backend.as_deref()).then_some((*key, backend))
+                }) else {
+                    break;
+                };
+                let mut job = state.queued.remove(&key).expect("key was just found");
+                state.running += 1;
+                *state.running_per_context.entry(job.context_id.clone()).or_default() += 1;
+                if let Some(backend) = &backend {
+                    *state.running_per_backend.entry(backend.clone()).or_default() += 1;
+                }
+                job.backend = backend;
+                if let Some(status) = state.statuses.get(&job.id) {
+                    status.send_replace(JobStatus::Running);
+                }
+                started.push(job);
+            }
+        }
+
+        if !started.is_empty() {
+            self.space.notify_waiters();
+        }
+        for job in started {
+            let inner = self.clone();
+            tokio::spawn(async move { inner.run(job).await });
+        }
+    }
+
+    // Queue the recovered jobs whose context is now initialized
+    fn unpark(&self, state: &mut State) {
+        for entry in std::mem::take(&mut state.parked) {
+            if self.cluster.backend_of(&entry.context_id).is_err() {
+                state.parked.push(entry);
+                continue;
+            }
+            println!("Resuming {} ({:?}) for context '{}'", entry.id, entry.state, entry.context_id);
+            let job = Job {
+                id: entry.id,
+                context_id: entry.context_id,
+                backend: None,
+                priority: entry.priority,
+                image: entry.image,
+            };
+            state.queued.insert((job.priority, job.id), job);
+        }
+    }
+
+    fn has_jobs_for(&self, context_id: &str) -> bool {
+        let state = self.state.lock().unwrap();
+        state.running_per_context.contains_key(context_id)
+            || state.reserved_per_context.contains_key(context_id)
+            || state.queued.values().any(|job| job.context_id == context_id)
+    }
+
+    // A job whose context is gone has no backend; it may start, and fails at once
+    fn may_start(&self, state: &State, context_id: &str, backend: Option<&str>) -> bool {
+        let running_in_context = state.running_per_context.get(context_id).copied().unwrap_or(0);
+        self.config.limit_for_context(context_id).is_none_or(|limit| running_in_context < limit)
+            && backend.is_none_or(|backend| {
+                let running_on_backend = state.running_per_backend.get(backend).copied().unwrap_or(0);
+                self.config.limit_for_backend(backend).is_none_or(|limit| running_on_backend < limit)
+            })
+    }
+
+    fn refusal(&self, refused: Refused) -> RadiologyError {
+        match refused {
+            Refused::Full(_) => RadiologyError::QueueFull {
+                capacity: self.config.capacity,
+            },
+            Refused::Failed(err) => err,
+        }
+    }
+
+    async fn run(self: Arc<Self>, job: Job) {
+        if let Some(journal) = &self.journal {
+            let id = job.id;
+            if let Err(err) = blocking(journal, move |journal| journal.mark_in_flight(id)).await {
+                println!("Failed to journal {} as in flight: {}", id, err);
+            }
+        }
+        let outcome = match self.journal {
+            Some(_) => self.run_durable(&job.context_id, job.image).await,
+            None => self.cluster.submit_image(&job.context_id, job.image).await,
+        };
+        let status = match outcome {
+            Ok(result) => JobStatus::Completed(result),
+            Err(err) => JobStatus::Failed(err.to_string()),
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.running -= 1;
+            decrement(&mut state.running_per_context, &job.context_id);
+            if let Some(backend) = &job.backend {
+                decrement(&mut state.running_per_backend, backend);
+            }
+            if let Some(sender) = state.statuses.get(&job.id) {
+                sender.send_replace(status);
+            }
+            state.finish(job.id, self.config.retain_finished);
+        }
+        self.forget(job.id).await;
+        self.finished.notify_waiters();
+        self.dispatch();
+    }
+
+    // The job may have run before a crash; if its result was recorded, don't analyze the image again
+    async fn run_durable(&self, context_id: &str, image: RadiologyImage) -> Result<RadiologyResult, RadiologyError> {
+        let query = ResultQuery::for_context(context_id).image_id(&image.image_id);
+        if let Some(stored) = self.cluster.query_results(&query).await?.into_iter().next() {
+            println!("Image {} already has a result in context '{}'", image.image_id, context_id);
+            return Ok(stored);
+        }
+        self.cluster.submit_image_recorded_once(context_id, image).await
+    }
+
+    // Drop a finished job from the journal
+    async fn forget(&self, id: JobId) {
+        if let Some(journal) = &self.journal {
+            if let Err(err) = blocking(journal, move |journal| journal.finish(id)).await {
+                println!("Failed to remove {} from the journal: {}", id, err);
+            }
+        }
+    }
+}
+
+// Run journal I/O on the blocking pool rather than a runtime worker
+async fn blocking<T: Send + 'static>(
+    journal: &JobJournal,
+    io: impl FnOnce(&JobJournal) -> Result<T, StoreError> + Send + 'static,
+) -> Result<T, StoreError> {
+    let journal = journal.clone();
+    tokio::task::spawn_blocking(move || io(&journal))
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+}
+
+fn decrement(counts: &mut HashMap<String, usize>, key: &str) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::RadiologyError;
+
+/// How long to wait between attempts
+#[derive(Clone, Debug, PartialEq)]
+pub enum Backoff {
+    /// The same pause every time
+    Fixed(Duration),
+    /// `initial * multiplier^n`, capped at `max`; with `jitter` drawn from the upper half of that
+    Exponential {
+        initial: Duration,
+        max: Duration,
+        multiplier: f64,
+        jitter: bool,
+    },
+    /// Each pause drawn between `base` and three times the previous one, capped at `max`
+    DecorrelatedJitter { base: Duration, max: Duration },
+}
+
+impl Backoff {
+    /// The sequence of pauses before each retry
+    pub fn delays(&self) -> Delays {
+        let previous = match self {
+            Backoff::DecorrelatedJitter { base, .. } => *base,
+            _ => Duration::ZERO,
+        };
+        Delays {
+            backoff: self.clone(),
+            attempt: 0,
+            previous,
+        }
+    }
+}
+
+/// Iterator over the pauses of a `Backoff`; never ends
+pub struct Delays {
+    backoff: Backoff,
+    attempt: u32,
+    previous: Duration,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential {
+                initial,
+                max,
+                multiplier,
+                jitter,
+            } => exponential_delay(initial, max, multiplier, jitter, self.attempt),
+            Backoff::DecorrelatedJitter { base, max } => {
+                let upper = (self.previous * 3).max(base);
+                rand::thread_rng().gen_range(base..=upper).min(max)
+            }
+        };
+        self.attempt = self.attempt.saturating_add(1);
+        self.previous = delay;
+        Some(delay)
+    }
+}
+
+pub(crate) fn exponential_delay(initial: Duration, max: Duration, multiplier: f64, jitter: bool, attempt: u32) -> Duration {
+    let exponential = initial.as_secs_f64() * multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+    // A multiplier below zero or NaN would make the delay invalid; wait the maximum instead
+    let capped = Duration::try_from_secs_f64(exponential.min(max.as_secs_f64())).unwrap_or(max);
+    if jitter {
+        rand::thread_rng().gen_range(capped / 2..=capped)
+    } else {
+        capped
+    }
+}
+
+type Predicate = Arc<dyn Fn(&RadiologyError) -> bool + Send + Sync>;
+
+/// When and how often a failed operation is attempted again.
+///
+/// Used by `Connector` for connection setup and by
+/// `RadiologyCluster::with_retry_policy` for `submit_image`. Only errors the
+/// predicate accepts are retried, `RadiologyError::is_retryable` by default.
+///
+/// ```
+/// use std::time::Duration;
+/// use mcp::retry::{Backoff, RetryPolicy};
+///
+/// let policy = RetryPolicy::new(Backoff::DecorrelatedJitter {
+///     base: Duration::from_millis(100),
+///     max: Duration::from_secs(5),
+/// })
+/// .max_attempts(5)
+/// .max_elapsed(Duration::from_secs(30));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    backoff: Backoff,
+    max_attempts: Option<u32>,
+    max_elapsed: Option<Duration>,
+    retryable: Predicate,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryConfig::default().into()
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("backoff", &self.backoff)
+            .field("max_attempts", &self.max_attempts)
+            .field("max_elapsed", &self.max_elapsed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    /// Three attempts spaced by `backoff`, retrying errors that are `is_retryable`
+    pub fn new(backoff: Backoff) -> Self {
+        RetryPolicy {
+            backoff,
+            max_attempts: Some(3),
+            max_elapsed: None,
+            retryable: Arc::new(RadiologyError::is_retryable),
+        }
+    }
+
+    /// A single attempt; failures are returned as they are
+    pub fn none() -> Self {
+        Self::new(Backoff::Fixed(Duration::ZERO)).max_attempts(1)
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Total number of attempts, the first included (at least one is always made)
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Keep retrying until the operation succeeds or a non-retryable error occurs
+    pub fn unlimited_attempts(mut self) -> Self {
+        self.max_attempts = None;
+        self
+    }
+
+    /// Give up rather than start a pause that would end past this much time since the first attempt
+    pub fn max_elapsed(mut self, elapsed: Duration) -> Self {
+        self.max_elapsed = Some(elapsed);
+        self
+    }
+
+    /// Retry only errors for which `predicate` returns true
+    pub fn retry_if(mut self, predicate: impl Fn(&RadiologyError) -> bool + Send + Sync + 'static) -> Self {
+        self.retryable = Arc::new(predicate);
+        self
+    }
+
+    pub fn is_retryable(&self, err: &RadiologyError) -> bool {
+        (self.retryable)(err)
+    }
+
+    /// Run `operation` until it succeeds or the policy gives up, returning the last error
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, RadiologyError>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, RadiologyError>>,
+    {
+        let started = Instant::now();
+        let mut delays = self.backoff.delays();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            let err = match operation(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            let exhausted = self.max_attempts.is_some_and(|max| attempt >= max);
+            if exhausted || !self.is_retryable(&err) {
+                return Err(err);
+            }
+            let delay = delays.next().unwrap_or_default();
+            if self.max_elapsed.is_some_and(|max| started.elapsed() + delay > max) {
+                return Err(err);
+            }
+
+            println!("Attempt {} failed: {}. Retrying in {:?}...", attempt, err, delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Failure to load a retry configuration
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid config file")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("invalid value '{value}' for {key}")]
+    InvalidValue { key: String, value: String },
+}
+
+/// Backoff strategy named in a `RetryConfig`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryStrategy {
+    Fixed,
+    Exponential,
+    DecorrelatedJitter,
+}
+
+/// Serializable form of a `RetryPolicy`, read from a JSON file or the environment.
+///
+/// `fixed` waits `initial_delay_ms` every time; the other strategies grow from it
+/// up to `max_delay_ms`. `max_attempts` and `max_elapsed_ms` of `null` mean no limit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub strategy: RetryStrategy,
+    pub max_attempts: Option<u32>,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    pub jitter: bool,
+    pub max_elapsed_ms: Option<u64>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            strategy: RetryStrategy::Exponential,
+            max_attempts: Some(3),
+            initial_delay_ms: 500,
+            max_delay_ms: 10_000,
+            multiplier: 2.0,
+            jitter: true,
+            max_elapsed_ms: None,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Read a JSON config file; missing fields keep their defaults
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path)?;
+        let config: Self = serde_json::from_str(&text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that the delays can grow as configured: `multiplier` is finite and
+    /// at least 1, and for a growing backoff `initial_delay_ms` doesn't exceed `max_delay_ms`
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !self.multiplier.is_finite() || self.multiplier < 1.0 {
+            return Err(ConfigError::InvalidValue {
+                key: "multiplier".to_string(),
+                value: self.multiplier.to_string(),
+            });
+        }
+        if self.strategy != RetryStrategy::Fixed && self.initial_delay_ms > self.max_delay_ms {
+            return Err(ConfigError::InvalidValue {
+                key: "initial_delay_ms".to_string(),
+                value: format!("{} (above max_delay_ms {})", self.initial_delay_ms, self.max_delay_ms),
+            });
+        }
+        Ok(())
+    }
+
+    /// Build a config from `{prefix}_*` environment variables.
+    ///
+    /// Starts from the file named by `{prefix}_CONFIG` when set, then applies
+    /// `{prefix}_STRATEGY`, `_MAX_ATTEMPTS`, `_INITIAL_DELAY_MS`, `_MAX_DELAY_MS`,
+    /// `_MULTIPLIER`, `_JITTER` and `_MAX_ELAPSED_MS`. `none` clears a limit. The
+    /// result is checked with `validate`.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let mut config = match std::env::var(format!("{}_CONFIG", prefix)) {
+            Ok(path) => Self::from_file(path)?,
+            Err(_) => Self::default(),
+        };
+
+        if let Some(strategy) = env_value(prefix, "STRATEGY", |v| match v {
+            "fixed" => Some(RetryStrategy::Fixed),
+            "exponential" => Some(RetryStrategy::Exponential),
+            "decorrelated_jitter" => Some(RetryStrategy::DecorrelatedJitter),
+            _ => None,
+        })? {
+            config.strategy = strategy;
+        }
+        if let Some(attempts) = env_value(prefix, "MAX_ATTEMPTS", parse_limit)? {
+            config.max_attempts = attempts;
+        }
+        if let Some(delay) = env_value(prefix, "INITIAL_DELAY_MS", |v| v.parse().ok())? {
+            config.initial_delay_ms = delay;
+        }
+        if let Some(delay) = env_value(prefix, "MAX_DELAY_MS", |v| v.parse().ok())? {
+            config.max_delay_ms = delay;
+        }
+        let valid_multiplier = |m: &f64| m.is_finite() && *m >= 1.0;
+        if let Some(multiplier) = env_value(prefix, "MULTIPLIER", |v| v.parse().ok().filter(valid_multiplier))? {
+            config.multiplier = multiplier;
+        }
+        if let Some(jitter) = env_value(prefix, "JITTER", |v| v.parse().ok())? {
+            config.jitter = jitter;
+        }
+        if let Some(elapsed) = env_value(prefix, "MAX_ELAPSED_MS", parse_limit)? {
+            config.max_elapsed_ms = elapsed;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        let initial = Duration::from_millis(self.initial_delay_ms);
+        let max = Duration::from_millis(self.max_delay_ms);
+        match self.strategy {
+            RetryStrategy::Fixed => Backoff::Fixed(initial),
+            RetryStrategy::Exponential => Backoff::Exponential {
+                initial,
+                max,
+                multiplier: self.multiplier,
+                jitter: self.jitter,
+            },
+            RetryStrategy::DecorrelatedJitter => Backoff::DecorrelatedJitter { base: initial, max },
+        }
+    }
+}
+
+impl From<RetryConfig> for RetryPolicy {
+    fn from(config: RetryConfig) -> Self {
+        let mut policy = RetryPolicy::new(config.backoff());
+        policy.max_attempts = config.max_attempts;
+        policy.max_elapsed = config.max_elapsed_ms.map(Duration::from_millis);
+        policy
+    }
+}
+
+// Reads `{prefix}_{key}`; unset is `None`, unparseable is an error
+fn env_value<T>(prefix: &str, key: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Option<T>, ConfigError> {
+    let key = format!("{}_{}", prefix, key);
+    match std::env::var(&key) {
+        Ok(value) => match parse(value.trim()) {
+            Some(parsed) => Ok(Some(parsed)),
+            None => Err(ConfigError::InvalidValue { key, value }),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+fn parse_limit<T: std::str::FromStr>(value: &str) -> Option<Option<T>> {
+    if value.eq_ignore_ascii_case("none") {
+        Some(None)
+    } else {
+        value.parse().ok().map(Some)
+    }
+}
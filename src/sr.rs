@@ -0,0 +1,582 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+
+use crate::findings::{scheme_designator, Finding, Laterality, Severity};
+use crate::study::{image_uid, SERIES_UID_KEY, STUDY_UID_KEY};
+use crate::{RadiologyImage, RadiologyResult};
+
+/// SOP Class of the exported reports
+pub const COMPREHENSIVE_SR_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.88.33";
+/// Identifies this exporter in the file meta information
+pub const IMPLEMENTATION_CLASS_UID: &str = "2.25.287220931991244683184101117466244867870";
+/// Private coding scheme for concepts without a standard code, such as the model's confidence
+pub const LOCAL_SCHEME: &str = "99MCP";
+
+const EXPLICIT_VR_LITTLE_ENDIAN: &str = crate::dicom::EXPLICIT_VR_LITTLE_ENDIAN;
+const MAX_UID_LEN: usize = 64;
+
+type Tag = (u16, u16);
+
+/// Errors raised while exporting a structured report
+#[derive(Debug, thiserror::Error)]
+pub enum SrError {
+    #[error("failed to write structured report")]
+    Io(#[from] std::io::Error),
+    #[error("structured report has no Study Instance UID")]
+    MissingStudyInstanceUid,
+    #[error("structured report references no images")]
+    NoReferencedImages,
+    #[error("'{0}' is not a valid DICOM UID")]
+    InvalidUid(String),
+    #[error("{0} is not a finite number")]
+    NonFiniteValue(String),
+}
+
+/// An analyzed image the report refers to, by its original (not de-identified) UIDs
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ReferencedImage {
+    pub sop_class_uid: String,
+    pub sop_instance_uid: String,
+    pub series_instance_uid: String,
+    /// `None` for an image of the report's own study
+    pub study_instance_uid: Option<String>,
+}
+
+impl ReferencedImage {
+    pub fn new(sop_class_uid: &str, sop_instance_uid: &str, series_instance_uid: &str) -> Self {
+        ReferencedImage {
+            sop_class_uid: sop_class_uid.to_string(),
+            sop_instance_uid: sop_instance_uid.to_string(),
+            series_instance_uid: series_instance_uid.to_string(),
+            study_instance_uid: None,
+        }
+    }
+
+    /// The UIDs of an image from its DICOM tags or `sop_class_uid` / `sop_instance_uid` /
+    /// `series_instance_uid` metadata; `None` when any of them is missing
+    pub fn from_image(image: &RadiologyImage) -> Option<Self> {
+        let tags = image.dicom.as_ref();
+        let sop_class_uid = tags
+            .and_then(|tags| tags.sop_class_uid.clone())
+            .or_else(|| image.metadata.get("sop_class_uid").cloned())?;
+        let sop_instance_uid = tags
+            .and_then(|tags| tags.sop_instance_uid.clone())
+            .or_else(|| image.metadata.get("sop_instance_uid").cloned())?;
+        Some(ReferencedImage {
+            sop_class_uid,
+            sop_instance_uid,
+            series_instance_uid: image_uid(image, SERIES_UID_KEY)?,
+            study_instance_uid: image_uid(image, STUDY_UID_KEY),
+        })
+    }
+}
+
+/// Exports a `RadiologyResult` as a DICOM Comprehensive SR Part 10 file laid out
+/// after TID 1500 (Measurement Report).
+///
+/// The document holds an image library of the referenced images, one measurement
+/// group per structured finding with its codes, laterality, site, severity,
+/// measurements and confidence, and the impression or free-text findings under
+/// qualitative evaluations. It is marked unverified, as no reader has signed it.
+///
+/// ```no_run
+/// use mcp::sr::{ReferencedImage, StructuredReport};
+/// # fn export(result: &mcp::RadiologyResult, image: &mcp::RadiologyImage) -> Result<(), mcp::sr::SrError> {
+/// StructuredReport::new(result)
+///     .reference(ReferencedImage::from_image(image).unwrap())
+///     .patient_id("P12345")
+///     .write("report.dcm")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StructuredReport {
+    result: RadiologyResult,
+    images: BTreeSet<ReferencedImage>,
+    study_instance_uid: Option<String>,
+    series_instance_uid: String,
+    sop_instance_uid: String,
+    patient_id: String,
+    patient_name: String,
+    accession_number: String,
+    observer_uid: String,
+}
+
+impl StructuredReport {
+    /// A report for `result` in a new series, with new UIDs for the series, the document and the observer
+    pub fn new(result: &RadiologyResult) -> Self {
+        StructuredReport {
+            result: result.clone(),
+            images: BTreeSet::new(),
+            study_instance_uid: result.study_instance_uid.clone(),
+            series_instance_uid: generate_uid(),
+            sop_instance_uid: generate_uid(),
+            patient_id: String::new(),
+            patient_name: String::new(),
+            accession_number: String::new(),
+            observer_uid: generate_uid(),
+        }
+    }
+
+    pub fn reference(mut self, image: ReferencedImage) -> Self {
+        self.images.insert(image);
+        self
+    }
+
+    pub fn references(mut self, images: impl IntoIterator<Item = ReferencedImage>) -> Self {
+        self.images.extend(images);
+        self
+    }
+
+    /// Defaults to the result's study, then to the study of the first referenced image
+    pub fn study_instance_uid(mut self, uid: &str) -> Self {
+        self.study_instance_uid = Some(uid.to_string());
+        self
+    }
+
+    pub fn series_instance_uid(mut self, uid: &str) -> Self {
+        self.series_instance_uid = uid.to_string();
+        self
+    }
+
+    pub fn sop_instance_uid(mut self, uid: &str) -> Self {
+        self.sop_instance_uid = uid.to_string();
+        self
+    }
+
+    pub fn patient_id(mut self, id: &str) -> Self {
+        self.patient_id = id.to_string();
+        self
+    }
+
+    /// In DICOM person name form, e.g. `Doe^Jane`
+    pub fn patient_name(mut self, name: &str) -> Self {
+        self.patient_name = name.to_string();
+        self
+    }
+
+    pub fn accession_number(mut self, number: &str) -> Self {
+        self.accession_number = number.to_string();
+        self
+    }
+
+    /// Device Observer UID identifying the analysis system in the report
+    pub fn observer_uid(mut self, uid: &str) -> Self {
+        self.observer_uid = uid.to_string();
+        self
+    }
+
+    /// SOP Instance UID of the document
+    pub fn uid(&self) -> &str {
+        &self.sop_instance_uid
+    }
+
+    /// The report as the bytes of a Part 10 file in explicit VR little endian
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SrError> {
+        let dataset = self.dataset()?;
+
+        let mut meta = Dataset::new();
+        meta.insert((0x0002, 0x0001), Value::Bytes("OB", vec![0, 1]));
+        meta.insert((0x0002, 0x0002), uid(COMPREHENSIVE_SR_STORAGE));
+        meta.insert((0x0002, 0x0003), uid(&self.sop_instance_uid));
+        meta.insert((0x0002, 0x0010), uid(EXPLICIT_VR_LITTLE_ENDIAN));
+        meta.insert((0x0002, 0x0012), uid(IMPLEMENTATION_CLASS_UID));
+        meta.insert((0x0002, 0x0013), string("SH", &format!("MCP_{}", env!("CARGO_PKG_VERSION"))));
+        let mut meta_bytes = Vec::new();
+        encode(&meta, &mut meta_bytes);
+
+        let mut bytes = vec![0; 128];
+        bytes.extend_from_slice(b"DICM");
+        let group_length = Value::Bytes("UL", (meta_bytes.len() as u32).to_le_bytes().to_vec());
+        encode(&BTreeMap::from([((0x0002, 0x0000), group_length)]), &mut bytes);
+        bytes.extend_from_slice(&meta_bytes);
+        encode(&dataset, &mut bytes);
+        Ok(bytes)
+    }
+
+    /// Write the report as a Part 10 file
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SrError> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    fn dataset(&self) -> Result<Dataset, SrError> {
+        let study_uid = self
+            .study_instance_uid
+            .clone()
+            .or_else(|| self.images.iter().find_map(|image| image.study_instance_uid.clone()))
+            .ok_or(SrError::MissingStudyInstanceUid)?;
+        if self.images.is_empty() {
+            return Err(SrError::NoReferencedImages);
+        }
+        let uids = [&study_uid, &self.series_instance_uid, &self.sop_instance_uid, &self.observer_uid];
+        let referenced = self
+            .images
+            .iter()
+            .flat_map(|image| [&image.sop_class_uid, &image.sop_instance_uid, &image.series_instance_uid]);
+        for value in uids.into_iter().chain(referenced) {
+            check_uid(value)?;
+        }
+        self.check_values()?;
+
+        let analyzed = DateTime::parse_from_rfc3339(&self.result.analysis_date)
+            .map(|date| date.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        let date = analyzed.format("%Y%m%d").to_string();
+        let time = analyzed.format("%H%M%S").to_string();
+
+        let mut dataset = Dataset::new();
+        dataset.insert((0x0008, 0x0005), string("CS", "ISO_IR 192"));
+        dataset.insert((0x0008, 0x0016), uid(COMPREHENSIVE_SR_STORAGE));
+        dataset.insert((0x0008, 0x0018), uid(&self.sop_instance_uid));
+        dataset.insert((0x0008, 0x0020), string("DA", ""));
+        dataset.insert((0x0008, 0x0023), string("DA", &date));
+        dataset.insert((0x0008, 0x0030), string("TM", ""));
+        dataset.insert((0x0008, 0x0033), string("TM", &time));
+        dataset.insert((0x0008, 0x0050), string("SH", &self.accession_number));
+        dataset.insert((0x0008, 0x0060), string("CS", "SR"));
+        dataset.insert((0x0008, 0x0070), string("LO", ""));
+        dataset.insert((0x0008, 0x0090), string("PN", ""));
+        dataset.insert((0x0008, 0x1111), Value::Sequence(Vec::new()));
+        dataset.insert((0x0010, 0x0010), string("PN", &self.patient_name));
+        dataset.insert((0x0010, 0x0020), string("LO", &self.patient_id));
+        dataset.insert((0x0010, 0x0030), string("DA", ""));
+        dataset.insert((0x0010, 0x0040), string("CS", ""));
+        dataset.insert((0x0020, 0x000D), uid(&study_uid));
+        dataset.insert((0x0020, 0x000E), uid(&self.series_instance_uid));
+        dataset.insert((0x0020, 0x0010), string("SH", ""));
+        dataset.insert((0x0020, 0x0011), string("IS", "1"));
+        dataset.insert((0x0020, 0x0013), string("IS", "1"));
+        dataset.insert((0x0040, 0xA372), Value::Sequence(Vec::new()));
+        dataset.insert((0x0040, 0xA375), Value::Sequence(self.evidence(&study_uid)));
+        dataset.insert((0x0040, 0xA491), string("CS", "COMPLETE"));
+        dataset.insert((0x0040, 0xA493), string("CS", "UNVERIFIED"));
+        dataset.insert((0x0040, 0xA504), Value::Sequence(vec![BTreeMap::from([
+            ((0x0008, 0x0105), string("CS", "DCMR")),
+            ((0x0040, 0xDB00), string("CS", "1500")),
+        ])]));
+
+        // The root container's item attributes sit at the top level of the dataset
+        let root = container(None, concept("126000", "DCM", "Imaging Measurement Report"), self.content());
+        dataset.extend(root);
+        Ok(dataset)
+    }
+
+    // Current Requested Procedure Evidence: the referenced images grouped by study and series
+    fn evidence(&self, study_uid: &str) -> Vec<Dataset> {
+        let mut studies: BTreeMap<&str, BTreeMap<&str, Vec<&ReferencedImage>>> = BTreeMap::new();
+        for image in &self.images {
+            let study = image.study_instance_uid.as_deref().unwrap_or(study_uid);
+            studies.entry(study).or_default().entry(&image.series_instance_uid).or_default().push(image);
+        }
+
+        studies
+            .into_iter()
+            .map(|(study, series)| {
+                let series = series
+                    .into_iter()
+                    .map(|(series, images)| {
+                        BTreeMap::from([
+                            ((0x0008, 0x1199), Value::Sequence(images.into_iter().map(sop_reference).collect())),
+                            ((0x0020, 0x000E), uid(series)),
+                        ])
+                    })
+                    .collect();
+                BTreeMap::from([((0x0008, 0x1115), Value::Sequence(series)), ((0x0020, 0x000D), uid(study))])
+            })
+            .collect()
+    }
+
+    // NaN and infinities have no decimal string form
+    fn check_values(&self) -> Result<(), SrError> {
+        if !self.result.confidence_score.is_finite() {
+            return Err(SrError::NonFiniteValue("confidence score".to_string()));
+        }
+        let findings = self.result.report.iter().flat_map(|report| &report.findings);
+        for (index, finding) in findings.enumerate() {
+            if finding.measurements.iter().any(|measurement| !measurement.value.is_finite()) {
+                return Err(SrError::NonFiniteValue(format!("a measurement of finding {}", index + 1)));
+            }
+            if finding.confidence.is_some_and(|confidence| !confidence.is_finite()) {
+                return Err(SrError::NonFiniteValue(format!("the confidence of finding {}", index + 1)));
+            }
+        }
+        Ok(())
+    }
+
+    fn content(&self) -> Vec<Dataset> {
+        let mut content = vec![
+            code_item(
+                "HAS CONCEPT MOD",
+                concept("121049", "DCM", "Language of Content Item and Descendants"),
+                concept("en-US", "RFC5646", "English (United States)"),
+            ),
+            code_item("HAS OBS CONTEXT", concept("121005", "DCM", "Observer Type"), concept("121007", "DCM", "Device")),
+            uidref_item("HAS OBS CONTEXT", concept("121012", "DCM", "Device Observer UID"), &self.observer_uid),
+            code_item(
+                "HAS CONCEPT MOD",
+                concept("121058", "DCM", "Procedure reported"),
+                concept("363679005", "SCT", "Imaging"),
+            ),
+        ];
+
+        let images = self.images.iter().map(image_item).collect();
+        let group = container(Some("CONTAINS"), concept("126200", "DCM", "Image Library Group"), images);
+        content.push(container(Some("CONTAINS"), concept("111028", "DCM", "Image Library"), vec![group]));
+
+        let findings = self.result.report.iter().flat_map(|report| &report.findings);
+        let groups: Vec<Dataset> = findings
+            .enumerate()
+            .map(|(index, finding)| measurement_group(index + 1, finding))
+            .collect();
+        if !groups.is_empty() {
+            content.push(container(Some("CONTAINS"), concept("126010", "DCM", "Imaging Measurements"), groups));
+        }
+
+        let mut evaluations = Vec::new();
+        let impression = self.result.report.as_ref().and_then(|report| report.impression.as_deref());
+        if let Some(impression) = impression {
+            evaluations.push(text_item("CONTAINS", concept("121073", "DCM", "Impression"), impression));
+        }
+        // Free-text findings only when they aren't already spelled out as measurement groups
+        if self.result.report.as_ref().is_none_or(|report| report.findings.is_empty()) {
+            evaluations.push(text_item("CONTAINS", concept("121070", "DCM", "Findings"), &self.result.findings));
+        }
+        evaluations.push(confidence_item(self.result.confidence_score));
+        content.push(container(Some("CONTAINS"), concept("C0034375", "UMLS", "Qualitative Evaluations"), evaluations));
+        content
+    }
+}
+
+/// A new UID under the `2.25` root, derived from a random 128-bit number
+pub fn generate_uid() -> String {
+    format!("2.25.{}", rand::thread_rng().gen::<u128>())
+}
+
+// One measurement group (TID 1501) per finding, numbered from 1
+fn measurement_group(number: usize, finding: &Finding) -> Dataset {
+    let tracking_id = format!("Finding {}", number);
+    let mut items = vec![
+        text_item("HAS OBS CONTEXT", concept("112039", "DCM", "Tracking Identifier"), &tracking_id),
+        uidref_item("HAS OBS CONTEXT", concept("112040", "DCM", "Tracking Unique Identifier"), &generate_uid()),
+    ];
+    for code in &finding.codes {
+        let meaning = code.meaning.as_deref().unwrap_or(&finding.description);
+        let value = concept(&code.code, &scheme_designator(&code.scheme), meaning);
+        items.push(code_item("CONTAINS", concept("121071", "DCM", "Finding"), value));
+    }
+    items.push(text_item("CONTAINS", concept("121071", "DCM", "Finding"), &finding.description));
+
+    // Laterality values of CID 244; a midline finding is described by its site instead
+    let laterality = match finding.laterality {
+        Some(Laterality::Left) => Some(concept("7771000", "SCT", "Left")),
+        Some(Laterality::Right) => Some(concept("24028007", "SCT", "Right")),
+        Some(Laterality::Bilateral) => Some(concept("51440002", "SCT", "Right and left")),
+        Some(Laterality::Midline) | None => None,
+    };
+    if let Some(laterality) = laterality {
+        items.push(code_item("HAS CONCEPT MOD", concept("272741003", "SCT", "Laterality"), laterality));
+    }
+    let site = match (finding.laterality, &finding.location) {
+        (Some(Laterality::Midline), Some(location)) => Some(format!("midline {}", location)),
+        (Some(Laterality::Midline), None) => Some("midline".to_string()),
+        (_, location) => location.clone(),
+    };
+    if let Some(site) = site {
+        items.push(text_item("HAS CONCEPT MOD", concept("363698007", "SCT", "Finding Site"), &site));
+    }
+
+    if let Some(severity) = finding.severity {
+        let value = match severity {
+            Severity::Normal => concept("17621005", "SCT", "Normal"),
+            Severity::Mild => concept("255604002", "SCT", "Mild"),
+            Severity::Moderate => concept("6736007", "SCT", "Moderate"),
+            Severity::Severe => concept("24484000", "SCT", "Severe"),
+            Severity::Critical => concept("442452003", "SCT", "Life threatening severity"),
+        };
+        items.push(code_item("HAS PROPERTIES", concept("246112005", "SCT", "Severity"), value));
+    }
+
+    for measurement in &finding.measurements {
+        let name = match &measurement.dimension {
+            Some(dimension) => concept(dimension, LOCAL_SCHEME, dimension),
+            None => concept("410668003", "SCT", "Length"),
+        };
+        let unit = concept(&measurement.unit, "UCUM", &measurement.unit);
+        items.push(num_item("CONTAINS", name, measurement.value, unit));
+    }
+    if let Some(confidence) = finding.confidence {
+        items.push(confidence_item(confidence));
+    }
+
+    container(Some("CONTAINS"), concept("125007", "DCM", "Measurement Group"), items)
+}
+
+fn confidence_item(confidence: f32) -> Dataset {
+    num_item(
+        "HAS PROPERTIES",
+        concept("confidence", LOCAL_SCHEME, "Confidence"),
+        f64::from(confidence),
+        concept("1", "UCUM", "no units"),
+    )
+}
+
+fn image_item(image: &ReferencedImage) -> Dataset {
+    let mut item = content_item(Some("CONTAINS"), "IMAGE", None);
+    item.insert((0x0008, 0x1199), Value::Sequence(vec![sop_reference(image)]));
+    item
+}
+
+fn sop_reference(image: &ReferencedImage) -> Dataset {
+    BTreeMap::from([
+        ((0x0008, 0x1150), uid(&image.sop_class_uid)),
+        ((0x0008, 0x1155), uid(&image.sop_instance_uid)),
+    ])
+}
+
+// A coded concept as an item of a code sequence; codes longer than 16 characters go in Long Code Value
+fn concept(value: &str, scheme: &str, meaning: &str) -> Dataset {
+    let value_tag = if value.len() > 16 { (0x0008, 0x0119) } else { (0x0008, 0x0100) };
+    let value_vr = if value.len() > 16 { "UC" } else { "SH" };
+    BTreeMap::from([
+        (value_tag, string(value_vr, value)),
+        ((0x0008, 0x0102), string("SH", scheme)),
+        ((0x0008, 0x0104), string("LO", meaning)),
+    ])
+}
+
+// Relationship type (absent for the root), value type and concept name of a content item
+fn content_item(relationship: Option<&str>, value_type: &str, name: Option<Dataset>) -> Dataset {
+    let mut item = Dataset::new();
+    if let Some(relationship) = relationship {
+        item.insert((0x0040, 0xA010), string("CS", relationship));
+    }
+    item.insert((0x0040, 0xA040), string("CS", value_type));
+    if let Some(name) = name {
+        item.insert((0x0040, 0xA043), Value::Sequence(vec![name]));
+    }
+    item
+}
+
+fn container(relationship: Option<&str>, name: Dataset, children: Vec<Dataset>) -> Dataset {
+    let mut item = content_item(relationship, "CONTAINER", Some(name));
+    item.insert((0x0040, 0xA050), string("CS", "SEPARATE"));
+    item.insert((0x0040, 0xA730), Value::Sequence(children));
+    item
+}
+
+fn text_item(relationship: &str, name: Dataset, text: &str) -> Dataset {
+    let mut item = content_item(Some(relationship), "TEXT", Some(name));
+    item.insert((0x0040, 0xA160), string("UT", text));
+    item
+}
+
+fn code_item(relationship: &str, name: Dataset, value: Dataset) -> Dataset {
+    let mut item = content_item(Some(relationship), "CODE", Some(name));
+    item.insert((0x0040, 0xA168), Value::Sequence(vec![value]));
+    item
+}
+
+fn uidref_item(relationship: &str, name: Dataset, value: &str) -> Dataset {
+    let mut item = content_item(Some(relationship), "UIDREF", Some(name));
+    item.insert((0x0040, 0xA124), uid(value));
+    item
+}
+
+fn num_item(relationship: &str, name: Dataset, value: f64, unit: Dataset) -> Dataset {
+    let mut item = content_item(Some(relationship), "NUM", Some(name));
+    let measured = BTreeMap::from([
+        ((0x0040, 0x08EA), Value::Sequence(vec![unit])),
+        ((0x0040, 0xA30A), string("DS", &decimal(value))),
+    ]);
+    item.insert((0x0040, 0xA300), Value::Sequence(vec![measured]));
+    item
+}
+
+// Elements keyed by tag, so they encode in the ascending order DICOM requires
+type Dataset = BTreeMap<Tag, Value>;
+
+#[derive(Clone, Debug)]
+enum Value {
+    Text(&'static str, String),
+    Bytes(&'static str, Vec<u8>),
+    Sequence(Vec<Dataset>),
+}
+
+fn string(vr: &'static str, value: &str) -> Value {
+    // Short string types are cut to their maximum length rather than rejected
+    let limit = match vr {
+        "CS" | "SH" | "DA" | "DS" | "TM" | "IS" => 16,
+        "LO" | "PN" => 64,
+        _ => usize::MAX,
+    };
+    Value::Text(vr, value.chars().take(limit).collect())
+}
+
+fn uid(value: &str) -> Value {
+    Value::Text("UI", value.to_string())
+}
+
+fn check_uid(value: &str) -> Result<(), SrError> {
+    let valid = !value.is_empty()
+        && value.len() <= MAX_UID_LEN
+        && value.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) && (part == "0" || !part.starts_with('0'))
+        });
+    if !valid {
+        return Err(SrError::InvalidUid(value.to_string()));
+    }
+    Ok(())
+}
+
+// A decimal string of at most 16 characters; `check_values` has ruled out NaN and infinities
+fn decimal(value: f64) -> String {
+    let fixed = format!("{:.6}", value);
+    let fixed = fixed.trim_end_matches('0').trim_end_matches('.');
+    if fixed.len() <= 16 {
+        fixed.to_string()
+    } else {
+        format!("{:.6e}", value)
+    }
+}
+
+fn encode(dataset: &Dataset, out: &mut Vec<u8>) {
+    for (&(group, element), value) in dataset {
+        let (vr, bytes) = match value {
+            Value::Text(vr, text) => {
+                let mut bytes = text.as_bytes().to_vec();
+                if bytes.len() % 2 == 1 {
+                    bytes.push(if *vr == "UI" { 0 } else { b' ' });
+                }
+                (*vr, bytes)
+            }
+            Value::Bytes(vr, bytes) => (*vr, bytes.clone()),
+            Value::Sequence(items) => {
+                let mut bytes = Vec::new();
+                for item in items {
+                    let mut item_bytes = Vec::new();
+                    encode(item, &mut item_bytes);
+                    bytes.extend_from_slice(&0xFFFEu16.to_le_bytes());
+                    bytes.extend_from_slice(&0xE000u16.to_le_bytes());
+                    bytes.extend_from_slice(&(item_bytes.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(&item_bytes);
+                }
+                ("SQ", bytes)
+            }
+        };
+
+        out.extend_from_slice(&group.to_le_bytes());
+        out.extend_from_slice(&element.to_le_bytes());
+        out.extend_from_slice(vr.as_bytes());
+        if matches!(vr, "OB" | "SQ" | "UC" | "UT") {
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        } else {
+            out.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+        }
+        out.extend_from_slice(&bytes);
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sled::transaction::{TransactionError, Transactional};
+
+use crate::{AnalysisMessage, RadiologyResult};
+
+/// Errors raised by a `ResultStore` backend
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("storage backend error: {0}")]
+    Backend(#[from] sled::Error),
+    #[error("failed to encode stored result: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error("result store lock poisoned")]
+    Poisoned,
+    #[error("corrupt stored data: {0}")]
+    Corrupt(String),
+}
+
+/// Filter applied when reading results back out of a store.
+///
+/// Every query is scoped to a single context; image id and date bounds are optional.
+#[derive(Clone, Debug, Default)]
+pub struct ResultQuery {
+    pub context_id: String,
+    pub image_id: Option<String>,
+    pub study_instance_uid: Option<String>,
+    pub series_instance_uid: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl ResultQuery {
+    pub fn for_context(context_id: &str) -> Self {
+        ResultQuery {
+            context_id: context_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn image_id(mut self, image_id: &str) -> Self {
+        self.image_id = Some(image_id.to_string());
+        self
+    }
+
+    pub fn study(mut self, study_instance_uid: &str) -> Self {
+        self.study_instance_uid = Some(study_instance_uid.to_string());
+        self
+    }
+
+    pub fn series(mut self, series_instance_uid: &str) -> Self {
+        self.series_instance_uid = Some(series_instance_uid.to_string());
+        self
+    }
+
+    /// Only match results analyzed at or after `from`
+    pub fn from(mut self, from: DateTime<Utc>) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Only match results analyzed strictly before `until`
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    pub fn matches(&self, result: &RadiologyResult) -> bool {
+        if let Some(image_id) = &self.image_id {
+            if &result.image_id != image_id {
+                return false;
+            }
+        }
+        if self.study_instance_uid.is_some() && result.study_instance_uid != self.study_instance_uid {
+            return false;
+        }
+        if self.series_instance_uid.is_some() && result.series_instance_uid != self.series_instance_uid {
+            return false;
+        }
+
+        if self.from.is_none() && self.until.is_none() {
+            return true;
+        }
+
+        // Results whose date cannot be parsed never match a date-bounded query
+        let Ok(date) = DateTime::parse_from_rfc3339(&result.analysis_date) else {
+            return false;
+        };
+        let date = date.with_timezone(&Utc);
+
+        self.from.is_none_or(|from| date >= from) && self.until.is_none_or(|until| date < until)
+    }
+}
+
+/// Storage for analysis results, keyed by context.
+///
+/// Implementations must be usable from several tasks at once.
+pub trait ResultStore: Send + Sync {
+    /// Record a result for the given context
+    fn insert(&self, context_id: &str, result: &RadiologyResult) -> Result<(), StoreError>;
+
+    /// Return every stored result matching the query, in insertion order
+    fn query(&self, query: &ResultQuery) -> Result<Vec<RadiologyResult>, StoreError>;
+
+    /// Record a result unless the context already has one for its image id.
+    ///
+    /// Returns whether the result was recorded. The default implementation
+    /// checks and inserts separately; stores shared between processes should
+    /// override it to do both atomically.
+    fn insert_once(&self, context_id: &str, result: &RadiologyResult) -> Result<bool, StoreError> {
+        let existing = self.query(&ResultQuery::for_context(context_id).image_id(&result.image_id))?;
+        if !existing.is_empty() {
+            return Ok(false);
+        }
+        self.insert(context_id, result)?;
+        Ok(true)
+    }
+
+    /// Add messages to the conversation about an image, in order.
+    ///
+    /// The default implementation discards them: a store that doesn't keep
+    /// conversations has none to continue, so follow-up questions about its
+    /// images fail with `RadiologyError::UnknownImage`.
+    fn append_messages(&self, _context_id: &str, _image_id: &str, _messages: &[AnalysisMessage]) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    /// The whole conversation about an image, oldest message first; empty by default
+    fn conversation(&self, _context_id: &str, _image_id: &str) -> Result<Vec<AnalysisMessage>, StoreError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Result store that keeps everything in process memory
+#[derive(Default)]
+pub struct MemoryResultStore {
+    results: RwLock<HashMap<String, Vec<RadiologyResult>>>,
+    // Keyed by context and image id
+    conversations: RwLock<HashMap<(String, String), Vec<AnalysisMessage>>>,
+}
+
+impl MemoryResultStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ResultStore for MemoryResultStore {
+    fn insert(&self, context_id: &str, result: &RadiologyResult) -> Result<(), StoreError> {
+        self.results
+            .write()
+            .map_err(|_| StoreError::Poisoned)?
+            .entry(context_id.to_string())
+            .or_default()
+            .push(result.clone());
+        Ok(())
+    }
+
+    fn insert_once(&self, context_id: &str, result: &RadiologyResult) -> Result<bool, StoreError> {
+        let mut results = self.results.write().map_err(|_| StoreError::Poisoned)?;
+        let stored = results.entry(context_id.to_string()).or_default();
+        if stored.iter().any(|r| r.image_id == result.image_id) {
+            return Ok(false);
+        }
+        stored.push(result.clone());
+        Ok(true)
+    }
+
+    fn query(&self, query: &ResultQuery) -> Result<Vec<RadiologyResult>, StoreError> {
+        let results = self.results.read().map_err(|_| StoreError::Poisoned)?;
+        Ok(results
+            .get(&query.context_id)
+            .map(|stored| stored.iter().filter(|r| query.matches(r)).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn append_messages(&self, context_id: &str, image_id: &str, messages: &[AnalysisMessage]) -> Result<(), StoreError> {
+        self.conversations
+            .write()
+            .map_err(|_| StoreError::Poisoned)?
+            .entry((context_id.to_string(), image_id.to_string()))
+            .or_default()
+            .extend_from_slice(messages);
+        Ok(())
+    }
+
+    fn conversation(&self, context_id: &str, image_id: &str) -> Result<Vec<AnalysisMessage>, StoreError> {
+        let conversations = self.conversations.read().map_err(|_| StoreError::Poisoned)?;
+        Ok(conversations
+            .get(&(context_id.to_string(), image_id.to_string()))
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// On-disk record; keeps the context alongside the result so the tree can be inspected offline
+#[derive(Serialize, Deserialize)]
+struct StoredResult {
+    context_id: String,
+    result: RadiologyResult,
+}
+
+/// Result store backed by an embedded sled database.
+///
+/// Each context gets its own tree, keyed by a monotonically increasing id so
+/// that iteration returns results in insertion order, plus an index tree from
+/// image id to its latest result that makes `insert_once` atomic.
+/// Conversations live in a tree per context, keyed by image id and then
+/// message order.
+pub struct SledResultStore {
+    db: sled::Db,
+}
+
+impl SledResultStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Ok(SledResultStore {
+            db: sled::open(path)?,
+        })
+    }
+
+    /// Wrap an already opened database, e.g. one shared with other components
+    pub fn from_db(db: sled::Db) -> Self {
+        SledResultStore { db }
+    }
+
+    fn tree(&self, context_id: &str) -> Result<sled::Tree, StoreError> {
+        Ok(self.db.open_tree(format!("results/{}", context_id))?)
+    }
+
+    fn conversations(&self, context_id: &str) -> Result<sled::Tree, StoreError> {
+        Ok(self.db.open_tree(format!("conversations/{}", context_id))?)
+    }
+
+    fn index(&self, context_id: &str) -> Result<sled::Tree, StoreError> {
+        Ok(self.db.open_tree(format!("results-by-image/{}", context_id))?)
+    }
+
+    // Write the result and its index entry together; with `once`, skip images already indexed
+    fn write(&self, context_id: &str, result: &RadiologyResult, once: bool) -> Result<bool, StoreError> {
+        let record = serde_json::to_vec(&StoredResult {
+            context_id: context_id.to_string(),
+            result: result.clone(),
+        })?;
+        let key = self.db.generate_id()?.to_be_bytes();
+        let results = self.tree(context_id)?;
+        let index = self.index(context_id)?;
+
+        let written = (&results, &index)
+            .transaction(|(results, index)| {
+                if once && index.get(result.image_id.as_bytes())?.is_some() {
+                    return Ok(false);
+                }
+                index.insert(result.image_id.as_bytes(), &key)?;
+                results.insert(&key, record.clone())?;
+                Ok(true)
+            })
+            .map_err(|err: TransactionError<()>| match err {
+                TransactionError::Storage(err) => StoreError::Backend(err),
+                TransactionError::Abort(()) => unreachable!("result transactions never abort"),
+            })?;
+        self.db.flush()?;
+        Ok(written)
+    }
+}
+
+impl ResultStore for SledResultStore {
+    fn insert(&self, context_id: &str, result: &RadiologyResult) -> Result<(), StoreError> {
+        self.write(context_id, result, false)?;
+        Ok(())
+    }
+
+    fn insert_once(&self, context_id: &str, result: &RadiologyResult) -> Result<bool, StoreError> {
+        self.write(context_id, result, true)
+    }
+
+    fn query(&self, query: &ResultQuery) -> Result<Vec<RadiologyResult>, StoreError> {
+        let mut results = Vec::new();
+        for entry in self.tree(&query.context_id)?.iter() {
+            let (_, value) = entry?;
+            let record: StoredResult = serde_json::from_slice(&value)?;
+            if query.matches(&record.result) {
+                results.push(record.result);
+            }
+        }
+        Ok(results)
+    }
+
+    fn append_messages(&self, context_id: &str, image_id: &str, messages: &[AnalysisMessage]) -> Result<(), StoreError> {
+        let tree = self.conversations(context_id)?;
+        let mut batch = sled::Batch::default();
+        for message in messages {
+            batch.insert(message_key(image_id, self.db.generate_id()?), serde_json::to_vec(message)?);
+        }
+        tree.apply_batch(batch)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn conversation(&self, context_id: &str, image_id: &str) -> Result<Vec<AnalysisMessage>, StoreError> {
+        let mut messages = Vec::new();
+        for entry in self.conversations(context_id)?.scan_prefix(message_prefix(image_id)) {
+            let (_, value) = entry?;
+            messages.push(serde_json::from_slice(&value)?);
+        }
+        Ok(messages)
+    }
+}
+
+// The image id and a separator no id contains, so one image's prefix never matches another's
+fn message_prefix(image_id: &str) -> Vec<u8> {
+    let mut prefix = image_id.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+// Big-endian sequence numbers keep an image's messages in order
+fn message_key(image_id: &str, sequence: u64) -> Vec<u8> {
+    let mut key = message_prefix(image_id);
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
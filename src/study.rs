@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::findings::FindingsReport;
+use crate::{RadiologyImage, RadiologyResult};
+
+/// Metadata keys linking an image or result to its study and series
+pub const STUDY_UID_KEY: &str = "study_instance_uid";
+pub const SERIES_UID_KEY: &str = "series_instance_uid";
+
+/// All images acquired in one series, e.g. a single CT reconstruction
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RadiologySeries {
+    pub series_instance_uid: String,
+    pub modality: Option<String>,
+    pub body_part: Option<String>,
+    pub images: Vec<RadiologyImage>,
+}
+
+/// A study: the series acquired for one patient in one imaging session
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RadiologyStudy {
+    pub study_instance_uid: String,
+    pub patient_id: Option<String>,
+    pub series: Vec<RadiologySeries>,
+}
+
+impl RadiologyStudy {
+    /// Group loose images into studies and series by their instance UIDs.
+    ///
+    /// UIDs come from the image's DICOM tags, or its metadata when it has none.
+    /// Images without UIDs are grouped under an empty UID. Studies, series and
+    /// images keep the order in which they first appear.
+    pub fn from_images(images: impl IntoIterator<Item = RadiologyImage>) -> Vec<RadiologyStudy> {
+        let mut studies: Vec<RadiologyStudy> = Vec::new();
+
+        for image in images {
+            let study_uid = image_uid(&image, STUDY_UID_KEY).unwrap_or_default();
+            let series_uid = image_uid(&image, SERIES_UID_KEY).unwrap_or_default();
+
+            let study = match studies.iter().position(|s| s.study_instance_uid == study_uid) {
+                Some(index) => &mut studies[index],
+                None => {
+                    studies.push(RadiologyStudy {
+                        study_instance_uid: study_uid,
+                        patient_id: image.metadata.get("patient_id").cloned(),
+                        series: Vec::new(),
+                    });
+                    studies.last_mut().unwrap()
+                }
+            };
+
+            let series = match study.series.iter().position(|s| s.series_instance_uid == series_uid) {
+                Some(index) => &mut study.series[index],
+                None => {
+                    study.series.push(RadiologySeries {
+                        series_instance_uid: series_uid,
+                        modality: image.metadata.get("modality").cloned(),
+                        body_part: image.metadata.get("body_part").cloned(),
+                        images: Vec::new(),
+                    });
+                    study.series.last_mut().unwrap()
+                }
+            };
+
+            series.images.push(image);
+        }
+
+        studies
+    }
+}
+
+impl RadiologySeries {
+    /// Metadata describing the series as a whole, sent with per-series requests
+    pub fn metadata(&self, study_instance_uid: &str) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert(STUDY_UID_KEY.to_string(), study_instance_uid.to_string());
+        metadata.insert(SERIES_UID_KEY.to_string(), self.series_instance_uid.clone());
+        metadata.insert("image_count".to_string(), self.images.len().to_string());
+        if let Some(modality) = &self.modality {
+            metadata.insert("modality".to_string(), modality.clone());
+        }
+        if let Some(body_part) = &self.body_part {
+            metadata.insert("body_part".to_string(), body_part.clone());
+        }
+        metadata
+    }
+}
+
+/// How `RadiologyCluster::submit_study` sends a study to the server
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StudySubmission {
+    /// One analysis request per series carrying every image in it
+    #[default]
+    PerSeries,
+    /// One request per image, sent concurrently, with results aggregated per series
+    FanOut,
+}
+
+/// Outcome of analyzing one series of a study
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeriesResult {
+    pub series_instance_uid: String,
+    /// Series-level result, stored with the series UID as its image id
+    pub result: RadiologyResult,
+    /// Individual image results; only populated for `StudySubmission::FanOut`
+    pub image_results: Vec<RadiologyResult>,
+}
+
+/// Outcome of analyzing a whole study
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StudyResult {
+    pub study_instance_uid: String,
+    pub series: Vec<SeriesResult>,
+}
+
+/// Combine per-image results into a series result.
+///
+/// Findings are listed per image, and the series confidence is the lowest
+/// image confidence so a single uncertain image isn't hidden by the others.
+/// Structured findings of the images are gathered into one report; their
+/// impressions are left out, as they describe single images.
+pub(crate) fn aggregate(study_uid: &str, series_uid: &str, image_results: &[RadiologyResult]) -> RadiologyResult {
+    let findings = image_results
+        .iter()
+        .map(|r| format!("{}: {}", r.image_id, r.findings))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let confidence_score = image_results
+        .iter()
+        .map(|r| r.confidence_score)
+        .reduce(f32::min)
+        .unwrap_or_default();
+    let report = image_results
+        .iter()
+        .any(|r| r.report.is_some())
+        .then(|| FindingsReport {
+            findings: image_results
+                .iter()
+                .filter_map(|r| r.report.as_ref())
+                .flat_map(|report| report.findings.iter().cloned())
+                .collect(),
+            impression: None,
+        });
+
+    RadiologyResult {
+        image_id: series_uid.to_string(),
+        findings,
+        confidence_score,
+        analysis_date: chrono::Utc::now().to_rfc3339(),
+        study_instance_uid: Some(study_uid.to_string()),
+        series_instance_uid: Some(series_uid.to_string()),
+        report,
+    }
+}
+
+/// Study or series UID of an image, preferring typed DICOM tags over free-form metadata
+pub(crate) fn image_uid(image: &RadiologyImage, key: &str) -> Option<String> {
+    let tagged = image.dicom.as_ref().and_then(|tags| match key {
+        STUDY_UID_KEY => tags.study_instance_uid.clone(),
+        _ => tags.series_instance_uid.clone(),
+    });
+    tagged.or_else(|| image.metadata.get(key).cloned())
+}
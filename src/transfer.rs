@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::client::McpClient;
+use crate::error::RadiologyError;
+
+/// JSON-RPC method used to stream image bytes to the server ahead of an analysis request
+pub const UPLOAD_METHOD: &str = "images/upload";
+
+/// Metadata key consulted for the MIME type of the image bytes
+pub const MIME_TYPE_KEY: &str = "mime_type";
+
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Limits controlling how image bytes are sent to the server.
+///
+/// Images up to `inline_limit` bytes are embedded as a base64 MCP image content
+/// block in the analysis request. Anything larger is uploaded first in
+/// `chunk_size` pieces and the analysis request references the upload instead.
+#[derive(Clone, Debug)]
+pub struct TransferConfig {
+    /// Largest image accepted at all; bigger images fail with `ImageTooLarge`
+    pub max_image_bytes: u64,
+    /// Largest image sent inline in the analysis request
+    pub inline_limit: usize,
+    /// Size of each uploaded chunk before base64 encoding
+    pub chunk_size: usize,
+}
+
+impl Default for TransferConfig {
+    fn default() -> Self {
+        TransferConfig {
+            max_image_bytes: 1024 * 1024 * 1024,
+            inline_limit: 4 * 1024 * 1024,
+            chunk_size: 1024 * 1024,
+        }
+    }
+}
+
+/// MCP content block carrying the image inline
+pub(crate) fn inline_content(data: &[u8], mime_type: &str) -> Value {
+    json!({
+        "type": "image",
+        "data": BASE64.encode(data),
+        "mimeType": mime_type,
+    })
+}
+
+/// MCP resource content block pointing at a completed upload
+pub(crate) fn upload_content(upload_id: &str, mime_type: &str, size: u64) -> Value {
+    json!({
+        "type": "resource",
+        "resource": {
+            "uri": format!("upload://{}", upload_id),
+            "mimeType": mime_type,
+            "size": size,
+        }
+    })
+}
+
+pub(crate) fn mime_type(metadata: &std::collections::HashMap<String, String>) -> &str {
+    metadata.get(MIME_TYPE_KEY).map(String::as_str).unwrap_or(DEFAULT_MIME_TYPE)
+}
+
+pub(crate) fn new_upload_id(image_id: &str) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}-{}",
+        image_id,
+        chrono::Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Stream everything `reader` yields to the server as numbered chunks.
+///
+/// Each chunk waits for the server's acknowledgement before the next is read,
+/// so at most one chunk is held in memory regardless of the image size.
+/// Returns the total number of bytes uploaded.
+pub(crate) async fn upload<R>(
+    client: &McpClient,
+    upload_id: &str,
+    mut reader: R,
+    config: &TransferConfig,
+) -> Result<u64, RadiologyError>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    client.initialize().await?;
+
+    let mut buffer = vec![0; config.chunk_size.max(1)];
+    let mut total: u64 = 0;
+    let mut sequence: u64 = 0;
+
+    loop {
+        let filled = fill(&mut reader, &mut buffer).await?;
+        total += filled as u64;
+        if total > config.max_image_bytes {
+            return Err(RadiologyError::ImageTooLarge {
+                size: total,
+                limit: config.max_image_bytes,
+            });
+        }
+
+        // A short read means the reader is exhausted, so this is the final chunk
+        let done = filled < buffer.len();
+        let params = json!({
+            "upload_id": upload_id,
+            "sequence": sequence,
+            "data": BASE64.encode(&buffer[..filled]),
+            "done": done,
+        });
+        client.request(UPLOAD_METHOD, Some(params)).await?;
+        sequence += 1;
+
+        if done {
+            return Ok(total);
+        }
+    }
+}
+
+// Read until the buffer is full or the reader hits EOF
+async fn fill<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut [u8]) -> Result<usize, RadiologyError> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let n = reader.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
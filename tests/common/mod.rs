@@ -0,0 +1,73 @@
+// Each test crate uses only some of these
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use mcp::deid::DeidPolicy;
+use mcp::mock::{Fault, FaultRule, Latency, MockServer};
+use mcp::store::ResultStore;
+use mcp::{McpClient, RadiologyCluster, RadiologyImage};
+
+/// A server whose analyses take `delay` and report `findings(arguments)`
+pub async fn delayed_server(delay: Duration, findings: impl Fn(&Value) -> String + Send + Sync + 'static) -> MockServer {
+    MockServer::builder()
+        .tool("analyze_image", move |arguments| {
+            Ok(json!({ "status": "success", "findings": findings(arguments), "confidence": 0.9 }))
+        })
+        .fault(FaultRule::on("tools/call", Fault::Delay(Latency::Fixed(delay))))
+        .start()
+        .await
+        .unwrap()
+}
+
+/// A server whose analyses take `delay`; findings name the image
+pub async fn slow_server(delay: Duration) -> MockServer {
+    delayed_server(delay, |arguments| {
+        format!("Findings for {}", arguments["image_id"].as_str().unwrap_or_default())
+    })
+    .await
+}
+
+pub async fn connect(server: &MockServer) -> Arc<McpClient> {
+    Arc::new(McpClient::connect(&server.url()).await.unwrap())
+}
+
+/// A cluster with a "ct" context that keeps image ids as they are, so tests
+/// can see which images the server got
+pub async fn ct_cluster(server: &MockServer, store: Arc<dyn ResultStore>) -> Arc<RadiologyCluster> {
+    let cluster = RadiologyCluster::with_store(connect(server).await, store).with_deid_policy(DeidPolicy::keep_all());
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    Arc::new(cluster)
+}
+
+pub fn image(id: &str) -> RadiologyImage {
+    RadiologyImage {
+        image_id: id.to_string(),
+        data: vec![1, 2, 3],
+        metadata: HashMap::new(),
+        dicom: None,
+    }
+}
+
+/// Image ids of the analyses the server received, in order
+pub fn called_ids(server: &MockServer) -> Vec<String> {
+    server
+        .requests_for("tools/call")
+        .iter()
+        .map(|call| call["params"]["arguments"]["image_id"].as_str().unwrap_or_default().to_string())
+        .collect()
+}
+
+pub async fn wait_for(condition: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("condition never became true");
+}
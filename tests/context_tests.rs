@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp::context::ContextConfig;
+use mcp::deid::{DeidPolicy, IMAGE_ID_KEY};
+use mcp::mock::MockServer;
+use mcp::queue::{JobQueue, JobStatus, Priority, QueueConfig};
+use mcp::{RadiologyCluster, RadiologyError};
+
+mod common;
+use common::{connect, delayed_server, image};
+
+// Findings name the model that read the image; every analysis takes `delay`
+async fn server(delay: Duration) -> MockServer {
+    delayed_server(delay, |arguments| format!("Read by {}", arguments["model"].as_str().unwrap_or_default())).await
+}
+
+async fn cluster_for(server: &MockServer) -> Arc<RadiologyCluster> {
+    Arc::new(RadiologyCluster::new(connect(server).await))
+}
+
+#[tokio::test]
+async fn test_contexts_can_be_listed_inspected_and_updated() {
+    let server = server(Duration::ZERO).await;
+    let cluster = cluster_for(&server).await;
+    cluster.create_context("mri", ContextConfig::new("mri-model").timeout(Duration::from_secs(5))).unwrap();
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let ids: Vec<String> = cluster.list_contexts().into_iter().map(|info| info.id).collect();
+    assert_eq!(ids, vec!["ct", "mri"]);
+    let mri = cluster.context("mri").unwrap();
+    assert_eq!(mri.config.model, "mri-model");
+    assert_eq!(mri.config.timeout, Some(Duration::from_secs(5)));
+    assert_eq!(mri.in_flight, 0);
+
+    let err = cluster.initialize_context("ct", "other-model").await.unwrap_err();
+    assert!(matches!(err, RadiologyError::ContextExists(ref id) if id == "ct"));
+
+    assert_eq!(cluster.submit_image("ct", image("IMG1")).await.unwrap().findings, "Read by ct-model");
+    cluster.set_context_model("ct", "ct-model-v2").unwrap();
+    assert_eq!(cluster.submit_image("ct", image("IMG2")).await.unwrap().findings, "Read by ct-model-v2");
+
+    let err = cluster.update_context("ct", ContextConfig::new("ct-model").backend("missing")).unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownBackend(_)));
+    assert!(cluster.context("ct").unwrap().last_used >= cluster.context("ct").unwrap().created_at);
+}
+
+#[tokio::test]
+async fn test_close_waits_for_in_flight_submissions() {
+    let server = server(Duration::from_millis(200)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let submission = {
+        let cluster = cluster.clone();
+        tokio::spawn(async move { cluster.submit_image("ct", image("IMG1")).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(cluster.context("ct").unwrap().in_flight, 1);
+
+    let closing = {
+        let cluster = cluster.clone();
+        tokio::spawn(async move { cluster.close_context("ct").await })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(cluster.context("ct").unwrap().closing);
+    let err = cluster.submit_image("ct", image("IMG2")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::ContextClosed(_)));
+
+    assert!(submission.await.unwrap().is_ok());
+    let closed = closing.await.unwrap().unwrap();
+    assert_eq!(closed.in_flight, 0);
+    assert!(matches!(cluster.context("ct"), Err(RadiologyError::UnknownContext(_))));
+
+    // Results outlive the context, and the id can be used again
+    assert_eq!(cluster.get_results("ct").await.unwrap().len(), 1);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_idle_contexts_expire() {
+    let server = server(Duration::ZERO).await;
+    let cluster = cluster_for(&server).await;
+    cluster.create_context("ct", ContextConfig::new("ct-model").ttl(Duration::from_millis(50))).unwrap();
+    cluster.initialize_context("mri", "mri-model").await.unwrap();
+
+    cluster.submit_image("ct", image("IMG1")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let err = cluster.submit_image("ct", image("IMG2")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(_)));
+    assert_eq!(cluster.expire_contexts(), vec!["ct"]);
+    let ids: Vec<String> = cluster.list_contexts().into_iter().map(|info| info.id).collect();
+    assert_eq!(ids, vec!["mri"]);
+    cluster.create_context("ct", ContextConfig::new("ct-model")).unwrap();
+}
+
+#[tokio::test]
+async fn test_in_flight_submissions_keep_a_context_alive() {
+    let server = server(Duration::from_millis(150)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.create_context("ct", ContextConfig::new("ct-model").ttl(Duration::from_millis(50))).unwrap();
+
+    cluster.submit_image("ct", image("IMG1")).await.unwrap();
+    assert!(cluster.context("ct").is_ok());
+}
+
+#[tokio::test]
+async fn test_prompt_template_and_deid_policy_are_per_context() {
+    let server = server(Duration::ZERO).await;
+    let cluster = cluster_for(&server).await;
+    cluster.initialize_context("default", "model").await.unwrap();
+    let config = ContextConfig::new("model")
+        .prompt_template("Describe this chest film. Metadata: {metadata}")
+        .deid_policy(DeidPolicy::keep_all());
+    cluster.create_context("research", config).unwrap();
+
+    let mut tagged = image("IMG1");
+    tagged.metadata.insert("patient_id".to_string(), "P12345".to_string());
+    cluster.submit_image("default", tagged.clone()).await.unwrap();
+    cluster.submit_image("research", tagged).await.unwrap();
+
+    let calls = server.requests_for("tools/call");
+    let (default, research) = (&calls[0]["params"]["arguments"], &calls[1]["params"]["arguments"]);
+    assert!(default["prompt"].as_str().unwrap().starts_with("You are a radiology analysis system"));
+    assert_ne!(default["image_id"], "IMG1");
+    assert_eq!(research["prompt"], r#"Describe this chest film. Metadata: {"patient_id":"P12345"}"#);
+    assert_eq!(research["image_id"], "IMG1");
+
+    let pseudonym = default["image_id"].as_str().unwrap();
+    let default = cluster.context_deidentifier("default").unwrap();
+    assert_eq!(default.reidentify(IMAGE_ID_KEY, pseudonym).as_deref(), Some("IMG1"));
+    assert_eq!(cluster.context_deidentifier("research").unwrap().reidentify(IMAGE_ID_KEY, pseudonym), None);
+}
+
+#[tokio::test]
+async fn test_closing_through_the_queue_drains_queued_jobs() {
+    let server = server(Duration::from_millis(30)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    let queue = JobQueue::new(cluster.clone(), QueueConfig::new().max_concurrent(1));
+
+    let mut jobs = Vec::new();
+    for i in 0..3 {
+        jobs.push(queue.submit("ct", image(&format!("IMG{}", i)), Priority::Routine).await.unwrap());
+    }
+    queue.close_context("ct").await.unwrap();
+
+    for job in jobs {
+        assert!(matches!(queue.status(job), Some(JobStatus::Completed(_))));
+    }
+    let err = queue.submit("ct", image("IMG3"), Priority::Routine).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(_)));
+    assert_eq!(server.requests_for("tools/call").len(), 3);
+}
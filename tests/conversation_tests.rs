@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use mcp::context::ContextConfig;
+use mcp::conversation::{estimate_tokens, truncate};
+use mcp::mock::MockServer;
+use mcp::store::SledResultStore;
+use mcp::{AnalysisMessage, RadiologyCluster, RadiologyError};
+
+mod common;
+use common::{connect, image};
+
+// Reads images, and answers follow-ups by echoing the question and how much history it got
+async fn radiologist() -> MockServer {
+    MockServer::builder()
+        .tool("analyze_image", |arguments| match arguments["messages"].as_array() {
+            Some(messages) => Ok(json!({
+                "status": "success",
+                "answer": format!("{} ({} messages)", arguments["prompt"].as_str().unwrap_or_default(), messages.len())
+            })),
+            None => Ok(json!({ "status": "success", "findings": "8 mm nodule in the right upper lobe", "confidence": 0.9 })),
+        })
+        .start()
+        .await
+        .unwrap()
+}
+
+fn sent_messages(server: &MockServer, n: usize) -> Vec<Value> {
+    server.requests_for("tools/call")[n]["params"]["arguments"]["messages"].as_array().unwrap().clone()
+}
+
+#[test]
+fn test_truncation_keeps_the_first_exchange_and_the_question() {
+    let messages: Vec<AnalysisMessage> = (0..8)
+        .map(|i| match i % 2 {
+            0 => AnalysisMessage::user(&format!("question {} {}", i, "x".repeat(36))),
+            _ => AnalysisMessage::assistant(&format!("answer {} {}", i, "y".repeat(36))),
+        })
+        .collect();
+    let each = estimate_tokens(&messages[0]);
+    assert!(messages.iter().all(|message| estimate_tokens(message) == each));
+
+    assert_eq!(truncate(&messages, each * 8), messages);
+
+    let kept = truncate(&messages, each * 5);
+    let expected: Vec<AnalysisMessage> = [0, 1, 5, 6, 7].iter().map(|&i| messages[i].clone()).collect();
+    assert_eq!(kept, expected);
+
+    // Too small a budget still sends the original read and the question
+    let kept = truncate(&messages, 1);
+    assert_eq!(kept, vec![messages[0].clone(), messages[1].clone(), messages[7].clone()]);
+}
+
+#[tokio::test]
+async fn test_follow_up_questions_continue_the_conversation() {
+    let server = radiologist().await;
+    let cluster = RadiologyCluster::new(connect(&server).await);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster.submit_image("ct", image("IMG1")).await.unwrap();
+
+    let answer = cluster.ask("ct", "IMG1", "Measure the nodule").await.unwrap();
+    assert_eq!(answer, AnalysisMessage::assistant("Measure the nodule (3 messages)"));
+    let answer = cluster.ask("ct", "IMG1", "Compare to prior").await.unwrap();
+    assert_eq!(answer.content, "Compare to prior (5 messages)");
+
+    let conversation = cluster.conversation("ct", "IMG1").await.unwrap();
+    let roles: Vec<&str> = conversation.iter().map(|message| message.role.as_str()).collect();
+    assert_eq!(roles, vec!["user", "assistant", "user", "assistant", "user", "assistant"]);
+    assert!(conversation[0].content.starts_with("You are a radiology analysis system"));
+    assert_eq!(conversation[1].content, "8 mm nodule in the right upper lobe");
+
+    // The server sees the history with the image id as it knows it
+    let call = &server.requests_for("tools/call")[2]["params"]["arguments"];
+    assert_eq!(call["messages"][1]["content"], "8 mm nodule in the right upper lobe");
+    assert_eq!(call["image_id"], server.requests_for("tools/call")[0]["params"]["arguments"]["image_id"]);
+}
+
+#[tokio::test]
+async fn test_follow_ups_need_an_analyzed_image() {
+    let server = radiologist().await;
+    let cluster = RadiologyCluster::new(connect(&server).await);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let err = cluster.ask("ct", "IMG1", "Measure the nodule").await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownImage { ref image_id, .. } if image_id == "IMG1"));
+    assert!(server.requests_for("tools/call").is_empty());
+}
+
+#[tokio::test]
+async fn test_history_sent_is_limited_by_the_budget() {
+    let server = radiologist().await;
+    let cluster = RadiologyCluster::new(connect(&server).await);
+    cluster.create_context("ct", ContextConfig::new("ct-model").history_tokens(1)).unwrap();
+    cluster.submit_image("ct", image("IMG1")).await.unwrap();
+
+    for question in ["Measure the nodule", "Compare to prior", "Any effusion?"] {
+        cluster.ask("ct", "IMG1", question).await.unwrap();
+    }
+    let last = sent_messages(&server, 3);
+    assert_eq!(last.len(), 3);
+    assert_eq!(last[1]["content"], "8 mm nodule in the right upper lobe");
+    assert_eq!(last[2]["content"], "Any effusion?");
+    assert_eq!(cluster.conversation("ct", "IMG1").await.unwrap().len(), 8);
+}
+
+#[tokio::test]
+async fn test_conversations_persist_with_results() {
+    let dir = tempfile::tempdir().unwrap();
+    // sled lets go of its file lock only some time after the last handle drops,
+    // so each "process" gets its own store over one shared database
+    let db = sled::open(dir.path()).unwrap();
+    let server = radiologist().await;
+    {
+        let store = Arc::new(SledResultStore::from_db(db.clone()));
+        let cluster = RadiologyCluster::with_store(connect(&server).await, store);
+        cluster.initialize_context("ct", "ct-model").await.unwrap();
+        cluster.submit_image("ct", image("IMG1")).await.unwrap();
+        cluster.ask("ct", "IMG1", "Measure the nodule").await.unwrap();
+    }
+
+    let store = Arc::new(SledResultStore::from_db(db));
+    let cluster = RadiologyCluster::with_store(connect(&server).await, store);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    assert_eq!(cluster.conversation("ct", "IMG1").await.unwrap().len(), 4);
+
+    let answer = cluster.ask("ct", "IMG1", "Compare to prior").await.unwrap();
+    assert_eq!(answer.content, "Compare to prior (5 messages)");
+}
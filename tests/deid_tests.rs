@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::json;
+
+use mcp::deid::{DeidPolicy, Deidentifier, FieldAction};
+use mcp::dicom::DicomTags;
+use mcp::mock::MockServer;
+use mcp::{McpClient, RadiologyCluster, RadiologyImage};
+
+fn metadata(entries: &[(&str, &str)]) -> HashMap<String, String> {
+    entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn test_default_policy_pseudonymizes_and_drops_identifiers() {
+    let deid = Deidentifier::new(DeidPolicy::default());
+    let original = metadata(&[
+        ("patient_id", "P12345"),
+        ("patient_name", "Doe^Jane"),
+        ("patient_birth_date", "19700101"),
+        ("patient_birthplace", "Springfield"),
+        ("modality", "CT"),
+    ]);
+
+    let cleaned = deid.deidentify_metadata(&original);
+
+    assert_eq!(cleaned.get("patient_id").map(String::as_str), Some("ANON-000001"));
+    assert!(!cleaned.contains_key("patient_name"));
+    assert!(!cleaned.contains_key("patient_birth_date"));
+    // Fields the policy doesn't list are dropped, not sent
+    assert!(!cleaned.contains_key("patient_birthplace"));
+    assert_eq!(cleaned.get("modality").map(String::as_str), Some("CT"));
+
+    // The same patient always maps to the same pseudonym, and back again
+    assert_eq!(deid.deidentify_value("patient_id", "P12345").as_deref(), Some("ANON-000001"));
+    assert_eq!(deid.reidentify("patient_id", "ANON-000001").as_deref(), Some("P12345"));
+    assert_eq!(deid.reidentify_metadata(&cleaned).get("patient_id").map(String::as_str), Some("P12345"));
+}
+
+#[test]
+fn test_reidentification_is_per_field() {
+    let deid = Deidentifier::new(DeidPolicy::default());
+    let pseudonym = deid.deidentify_value("patient_id", "P12345").unwrap();
+
+    // A model-echoed value that happens to equal the pseudonym in another field stays as it is
+    let echoed = metadata(&[("patient_id", &pseudonym), ("comment", &pseudonym)]);
+    let restored = deid.reidentify_metadata(&echoed);
+    assert_eq!(restored.get("patient_id").map(String::as_str), Some("P12345"));
+    assert_eq!(restored.get("comment"), Some(&pseudonym));
+    assert_eq!(deid.reidentify("comment", &pseudonym), None);
+}
+
+#[test]
+fn test_salted_hash_is_stable_per_salt() {
+    let policy = DeidPolicy::keep_all().field("patient_id", FieldAction::Hash);
+    let first = Deidentifier::new(policy.clone().salt("site-a"));
+    let again = Deidentifier::new(policy.clone().salt("site-a"));
+    let other = Deidentifier::new(policy.salt("site-b"));
+
+    let hashed = first.deidentify_value("patient_id", "P12345").unwrap();
+    assert_eq!(hashed.len(), 64);
+    assert_ne!(hashed, "P12345");
+    assert_eq!(again.deidentify_value("patient_id", "P12345").unwrap(), hashed);
+    assert_ne!(other.deidentify_value("patient_id", "P12345").unwrap(), hashed);
+    assert_eq!(first.reidentify("patient_id", &hashed).as_deref(), Some("P12345"));
+}
+
+#[test]
+fn test_date_shift_keeps_format() {
+    let deid = Deidentifier::new(
+        DeidPolicy::keep_all()
+            .default_action(FieldAction::DateShift)
+            .date_shift_days(-10),
+    );
+
+    assert_eq!(deid.deidentify_value("study_date", "20240115").as_deref(), Some("20240105"));
+    assert_eq!(deid.deidentify_value("study_date", "2024-03-05").as_deref(), Some("2024-02-24"));
+    assert_eq!(
+        deid.deidentify_value("study_date", "2024-01-15T14:30:00+00:00").as_deref(),
+        Some("2024-01-05T14:30:00+00:00")
+    );
+    // Values that aren't recognizable dates are dropped rather than leaked
+    assert_eq!(deid.deidentify_value("study_date", "last Tuesday"), None);
+    assert_eq!(deid.reidentify("study_date", "20240105").as_deref(), Some("20240115"));
+}
+
+#[test]
+fn test_allowlist_policy_and_dicom_tags() {
+    let deid = Deidentifier::new(
+        DeidPolicy::keep_all()
+            .default_action(FieldAction::Drop)
+            .field("modality", FieldAction::Keep)
+            .field("study_instance_uid", FieldAction::Pseudonymize),
+    );
+
+    let cleaned = deid.deidentify_metadata(&metadata(&[("modality", "MR"), ("station", "MR-2")]));
+    assert_eq!(cleaned, metadata(&[("modality", "MR")]));
+
+    let tags = DicomTags {
+        patient_id: Some("P12345".to_string()),
+        modality: Some("MR".to_string()),
+        study_instance_uid: Some("1.2.3".to_string()),
+        sop_instance_uid: Some("1.2.3.4.5".to_string()),
+        ..DicomTags::default()
+    };
+    let cleaned = deid.deidentify_tags(&tags);
+    assert_eq!(cleaned.patient_id, None);
+    assert_eq!(cleaned.sop_instance_uid, None);
+    assert_eq!(cleaned.modality.as_deref(), Some("MR"));
+    assert_eq!(cleaned.study_instance_uid.as_deref(), Some("ANON-000001"));
+}
+
+#[tokio::test]
+async fn test_submitted_requests_carry_no_phi() {
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "findings": "Normal", "confidence": 0.9 }))
+        .start()
+        .await
+        .unwrap();
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server.url()).await.unwrap()));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let image = RadiologyImage {
+        image_id: "1.2.840.1.99".to_string(),
+        data: vec![1, 2, 3],
+        metadata: metadata(&[
+            ("patient_id", "P12345"),
+            ("patient_name", "Doe^Jane"),
+            ("modality", "CT"),
+            ("study_instance_uid", "1.2.840.1"),
+        ]),
+        dicom: None,
+    };
+    let result = cluster.submit_image("ct", image).await.unwrap();
+
+    let request = server.requests_for("tools/call")[0].to_string();
+    for phi in ["P12345", "Doe^Jane", "1.2.840.1"] {
+        assert!(!request.contains(phi), "request leaked {}: {}", phi, request);
+    }
+    assert!(request.contains("CT"));
+
+    // Results are recorded against the real identifiers
+    assert_eq!(result.image_id, "1.2.840.1.99");
+    assert_eq!(result.study_instance_uid.as_deref(), Some("1.2.840.1"));
+    assert_eq!(cluster.get_study_results("ct", "1.2.840.1").await.unwrap().len(), 1);
+    let pseudonym = cluster.deidentifier().deidentify_value("patient_id", "P12345").unwrap();
+    assert!(request.contains(&pseudonym));
+    assert_eq!(cluster.deidentifier().reidentify("patient_id", &pseudonym).as_deref(), Some("P12345"));
+}
@@ -0,0 +1,207 @@
+use mcp::dicom::{load_dicom, parse_dicom, DicomError, EXPLICIT_VR_LITTLE_ENDIAN, IMPLICIT_VR_LITTLE_ENDIAN};
+
+// Minimal DICOM Part 10 writer for building test files
+struct DicomBuilder {
+    explicit: bool,
+    bytes: Vec<u8>,
+}
+
+impl DicomBuilder {
+    fn new(transfer_syntax: &str) -> Self {
+        let mut builder = DicomBuilder { explicit: true, bytes: vec![0; 128] };
+        builder.bytes.extend_from_slice(b"DICM");
+        builder.element(0x0002, 0x0010, b"UI", &padded(transfer_syntax, 0));
+        builder.explicit = transfer_syntax != IMPLICIT_VR_LITTLE_ENDIAN;
+        builder
+    }
+
+    fn header(&mut self, group: u16, element: u16, vr: &[u8; 2], len: u32) {
+        self.bytes.extend_from_slice(&group.to_le_bytes());
+        self.bytes.extend_from_slice(&element.to_le_bytes());
+        if !self.explicit {
+            self.bytes.extend_from_slice(&len.to_le_bytes());
+        } else if matches!(vr, b"OB" | b"OW" | b"SQ" | b"UN" | b"UT") {
+            self.bytes.extend_from_slice(vr);
+            self.bytes.extend_from_slice(&[0, 0]);
+            self.bytes.extend_from_slice(&len.to_le_bytes());
+        } else {
+            self.bytes.extend_from_slice(vr);
+            self.bytes.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+
+    fn element(&mut self, group: u16, element: u16, vr: &[u8; 2], value: &[u8]) -> &mut Self {
+        self.header(group, element, vr, value.len() as u32);
+        self.bytes.extend_from_slice(value);
+        self
+    }
+
+    fn text(&mut self, group: u16, element: u16, vr: &[u8; 2], value: &str) -> &mut Self {
+        let pad = if vr == b"UI" { 0 } else { b' ' };
+        self.element(group, element, vr, &padded(value, pad))
+    }
+
+    fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    fn delimiter(&mut self, element: u16, len: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&0xFFFEu16.to_le_bytes());
+        self.bytes.extend_from_slice(&element.to_le_bytes());
+        self.bytes.extend_from_slice(&len.to_le_bytes());
+        self
+    }
+
+    fn standard_tags(&mut self) -> &mut Self {
+        self.text(0x0008, 0x0016, b"UI", "1.2.840.10008.5.1.4.1.1.2")
+            .text(0x0008, 0x0018, b"UI", "1.2.3.4.5.6")
+            .text(0x0008, 0x0060, b"CS", "CT")
+            .text(0x0010, 0x0020, b"LO", "P12345")
+            .text(0x0018, 0x0015, b"CS", "CHEST")
+            .text(0x0020, 0x000D, b"UI", "1.2.3.4")
+            .text(0x0020, 0x000E, b"UI", "1.2.3.4.5")
+            .element(0x0028, 0x0010, b"US", &2u16.to_le_bytes())
+            .element(0x0028, 0x0011, b"US", &3u16.to_le_bytes())
+            .element(0x0028, 0x0100, b"US", &8u16.to_le_bytes())
+    }
+}
+
+fn padded(value: &str, pad: u8) -> Vec<u8> {
+    let mut bytes = value.as_bytes().to_vec();
+    if bytes.len() % 2 == 1 {
+        bytes.push(pad);
+    }
+    bytes
+}
+
+#[test]
+fn test_parses_explicit_vr_little_endian() {
+    let mut builder = DicomBuilder::new(EXPLICIT_VR_LITTLE_ENDIAN);
+    builder.standard_tags().element(0x7FE0, 0x0010, b"OW", &[1, 2, 3, 4, 5, 6]);
+
+    let image = parse_dicom(&builder.bytes).unwrap();
+    assert_eq!(image.image_id, "1.2.3.4.5.6");
+    assert_eq!(image.data, vec![1, 2, 3, 4, 5, 6]);
+
+    let tags = image.dicom.as_ref().unwrap();
+    assert_eq!(tags.patient_id.as_deref(), Some("P12345"));
+    assert_eq!(tags.modality.as_deref(), Some("CT"));
+    assert_eq!(tags.body_part_examined.as_deref(), Some("CHEST"));
+    assert_eq!(tags.study_instance_uid.as_deref(), Some("1.2.3.4"));
+    assert_eq!(tags.series_instance_uid.as_deref(), Some("1.2.3.4.5"));
+    assert_eq!(tags.sop_class_uid.as_deref(), Some("1.2.840.10008.5.1.4.1.1.2"));
+    assert_eq!(tags.sop_instance_uid.as_deref(), Some("1.2.3.4.5.6"));
+    assert_eq!(tags.transfer_syntax_uid, EXPLICIT_VR_LITTLE_ENDIAN);
+    assert_eq!((tags.rows, tags.columns, tags.bits_allocated), (Some(2), Some(3), Some(8)));
+
+    assert_eq!(image.metadata["patient_id"], "P12345");
+    assert_eq!(image.metadata["modality"], "CT");
+    assert_eq!(image.metadata["body_part"], "CHEST");
+    assert_eq!(image.metadata["study_instance_uid"], "1.2.3.4");
+}
+
+#[test]
+fn test_parses_implicit_vr_little_endian() {
+    let mut builder = DicomBuilder::new(IMPLICIT_VR_LITTLE_ENDIAN);
+    builder.standard_tags().element(0x7FE0, 0x0010, b"OW", &[9, 8, 7, 6]);
+
+    let image = parse_dicom(&builder.bytes).unwrap();
+    assert_eq!(image.data, vec![9, 8, 7, 6]);
+    let tags = image.dicom.unwrap();
+    assert_eq!(tags.modality.as_deref(), Some("CT"));
+    assert_eq!(tags.columns, Some(3));
+}
+
+#[test]
+fn test_skips_sequences_of_undefined_length() {
+    let mut builder = DicomBuilder::new(EXPLICIT_VR_LITTLE_ENDIAN);
+    builder.text(0x0008, 0x0060, b"CS", "MR");
+
+    // Referenced Series Sequence with one undefined-length item holding a nested sequence
+    builder.header(0x0008, 0x1115, b"SQ", u32::MAX);
+    builder.delimiter(0xE000, u32::MAX);
+    builder.text(0x0020, 0x000E, b"UI", "9.9.9");
+    builder.header(0x0008, 0x114A, b"SQ", u32::MAX);
+    builder.delimiter(0xE000, 12);
+    builder.text(0x0008, 0x1155, b"UI", "9.9");
+    builder.delimiter(0xE0DD, 0);
+    builder.delimiter(0xE00D, 0);
+    builder.delimiter(0xE0DD, 0);
+
+    builder.text(0x0020, 0x000E, b"UI", "1.2.3.4.5");
+    builder.element(0x7FE0, 0x0010, b"OB", &[1, 2]);
+
+    let image = parse_dicom(&builder.bytes).unwrap();
+    let tags = image.dicom.unwrap();
+    assert_eq!(tags.modality.as_deref(), Some("MR"));
+    // The series UID inside the sequence must not leak into the top-level tags
+    assert_eq!(tags.series_instance_uid.as_deref(), Some("1.2.3.4.5"));
+    assert_eq!(image.data, vec![1, 2]);
+}
+
+#[test]
+fn test_rejects_sequences_nested_too_deeply() {
+    // Each level is a sequence whose one item holds the next; a crafted file can nest far deeper
+    let nested = |levels: usize| {
+        let mut builder = DicomBuilder::new(EXPLICIT_VR_LITTLE_ENDIAN);
+        for _ in 0..levels {
+            builder.header(0x0008, 0x1115, b"SQ", u32::MAX);
+            builder.delimiter(0xE000, u32::MAX);
+        }
+        for _ in 0..levels {
+            builder.delimiter(0xE00D, 0).delimiter(0xE0DD, 0);
+        }
+        builder.element(0x7FE0, 0x0010, b"OB", &[1, 2]);
+        parse_dicom(&builder.bytes)
+    };
+
+    assert_eq!(nested(64).unwrap().data, vec![1, 2]);
+    assert!(matches!(nested(65), Err(DicomError::TooDeep(_))));
+    assert!(matches!(nested(100_000), Err(DicomError::TooDeep(_))));
+}
+
+#[test]
+fn test_concatenates_encapsulated_pixel_fragments() {
+    let mut builder = DicomBuilder::new("1.2.840.10008.1.2.4.50");
+    builder.standard_tags();
+    builder.header(0x7FE0, 0x0010, b"OB", u32::MAX);
+    builder.delimiter(0xE000, 0); // empty basic offset table
+    builder.delimiter(0xE000, 4).raw(&[0xFF, 0xD8, 0xFF, 0xE0]);
+    builder.delimiter(0xE000, 2).raw(&[0xFF, 0xD9]);
+    builder.delimiter(0xE0DD, 0);
+
+    let image = parse_dicom(&builder.bytes).unwrap();
+    assert_eq!(image.data, vec![0xFF, 0xD8, 0xFF, 0xE0, 0xFF, 0xD9]);
+    assert_eq!(image.metadata["mime_type"], "image/jpeg");
+}
+
+#[test]
+fn test_rejects_invalid_input() {
+    assert!(matches!(parse_dicom(b"not a dicom file"), Err(DicomError::NotDicom)));
+
+    let mut builder = DicomBuilder::new(EXPLICIT_VR_LITTLE_ENDIAN);
+    builder.standard_tags();
+    assert!(matches!(parse_dicom(&builder.bytes), Err(DicomError::MissingPixelData)));
+
+    builder.header(0x7FE0, 0x0010, b"OW", 100);
+    assert!(matches!(parse_dicom(&builder.bytes), Err(DicomError::Truncated(_))));
+
+    let builder = DicomBuilder::new("1.2.840.10008.1.2.2");
+    assert!(matches!(parse_dicom(&builder.bytes), Err(DicomError::UnsupportedTransferSyntax(_))));
+}
+
+#[test]
+fn test_load_dicom_from_file() {
+    let mut builder = DicomBuilder::new(EXPLICIT_VR_LITTLE_ENDIAN);
+    builder.text(0x0008, 0x0060, b"CS", "CR").element(0x7FE0, 0x0010, b"OW", &[0, 1]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("chest.dcm");
+    std::fs::write(&path, &builder.bytes).unwrap();
+
+    // Without a SOP Instance UID the file name becomes the image id
+    let image = load_dicom(&path).unwrap();
+    assert_eq!(image.image_id, "chest");
+    assert_eq!(image.metadata["modality"], "CR");
+}
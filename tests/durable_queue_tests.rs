@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp::journal::{JobJournal, JournalEntry, JournalState};
+use mcp::mock::MockServer;
+use mcp::queue::{JobId, JobQueue, JobStatus, Priority, QueueConfig};
+use mcp::store::{MemoryResultStore, ResultQuery, ResultStore, SledResultStore};
+use mcp::{RadiologyCluster, RadiologyResult};
+
+mod common;
+use common::{called_ids, ct_cluster, image, slow_server, wait_for};
+
+// Results and the journal share one database, as they would in a deployment
+async fn cluster_for(server: &MockServer, db: &sled::Db) -> Arc<RadiologyCluster> {
+    ct_cluster(server, Arc::new(SledResultStore::from_db(db.clone()))).await
+}
+
+// For a journal opened on its own path; results are kept in memory
+async fn memory_cluster(server: &MockServer) -> Arc<RadiologyCluster> {
+    ct_cluster(server, Arc::new(MemoryResultStore::new())).await
+}
+
+fn entry(id: JobId, context_id: &str, image_id: &str, priority: Priority, state: JournalState) -> JournalEntry {
+    JournalEntry {
+        id,
+        context_id: context_id.to_string(),
+        priority,
+        image: image(image_id),
+        state,
+    }
+}
+
+#[tokio::test]
+async fn test_jobs_stay_journalled_until_finished() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+    let server = slow_server(Duration::from_millis(100)).await;
+    let journal = JobJournal::from_db(db.clone()).unwrap();
+    let queue = JobQueue::with_journal(cluster_for(&server, &db).await, QueueConfig::new().max_concurrent(1), journal).await.unwrap();
+
+    let first = queue.submit("ct", image("IMG1"), Priority::Routine).await.unwrap();
+    let second = queue.submit("ct", image("IMG2"), Priority::Routine).await.unwrap();
+
+    // The first job is marked in flight as it starts
+    let states = || {
+        let pending = JobJournal::from_db(db.clone()).unwrap().pending().unwrap();
+        pending.iter().map(|entry| (entry.id, entry.state)).collect::<Vec<_>>()
+    };
+    wait_for(|| states() == vec![(first, JournalState::InFlight), (second, JournalState::Queued)]).await;
+
+    assert!(matches!(queue.wait(first).await, Some(JobStatus::Completed(_))));
+    assert!(matches!(queue.wait(second).await, Some(JobStatus::Completed(_))));
+    // The journal entry goes just after the status is published
+    let journal = JobJournal::from_db(db).unwrap();
+    wait_for(|| journal.pending().unwrap().is_empty()).await;
+}
+
+#[tokio::test]
+async fn test_pending_jobs_resume_after_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+
+    // What a crashed process left behind
+    let journal = JobJournal::from_db(db.clone()).unwrap();
+    let ids: Vec<JobId> = (0..3).map(|_| journal.next_id().unwrap()).collect();
+    journal.append(&entry(ids[0], "ct", "RUNNING", Priority::Routine, JournalState::InFlight)).unwrap();
+    journal.append(&entry(ids[1], "ct", "ROUTINE", Priority::Routine, JournalState::Queued)).unwrap();
+    journal.append(&entry(ids[2], "ct", "STAT", Priority::Stat, JournalState::Queued)).unwrap();
+
+    let server = slow_server(Duration::from_millis(10)).await;
+    let cluster = cluster_for(&server, &db).await;
+    let queue = JobQueue::with_journal(cluster.clone(), QueueConfig::new().max_concurrent(1), journal).await.unwrap();
+
+    for (id, image_id) in ids.iter().zip(["RUNNING", "ROUTINE", "STAT"]) {
+        match queue.wait(*id).await {
+            Some(JobStatus::Completed(result)) => assert_eq!(result.findings, format!("Findings for {}", image_id)),
+            other => panic!("unexpected status {:?}", other),
+        }
+    }
+    assert_eq!(called_ids(&server), vec!["STAT", "RUNNING", "ROUTINE"]);
+    assert_eq!(cluster.get_results("ct").await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_recorded_results_are_not_analyzed_again() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+
+    // The process crashed after recording the result but before clearing the journal
+    let recorded = RadiologyResult {
+        image_id: "IMG1".to_string(),
+        findings: "Recorded before the crash".to_string(),
+        confidence_score: 0.8,
+        analysis_date: "2024-01-10T08:00:00Z".to_string(),
+        study_instance_uid: None,
+        series_instance_uid: None,
+        report: None,
+    };
+    SledResultStore::from_db(db.clone()).insert("ct", &recorded).unwrap();
+    let journal = JobJournal::from_db(db.clone()).unwrap();
+    let id = journal.next_id().unwrap();
+    journal.append(&entry(id, "ct", "IMG1", Priority::Routine, JournalState::InFlight)).unwrap();
+
+    let server = slow_server(Duration::from_millis(10)).await;
+    let cluster = cluster_for(&server, &db).await;
+    let queue = JobQueue::with_journal(cluster.clone(), QueueConfig::new(), journal).await.unwrap();
+
+    assert_eq!(queue.wait(id).await, Some(JobStatus::Completed(recorded.clone())));
+    assert!(server.requests_for("tools/call").is_empty());
+
+    // Submitting the same image again still records it only once
+    let again = queue.submit("ct", image("IMG1"), Priority::Routine).await.unwrap();
+    assert_eq!(queue.wait(again).await, Some(JobStatus::Completed(recorded)));
+    let stored = cluster.query_results(&ResultQuery::for_context("ct").image_id("IMG1")).await.unwrap();
+    assert_eq!(stored.len(), 1);
+}
+
+#[tokio::test]
+async fn test_jobs_for_unknown_contexts_wait_for_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+    let journal = JobJournal::from_db(db.clone()).unwrap();
+    let id = journal.next_id().unwrap();
+    journal.append(&entry(id, "mri", "IMG1", Priority::Routine, JournalState::Queued)).unwrap();
+
+    let server = slow_server(Duration::from_millis(10)).await;
+    let cluster = cluster_for(&server, &db).await;
+    let queue = JobQueue::with_journal(cluster.clone(), QueueConfig::new(), journal).await.unwrap();
+
+    assert_eq!(queue.status(id), Some(JobStatus::Queued));
+    assert_eq!(queue.parked(), 1);
+    assert_eq!(JobJournal::from_db(db.clone()).unwrap().pending().unwrap().len(), 1);
+    assert!(server.requests_for("tools/call").is_empty());
+
+    cluster.initialize_context("mri", "mri-model").await.unwrap();
+    queue.resume();
+    assert_eq!(queue.parked(), 0);
+    match queue.wait(id).await {
+        Some(JobStatus::Completed(result)) => assert_eq!(result.findings, "Findings for IMG1"),
+        other => panic!("unexpected status {:?}", other),
+    }
+    let journal = JobJournal::from_db(db).unwrap();
+    wait_for(|| journal.pending().unwrap().is_empty()).await;
+}
+
+#[test]
+fn test_submitted_jobs_resume_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Stopping the runtime ends the first process with its jobs unfinished
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let ids = runtime.block_on(async {
+        let server = slow_server(Duration::from_secs(60)).await;
+        let journal = JobJournal::open(dir.path()).unwrap();
+        let queue = JobQueue::with_journal(memory_cluster(&server).await, QueueConfig::new().max_concurrent(1), journal).await.unwrap();
+        let mut ids = Vec::new();
+        for image_id in ["IMG1", "IMG2"] {
+            let mut submitted = image(image_id);
+            submitted.metadata.insert("modality".to_string(), "CT".to_string());
+            ids.push(queue.submit("ct", submitted, Priority::Routine).await.unwrap());
+        }
+        ids
+    });
+    drop(runtime);
+
+    let journal = reopen(dir.path());
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let pending = journal.pending().unwrap();
+        assert_eq!(pending.iter().map(|entry| entry.id).collect::<Vec<_>>(), ids);
+        for entry in &pending {
+            assert_eq!(entry.image.data, vec![1, 2, 3]);
+            assert_eq!(entry.image.metadata["modality"], "CT");
+        }
+
+        let server = slow_server(Duration::from_millis(10)).await;
+        let queue = JobQueue::with_journal(memory_cluster(&server).await, QueueConfig::new().max_concurrent(1), journal).await.unwrap();
+        for (id, image_id) in ids.iter().zip(["IMG1", "IMG2"]) {
+            match queue.wait(*id).await {
+                Some(JobStatus::Completed(result)) => assert_eq!(result.findings, format!("Findings for {}", image_id)),
+                other => panic!("unexpected status {:?}", other),
+            }
+        }
+        assert_eq!(called_ids(&server), vec!["IMG1", "IMG2"]);
+    });
+}
+
+// sled lets go of its file lock only some time after the last handle drops
+fn reopen(path: &Path) -> JobJournal {
+    for _ in 0..200 {
+        if let Ok(journal) = JobJournal::open(path) {
+            return journal;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the journal stayed locked");
+}
+
+#[test]
+fn test_job_ids_keep_increasing_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+    let first = {
+        let journal = JobJournal::from_db(db.clone()).unwrap();
+        journal.next_id().unwrap()
+    };
+    let journal = JobJournal::from_db(db).unwrap();
+    assert!(journal.next_id().unwrap() > first);
+}
@@ -0,0 +1,39 @@
+use std::error::Error;
+
+use mcp::decode::DecodeError;
+use mcp::RadiologyError;
+use mcp_rust_sdk::error::ErrorCode;
+
+fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+#[test]
+fn test_radiology_error_is_send_and_sync() {
+    assert_send_sync::<RadiologyError>();
+}
+
+#[test]
+fn test_decode_errors_keep_their_source() {
+    let err = RadiologyError::from(DecodeError::MissingField("findings"));
+    assert!(matches!(err, RadiologyError::Decode(DecodeError::MissingField("findings"))));
+
+    let source = err.source().expect("decode errors chain their cause");
+    assert_eq!(source.to_string(), "response is missing required field 'findings'");
+}
+
+#[test]
+fn test_sdk_errors_are_classified() {
+    let transport = RadiologyError::from(mcp_rust_sdk::Error::Transport("connection reset".to_string()));
+    assert!(matches!(transport, RadiologyError::Transport(_)));
+    // The detail comes from the source only, so error-chain reports don't repeat it
+    assert_eq!(transport.to_string(), "transport failure");
+    assert!(transport.source().unwrap().to_string().contains("connection reset"));
+
+    let rejected = RadiologyError::from(mcp_rust_sdk::Error::protocol(ErrorCode::RequestFailed, "model overloaded"));
+    assert!(matches!(
+        rejected,
+        RadiologyError::Rejected { code: Some(-32000), ref message } if message == "model overloaded"
+    ));
+
+    let malformed = RadiologyError::from(mcp_rust_sdk::Error::Serialization("expected value".to_string()));
+    assert!(matches!(malformed, RadiologyError::Protocol { source: Some(_), .. }));
+}
@@ -0,0 +1,153 @@
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use mcp::mock::{Fault, FaultRule, Latency, MockServer, RpcError};
+use mcp::{McpClient, RadiologyError};
+
+async fn analysis_server(configure: impl FnOnce(mcp::mock::MockServerBuilder) -> mcp::mock::MockServerBuilder) -> MockServer {
+    let builder = MockServer::builder().tool_response("analyze_image", json!({ "findings": "Normal", "confidence": 0.9 }));
+    configure(builder).start().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_latency_delays_answers() {
+    let server = analysis_server(|b| b.fault(FaultRule::on("ping", Fault::Delay(Latency::Fixed(Duration::from_millis(150)))))).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    let started = Instant::now();
+    client.request("ping", None).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(150));
+
+    // Other methods are unaffected
+    let started = Instant::now();
+    client.initialize().await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(150));
+}
+
+#[tokio::test]
+async fn test_dropped_connection_fails_in_flight_requests() {
+    let server = analysis_server(|b| b.fault(FaultRule::on("tools/call", Fault::Disconnect))).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    let err = client.call_tool("analyze_image", json!({})).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)), "unexpected error: {:?}", err);
+    assert!(!client.is_connected());
+    assert_eq!(server.requests_for("tools/call").len(), 1);
+}
+
+#[tokio::test]
+async fn test_malformed_answers_are_dropped() {
+    // Mostly multibyte text, so the middle of the answer falls inside a character
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "findings": "µ".repeat(200), "confidence": 0.9 }))
+        .fault(FaultRule::on("tools/call", Fault::MalformedJson).times(1))
+        .start()
+        .await
+        .unwrap();
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    // A truncated answer can't be matched to its request, which is left to its deadline
+    let first = tokio::time::timeout(Duration::from_millis(300), client.call_tool("analyze_image", json!({}))).await;
+    assert!(first.is_err(), "unexpected answer: {:?}", first);
+
+    // The connection survives and later answers are fine
+    assert!(client.is_connected());
+    assert_eq!(client.request("ping", None).await.unwrap(), json!({}));
+}
+
+#[tokio::test]
+async fn test_error_objects_replace_selected_answers() {
+    let busy = Fault::Error(RpcError::new(-32000, "model busy"));
+    let server = analysis_server(|b| b.fault(FaultRule::on("tools/call", busy).after(1).times(1))).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    assert!(client.call_tool("analyze_image", json!({})).await.is_ok());
+    let err = client.call_tool("analyze_image", json!({})).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: Some(-32000), ref message } if message == "model busy"));
+    assert!(client.call_tool("analyze_image", json!({})).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_faults_with_zero_probability_never_fire() {
+    let server = analysis_server(|b| b.fault(FaultRule::any(Fault::Disconnect).probability(0.0))).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    for _ in 0..20 {
+        client.call_tool("analyze_image", json!({})).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_reordered_answers_arrive_newest_first() {
+    let server = MockServer::builder().reorder(3).start().await.unwrap();
+    let (mut socket, _) = connect_async(server.url()).await.unwrap();
+
+    for id in 1..=3 {
+        let ping = json!({ "jsonrpc": "2.0", "id": id, "method": "ping" });
+        socket.send(Message::Text(ping.to_string())).await.unwrap();
+    }
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        let reply: Value = serde_json::from_str(socket.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+        ids.push(reply["id"].as_i64().unwrap());
+    }
+    assert_eq!(ids, [3, 2, 1]);
+
+    // A lone request isn't held back waiting for the window to fill
+    let client = McpClient::connect(&server.url()).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(1), client.request("ping", None)).await
+        .expect("Lone request was held")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_refused_handshake() {
+    let server = analysis_server(|b| b.refuse_handshake(-32001, "maintenance window")).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    let err = client.initialize().await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: Some(-32001), .. }));
+    assert!(client.call_tool("analyze_image", json!({})).await.is_err());
+    assert!(server.requests_for("tools/call").is_empty());
+}
+
+#[tokio::test]
+async fn test_malformed_answers_fail_only_the_request_they_name() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut answered = 0;
+        while let Some(Ok(Message::Text(text))) = socket.next().await {
+            let request: Value = serde_json::from_str(&text).unwrap();
+            // Cancellations of the unanswered requests get no answer
+            if request.get("id").is_none() {
+                continue;
+            }
+            let reply = match answered {
+                0 => "<html>502 Bad Gateway</html>".to_string(),
+                // The id is only nested inside the result
+                1 => json!({ "jsonrpc": "2.0", "result": { "id": request["id"] } }).to_string(),
+                // The id is at the top level, but the error isn't an error object
+                2 => json!({ "jsonrpc": "2.0", "id": request["id"], "error": "busy" }).to_string(),
+                _ => json!({ "jsonrpc": "2.0", "id": request["id"], "result": {} }).to_string(),
+            };
+            answered += 1;
+            socket.send(Message::Text(reply)).await.unwrap();
+        }
+    });
+    let client = McpClient::connect(&url).await.unwrap();
+
+    for _ in 0..2 {
+        let unanswered = tokio::time::timeout(Duration::from_millis(300), client.request("ping", None)).await;
+        assert!(unanswered.is_err(), "unexpected answer: {:?}", unanswered);
+    }
+    let err = tokio::time::timeout(Duration::from_secs(5), client.request("ping", None)).await.unwrap().unwrap_err();
+    assert!(matches!(err, RadiologyError::Protocol { source: Some(_), .. }), "unexpected error: {:?}", err);
+    assert_eq!(client.request("ping", None).await.unwrap(), json!({}));
+}
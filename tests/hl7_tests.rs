@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use mcp::findings::{Code, Finding, FindingsReport, Laterality, Measurement};
+use mcp::hl7::{escape, Forwarded, MllpSender, OruMessage, ResultStatus};
+use mcp::mock::MockServer;
+use mcp::retry::RetryPolicy;
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage, RadiologyResult};
+
+fn result(findings: &str, report: Option<FindingsReport>) -> RadiologyResult {
+    RadiologyResult {
+        image_id: "IMG001".to_string(),
+        findings: findings.to_string(),
+        confidence_score: 0.75,
+        analysis_date: "2024-03-01T09:30:15Z".to_string(),
+        study_instance_uid: Some("1.2.3.4".to_string()),
+        series_instance_uid: None,
+        report,
+    }
+}
+
+fn segments(message: &str) -> Vec<Vec<String>> {
+    message.split('\r').map(|segment| segment.split('|').map(str::to_string).collect()).collect()
+}
+
+fn observations(message: &str) -> Vec<Vec<String>> {
+    segments(message).into_iter().filter(|fields| fields[0] == "OBX").collect()
+}
+
+// A RIS interface stub: passes on each message it receives and acknowledges it
+// with the next of `codes`, `AA` once they run out; `None` leaves it unanswered
+async fn ris(codes: Vec<Option<&'static str>>) -> (String, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (received, messages) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut codes = codes.into_iter();
+        while let Ok((mut stream, _)) = listener.accept().await {
+            while let Some(message) = read_frame(&mut stream).await {
+                let control_id = segments(&message)[0][9].clone();
+                received.send(message).unwrap();
+                let Some(code) = codes.next().unwrap_or(Some("AA")) else {
+                    continue;
+                };
+                let ack = format!("MSH|^~\\&|RIS|||||ACK^R01|A1|P|2.5.1\rMSA|{}|{}|Checked", code, control_id);
+                let frame = [&[0x0B][..], ack.as_bytes(), &[0x1C, 0x0D]].concat();
+                stream.write_all(&frame).await.unwrap();
+            }
+        }
+    });
+    (address, messages)
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Option<String> {
+    let mut frame = Vec::new();
+    while !frame.ends_with(&[0x1C, 0x0D]) {
+        let mut byte = [0];
+        if stream.read(&mut byte).await.ok()? == 0 {
+            return None;
+        }
+        frame.push(byte[0]);
+    }
+    assert_eq!(frame[0], 0x0B);
+    Some(String::from_utf8(frame[1..frame.len() - 2].to_vec()).unwrap())
+}
+
+#[test]
+fn test_escapes_delimiters_and_line_breaks() {
+    assert_eq!(escape("a|b^c&d~e\\f"), "a\\F\\b\\S\\c\\T\\d\\R\\e\\E\\f");
+    assert_eq!(escape("line 1\r\nline 2\nline 3"), "line 1\\.br\\line 2\\.br\\line 3");
+}
+
+#[test]
+fn test_renders_structured_findings_as_oru() {
+    let report = FindingsReport {
+        findings: vec![Finding {
+            description: "Nodule | irregular & spiculated".to_string(),
+            location: Some("upper lobe".to_string()),
+            laterality: Some(Laterality::Right),
+            measurements: vec![Measurement { value: 8.5, unit: "mm".to_string(), dimension: None }],
+            codes: vec![Code::new("snomed", "27925004", Some("Nodule"))],
+            confidence: Some(0.7),
+            ..Finding::default()
+        }],
+        impression: Some("Suspicious nodule^follow up".to_string()),
+    };
+    let metadata = HashMap::from([
+        ("patient_id".to_string(), "P12345".to_string()),
+        ("patient_name".to_string(), "O'Brien&Co^Jane".to_string()),
+        ("accession_number".to_string(), "ACC-1".to_string()),
+        ("modality".to_string(), "CT".to_string()),
+        ("body_part".to_string(), "CHEST".to_string()),
+    ]);
+    let message = OruMessage::new(&result("ignored", Some(report)))
+        .metadata(&metadata)
+        .sending_application("RADIOLOGY-AI")
+        .receiving_application("RIS")
+        .control_id("MSG0001")
+        .status(ResultStatus::Final)
+        .render();
+
+    let segments = segments(&message);
+    let names: Vec<&str> = segments.iter().map(|fields| fields[0].as_str()).collect();
+    assert_eq!(names, vec!["MSH", "PID", "OBR", "OBX", "OBX", "OBX", "OBX", "OBX", "OBX", "OBX"]);
+
+    let msh = &segments[0];
+    assert_eq!(msh[1], "^~\\&");
+    assert_eq!((msh[2].as_str(), msh[4].as_str()), ("RADIOLOGY-AI", "RIS"));
+    assert_eq!((msh[8].as_str(), msh[9].as_str(), msh[11].as_str()), ("ORU^R01^ORU_R01", "MSG0001", "2.5.1"));
+
+    assert_eq!(segments[1][3], "P12345");
+    assert_eq!(segments[1][5], "O'Brien\\T\\Co^Jane");
+
+    let obr = &segments[2];
+    assert_eq!(obr[3], "ACC-1");
+    assert_eq!(obr[4], "^CT CHEST");
+    assert_eq!(obr[7], "20240301093015+0000");
+    assert_eq!(obr[25], "F");
+
+    let obx = observations(&message);
+    let set_ids: Vec<&str> = obx.iter().map(|fields| fields[1].as_str()).collect();
+    assert_eq!(set_ids, vec!["1", "2", "3", "4", "5", "6", "7"]);
+    assert!(obx.iter().all(|fields| fields[11] == "F" && fields[14] == "20240301093015+0000"));
+
+    assert_eq!((obx[0][2].as_str(), obx[0][3].as_str()), ("ST", "STUDY_UID^Study Instance UID^99MCP"));
+    assert_eq!(obx[0][5], "1.2.3.4");
+
+    // The finding's text, code, size and confidence share sub-id 1
+    assert!(obx[1..5].iter().all(|fields| fields[4] == "1"));
+    assert_eq!(obx[1][3], "18782-3^Radiology Study observation (narrative)^LN");
+    assert_eq!(obx[1][5], "right upper lobe: Nodule \\F\\ irregular \\T\\ spiculated (8.5 mm)");
+    assert_eq!((obx[2][2].as_str(), obx[2][5].as_str()), ("CWE", "27925004^Nodule^SCT"));
+    assert_eq!((obx[3][2].as_str(), obx[3][5].as_str(), obx[3][6].as_str()), ("NM", "8.5", "mm^mm^UCUM"));
+    assert_eq!(obx[4][5], "0.7");
+
+    assert_eq!(obx[5][3], "19005-8^Radiology Imaging study [Impression] (narrative)^LN");
+    assert_eq!(obx[5][5], "Suspicious nodule\\S\\follow up");
+    assert_eq!((obx[6][4].as_str(), obx[6][5].as_str()), ("", "0.75"));
+}
+
+#[test]
+fn test_renders_plain_text_findings() {
+    let message = OruMessage::new(&result("No acute findings.\nLungs are clear.", None)).render();
+    let obx = observations(&message);
+    assert_eq!(obx.len(), 3);
+    assert_eq!(obx[1][2], "TX");
+    assert_eq!(obx[1][5], "No acute findings.\\.br\\Lungs are clear.");
+    assert_eq!(obx[1][11], "P");
+    assert_eq!(segments(&message)[2][4], "^Imaging study");
+}
+
+#[tokio::test]
+async fn test_mllp_sender_checks_acknowledgements() {
+    let (address, mut received) = ris(vec![Some("AA"), Some("AE")]).await;
+    let sender = MllpSender::new(&address);
+
+    let first = OruMessage::new(&result("Normal", None)).control_id("MSG1").render();
+    let ack = sender.send(&first).await.unwrap();
+    assert_eq!((ack.code.as_str(), ack.control_id.as_str()), ("AA", "MSG1"));
+    assert_eq!(received.recv().await.unwrap(), first);
+
+    let second = OruMessage::new(&result("Normal", None)).control_id("MSG2").render();
+    let err = sender.send(&second).await.unwrap_err();
+    assert!(matches!(&err, RadiologyError::Rejected { message, .. } if message == "AE: Checked"), "{:?}", err);
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn test_mllp_sender_times_out_and_reconnects() {
+    let (address, mut received) = ris(vec![None]).await;
+    let sender = MllpSender::new(&address).timeout(Duration::from_millis(200));
+
+    let message = OruMessage::new(&result("Normal", None)).render();
+    let err = sender.send(&message).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Timeout(_)), "{:?}", err);
+    received.recv().await.unwrap();
+
+    // The unanswered connection is dropped and the retry goes over a new one
+    let ack = RetryPolicy::none().run(|_| sender.send(&message)).await.unwrap();
+    assert_eq!(ack.code, "AA");
+
+    let closed = MllpSender::new("127.0.0.1:1");
+    assert!(matches!(closed.send(&message).await, Err(RadiologyError::Transport(_))));
+}
+
+#[tokio::test]
+async fn test_recorded_results_are_forwarded() {
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "status": "success", "findings": "Normal", "confidence": 0.9 }))
+        .start()
+        .await
+        .unwrap();
+    let client = Arc::new(McpClient::connect(&server.url()).await.unwrap());
+    let cluster = RadiologyCluster::new(client);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let (address, mut received) = ris(Vec::new()).await;
+    let results = cluster.subscribe_results();
+    let forwarder = tokio::spawn(async move {
+        let sender = MllpSender::new(&address);
+        sender
+            .forward(results, &RetryPolicy::none(), |recorded| {
+                OruMessage::new(&recorded.result).metadata(&recorded.metadata).sending_application("RADIOLOGY-AI")
+            })
+            .await
+    });
+
+    let image = RadiologyImage {
+        image_id: "IMG001".to_string(),
+        data: vec![1, 2, 3],
+        metadata: HashMap::from([("patient_id".to_string(), "P12345".to_string())]),
+        dicom: None,
+    };
+    cluster.submit_image("ct", image).await.unwrap();
+
+    let message = received.recv().await.unwrap();
+    let segments = segments(&message);
+    // The RIS gets the real patient id, not the pseudonym the model server saw
+    assert_eq!(segments[1][3], "P12345");
+    assert_eq!(observations(&message)[0][5], "Normal");
+
+    drop(cluster);
+    assert_eq!(forwarder.await.unwrap(), Forwarded { delivered: 1, failed: 0, missed: 0 });
+}
+
+#[tokio::test]
+async fn test_forwarding_counts_results_it_fell_behind_on() {
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "status": "success", "findings": "Normal", "confidence": 0.9 }))
+        .start()
+        .await
+        .unwrap();
+    let client = Arc::new(McpClient::connect(&server.url()).await.unwrap());
+    let cluster = RadiologyCluster::new(client);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    // Nothing is forwarded until all 300 results are recorded, more than the subscription holds
+    let results = cluster.subscribe_results();
+    for index in 0..300 {
+        let image = RadiologyImage {
+            image_id: format!("IMG{}", index),
+            data: vec![1, 2, 3],
+            metadata: HashMap::new(),
+            dicom: None,
+        };
+        cluster.submit_image("ct", image).await.unwrap();
+    }
+    drop(cluster);
+
+    let (address, mut received) = ris(Vec::new()).await;
+    let forwarded = MllpSender::new(&address)
+        .forward(results, &RetryPolicy::none(), |recorded| {
+            OruMessage::new(&recorded.result).accession_number(&recorded.result.image_id)
+        })
+        .await;
+    assert_eq!(forwarded, Forwarded { delivered: 256, failed: 0, missed: 44 });
+    // The oldest results are the ones missed
+    let first = received.recv().await.unwrap();
+    assert_eq!(segments(&first)[2][3], "IMG44");
+    assert_eq!(received.len(), 255);
+}
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use mcp::mock::MockServer;
+use mcp::transfer::{TransferConfig, UPLOAD_METHOD};
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
+
+// Server that acknowledges uploads and analyzes anything
+async fn start_server() -> MockServer {
+    MockServer::builder()
+        .tool_response("analyze_image", json!({ "findings": "Normal", "confidence": 0.9 }))
+        .method(UPLOAD_METHOD, |params| Ok(json!({ "received": params["sequence"] })))
+        .start()
+        .await
+        .unwrap()
+}
+
+// Uploads and analysis calls, leaving out the handshake
+fn work_requests(server: &MockServer) -> Vec<Value> {
+    server
+        .requests()
+        .into_iter()
+        .filter(|r| r["method"] == UPLOAD_METHOD || r["method"] == "tools/call")
+        .collect()
+}
+
+async fn cluster_for(server_url: &str, transfer: TransferConfig) -> RadiologyCluster {
+    let client = McpClient::connect(server_url).await.expect("Failed to connect to test server");
+    let cluster = RadiologyCluster::new(Arc::new(client)).with_transfer_config(transfer);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster
+}
+
+fn image(data: Vec<u8>) -> RadiologyImage {
+    let mut metadata = HashMap::new();
+    metadata.insert("mime_type".to_string(), "application/dicom".to_string());
+    RadiologyImage {
+        image_id: "IMG001".to_string(),
+        data,
+        metadata,
+        dicom: None,
+    }
+}
+
+// Reassemble uploaded chunks in sequence order
+fn uploaded_bytes(requests: &[Value]) -> Vec<u8> {
+    let mut chunks: Vec<&Value> = requests.iter().filter(|r| r["method"] == UPLOAD_METHOD).collect();
+    chunks.sort_by_key(|r| r["params"]["sequence"].as_u64());
+    chunks
+        .iter()
+        .flat_map(|r| BASE64.decode(r["params"]["data"].as_str().unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_small_images_are_sent_inline() {
+    let server = start_server().await;
+    let cluster = cluster_for(&server.url(), TransferConfig::default()).await;
+
+    let data: Vec<u8> = (0..=255).collect();
+    cluster.submit_image("ct", image(data.clone())).await.unwrap();
+
+    let requests = work_requests(&server);
+    assert_eq!(requests.len(), 1);
+    let block = &requests[0]["params"]["arguments"]["image"];
+    assert_eq!(block["type"], "image");
+    assert_eq!(block["mimeType"], "application/dicom");
+    assert_eq!(BASE64.decode(block["data"].as_str().unwrap()).unwrap(), data);
+}
+
+#[tokio::test]
+async fn test_large_images_are_uploaded_in_chunks() {
+    let server = start_server().await;
+    let transfer = TransferConfig {
+        inline_limit: 64,
+        chunk_size: 100,
+        ..TransferConfig::default()
+    };
+    let cluster = cluster_for(&server.url(), transfer).await;
+
+    let data: Vec<u8> = (0..250u32).map(|i| i as u8).collect();
+    cluster.submit_image("ct", image(data.clone())).await.unwrap();
+
+    let requests = work_requests(&server);
+    let uploads = requests.iter().filter(|r| r["method"] == UPLOAD_METHOD).count();
+    assert_eq!(uploads, 3);
+    assert_eq!(uploaded_bytes(&requests), data);
+
+    // The analysis request comes last and references the finished upload
+    let analysis = requests.last().unwrap();
+    let resource = &analysis["params"]["arguments"]["image"]["resource"];
+    assert_eq!(analysis["params"]["arguments"]["image"]["type"], "resource");
+    assert_eq!(resource["size"], 250);
+    let upload_id = requests[0]["params"]["upload_id"].as_str().unwrap();
+    assert_eq!(resource["uri"], format!("upload://{}", upload_id));
+}
+
+#[tokio::test]
+async fn test_streamed_images_are_uploaded_without_buffering() {
+    let server = start_server().await;
+    let transfer = TransferConfig {
+        chunk_size: 1024,
+        ..TransferConfig::default()
+    };
+    let cluster = cluster_for(&server.url(), transfer).await;
+
+    let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let result = cluster
+        .submit_image_stream("ct", "SERIES001", HashMap::new(), std::io::Cursor::new(data.clone()))
+        .await
+        .unwrap();
+    assert_eq!(result.image_id, "SERIES001");
+
+    let requests = work_requests(&server);
+    assert_eq!(uploaded_bytes(&requests), data);
+    let last_chunk = requests.iter().rfind(|r| r["method"] == UPLOAD_METHOD).unwrap();
+    assert_eq!(last_chunk["params"]["done"], true);
+}
+
+#[tokio::test]
+async fn test_size_limit_is_enforced() {
+    let server = start_server().await;
+    let transfer = TransferConfig {
+        max_image_bytes: 100,
+        chunk_size: 40,
+        ..TransferConfig::default()
+    };
+    let cluster = cluster_for(&server.url(), transfer).await;
+
+    let err = cluster.submit_image("ct", image(vec![0; 101])).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::ImageTooLarge { size: 101, limit: 100 }));
+    assert!(work_requests(&server).is_empty(), "Oversized image should not be sent");
+
+    // Streams only discover their size while reading, and stop once over the limit
+    let err = cluster
+        .submit_image_stream("ct", "SERIES001", HashMap::new(), std::io::Cursor::new(vec![0u8; 1000]))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, RadiologyError::ImageTooLarge { limit: 100, .. }));
+    assert!(work_requests(&server).iter().all(|r| r["method"] == UPLOAD_METHOD));
+}
@@ -1,57 +1,41 @@
+use std::path::Path;
 use std::process::{Command, Stdio};
 use std::thread::sleep;
 use std::time::Duration;
-use std::sync::Once;
-
-// Used to ensure the mock server is only started once
-static START_MOCK_SERVER: Once = Once::new();
 
 // Integration test that uses the actual binary
 #[test]
 fn test_mcp_client_with_mock_server() {
-    // Start the mock server
-    START_MOCK_SERVER.call_once(|| {
-        // Start the mock server as a separate process
-        let server_process = Command::new("cargo")
-            .args(&["run", "--example", "mock_server"])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .expect("Failed to start mock server");
-        
-        // Give the server time to start
-        sleep(Duration::from_secs(2));
-        
-        // Register a shutdown function to kill the server process when tests complete
-        let pid = server_process.id();
-        std::mem::forget(server_process); // Don't kill process when variable goes out of scope
-        
-        #[cfg(unix)]
-        {
-            use std::os::unix::process::ExitStatusExt;
-            let _ = Command::new("kill")
-                .args(&[pid.to_string()])
-                .status();
-        }
-        
-        #[cfg(windows)]
-        {
-            let _ = Command::new("taskkill")
-                .args(&["/PID", &pid.to_string(), "/F"])
-                .status();
-        }
-    });
+    // Build the mock server up front and run it directly, so killing it
+    // doesn't leave an orphaned child of `cargo run` holding the port
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--example", "mock_server"])
+        .status()
+        .expect("Failed to build mock server");
+    assert!(status.success(), "Failed to build mock server");
+    
+    // Start the mock server as a separate process
+    let server_path = Path::new(env!("CARGO_BIN_EXE_mcp")).with_file_name("examples").join("mock_server");
+    let mut server_process = Command::new(server_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start mock server");
     
     // Wait for server to be ready
     sleep(Duration::from_secs(1));
     
     // Run the main client application
     let output = Command::new("cargo")
-        .args(&["run"])
+        .args(["run"])
         .env("MCP_WEBSOCKET_URL", "ws://localhost:8080")
         .output()
         .expect("Failed to run client");
     
+    // Shut the mock server down before asserting so a failure doesn't leak the process
+    let _ = server_process.kill();
+    let _ = server_process.wait();
+    
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
     
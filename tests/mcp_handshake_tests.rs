@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+use mcp::protocol::LATEST_PROTOCOL_VERSION;
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
+
+type Recorded = Arc<Mutex<Vec<Value>>>;
+
+// Test server that records every frame and answers through `reply`; `None` sends nothing
+async fn start_server<F>(reply: F) -> (String, Recorded)
+where
+    F: Fn(&Value) -> Option<Value> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let server_url = format!("ws://{}", listener.local_addr().expect("Failed to get local address"));
+    let recorded: Recorded = Arc::new(Mutex::new(Vec::new()));
+    let reply = Arc::new(reply);
+
+    let requests = recorded.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (requests, reply) = (requests.clone(), reply.clone());
+            tokio::spawn(async move {
+                let mut ws_stream = accept_async(stream).await.expect("Failed to accept WebSocket");
+                while let Some(Ok(msg)) = ws_stream.next().await {
+                    let Ok(request) = serde_json::from_str::<Value>(msg.to_text().unwrap_or_default()) else {
+                        continue;
+                    };
+                    requests.lock().unwrap().push(request.clone());
+                    let Some(result) = reply(&request) else {
+                        continue;
+                    };
+                    let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result });
+                    ws_stream.send(Message::Text(response.to_string())).await
+                        .expect("Failed to send response");
+                }
+            });
+        }
+    });
+
+    (server_url, recorded)
+}
+
+fn initialize_result(version: &str) -> Value {
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": { "name": "handshake-server", "version": "1.2.3" }
+    })
+}
+
+// A well-behaved server whose tool list comes in two pages
+fn paged_server(request: &Value) -> Option<Value> {
+    request.get("id")?;
+    Some(match request["method"].as_str()? {
+        "initialize" => initialize_result(LATEST_PROTOCOL_VERSION),
+        "tools/list" if request["params"]["cursor"] == "page-2" => json!({
+            "tools": [{ "name": "analyze_image", "inputSchema": { "type": "object" } }]
+        }),
+        "tools/list" => json!({
+            "tools": [{ "name": "echo", "inputSchema": { "type": "object" } }],
+            "nextCursor": "page-2"
+        }),
+        "tools/call" => json!({
+            "content": [{
+                "type": "text",
+                "text": "{\"findings\": \"No acute findings\", \"confidence\": 0.8}"
+            }]
+        }),
+        _ => json!({}),
+    })
+}
+
+fn image() -> RadiologyImage {
+    let mut metadata = HashMap::new();
+    metadata.insert("modality".to_string(), "CT".to_string());
+    RadiologyImage {
+        image_id: "IMG001".to_string(),
+        data: vec![1, 2, 3],
+        metadata,
+        dicom: None,
+    }
+}
+
+#[tokio::test]
+async fn test_handshake_precedes_tool_calls() {
+    let (server_url, recorded) = start_server(paged_server).await;
+    let client = McpClient::connect(&server_url).await.unwrap();
+
+    let session = client.initialize().await.unwrap();
+    assert_eq!(session.server_info.name, "handshake-server");
+    assert_eq!(session.protocol_version, LATEST_PROTOCOL_VERSION);
+    assert!(session.capabilities.tools.is_some());
+
+    // The handshake only runs once
+    client.initialize().await.unwrap();
+    let tools = client.list_tools().await.unwrap();
+    let names: Vec<_> = tools.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["echo", "analyze_image"]);
+
+    let requests = recorded.lock().unwrap();
+    let methods: Vec<_> = requests.iter().map(|r| r["method"].as_str().unwrap()).collect();
+    assert_eq!(methods, ["initialize", "notifications/initialized", "tools/list", "tools/list"]);
+    assert_eq!(requests[0]["params"]["protocolVersion"], LATEST_PROTOCOL_VERSION);
+    assert_eq!(requests[0]["params"]["clientInfo"]["name"], "mcp");
+    assert!(requests[1].get("id").is_none());
+}
+
+#[tokio::test]
+async fn test_submit_image_calls_analysis_tool_with_structured_arguments() {
+    let (server_url, recorded) = start_server(paged_server).await;
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server_url).await.unwrap()));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    // Text content holding JSON is decoded when there is no structured content
+    let result = cluster.submit_image("ct", image()).await.unwrap();
+    assert_eq!(result.findings, "No acute findings");
+    cluster.submit_image("ct", image()).await.unwrap();
+
+    let requests = recorded.lock().unwrap();
+    let calls: Vec<_> = requests.iter().filter(|r| r["method"] == "tools/call").collect();
+    assert_eq!(calls.len(), 2);
+    // Tools are only listed for the first submission
+    assert_eq!(requests.iter().filter(|r| r["method"] == "tools/list").count(), 2);
+
+    let params = &calls[0]["params"];
+    assert_eq!(params["name"], "analyze_image");
+    assert_eq!(params["arguments"]["model"], "ct-model");
+    assert_eq!(params["arguments"]["metadata"]["modality"], "CT");
+    assert_eq!(params["arguments"]["image"]["type"], "image");
+    assert!(params["arguments"]["prompt"].as_str().unwrap().contains("CT"));
+}
+
+#[tokio::test]
+async fn test_unsupported_protocol_version_is_refused() {
+    let (server_url, _) = start_server(|request| {
+        request.get("id")?;
+        Some(initialize_result("1999-01-01"))
+    })
+    .await;
+    let client = McpClient::connect(&server_url).await.unwrap();
+
+    let err = client.initialize().await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Protocol { .. }));
+    assert!(err.to_string().contains("1999-01-01"));
+}
+
+#[tokio::test]
+async fn test_missing_analysis_tool_is_reported() {
+    let (server_url, recorded) = start_server(paged_server).await;
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server_url).await.unwrap()))
+        .with_analysis_tool("segment_lungs");
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let err = cluster.submit_image("ct", image()).await.unwrap_err();
+    assert!(err.to_string().contains("segment_lungs"));
+    assert!(recorded.lock().unwrap().iter().all(|r| r["method"] != "tools/call"));
+}
+
+#[tokio::test]
+async fn test_tool_errors_are_rejections() {
+    let (server_url, _) = start_server(|request| {
+        if request["method"] == "tools/call" {
+            return Some(json!({
+                "content": [{ "type": "text", "text": "model is overloaded" }],
+                "isError": true
+            }));
+        }
+        paged_server(request)
+    })
+    .await;
+    let client = McpClient::connect(&server_url).await.unwrap();
+
+    let err = client.call_tool("analyze_image", json!({})).await.unwrap_err();
+    match err {
+        RadiologyError::Rejected { code, message } => {
+            assert_eq!(code, None);
+            assert_eq!(message, "model is overloaded");
+        }
+        other => panic!("expected a rejection, got {:?}", other),
+    }
+}
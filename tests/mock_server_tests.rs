@@ -0,0 +1,124 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use mcp::mock::{MockServer, RpcError, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR, SERVER_NOT_INITIALIZED};
+use mcp::{McpClient, RadiologyError};
+
+type RawSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Speak to the server frame by frame, without the client's framing
+async fn raw_connect(server: &MockServer) -> RawSocket {
+    connect_async(server.url()).await.expect("Failed to connect").0
+}
+
+async fn exchange(socket: &mut RawSocket, frame: &str) -> Value {
+    socket.send(Message::Text(frame.to_string())).await.unwrap();
+    let reply = socket.next().await.unwrap().unwrap();
+    serde_json::from_str(reply.to_text().unwrap()).unwrap()
+}
+
+async fn initialize(socket: &mut RawSocket) {
+    exchange(socket, r#"{"jsonrpc":"2.0","id":0,"method":"initialize","params":{"protocolVersion":"2025-06-18"}}"#).await;
+}
+
+#[tokio::test]
+async fn test_framing_errors_use_json_rpc_codes() {
+    let server = MockServer::builder().start().await.unwrap();
+    let mut socket = raw_connect(&server).await;
+
+    let reply = exchange(&mut socket, "{not json").await;
+    assert_eq!(reply["error"]["code"], PARSE_ERROR);
+    assert_eq!(reply["id"], Value::Null);
+
+    let reply = exchange(&mut socket, r#"{"jsonrpc":"1.0","id":7,"method":"ping"}"#).await;
+    assert_eq!(reply["error"]["code"], INVALID_REQUEST);
+    assert_eq!(reply["id"], 7);
+
+    let reply = exchange(&mut socket, r#"{"jsonrpc":"2.0","id":"abc","method":"tools/list"}"#).await;
+    assert_eq!(reply["error"]["code"], SERVER_NOT_INITIALIZED);
+    assert_eq!(reply["id"], "abc");
+
+    initialize(&mut socket).await;
+    let reply = exchange(&mut socket, r#"{"jsonrpc":"2.0","id":"abc","method":"sampling/createMessage"}"#).await;
+    assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_batches_answer_requests_but_not_notifications() {
+    let server = MockServer::builder().start().await.unwrap();
+    let mut socket = raw_connect(&server).await;
+    initialize(&mut socket).await;
+
+    let reply = exchange(
+        &mut socket,
+        r#"[{"jsonrpc":"2.0","id":1,"method":"ping"},
+            {"jsonrpc":"2.0","method":"notifications/initialized"},
+            {"jsonrpc":"2.0","id":2,"method":"tools/list"}]"#,
+    )
+    .await;
+
+    let replies = reply.as_array().unwrap();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["id"], 1);
+    assert_eq!(replies[1]["result"]["tools"], json!([]));
+    assert_eq!(server.requests_for("notifications/initialized").len(), 1);
+}
+
+#[tokio::test]
+async fn test_protocol_version_is_negotiated() {
+    let server = MockServer::builder().server_info("radiology-backend", "2.0.0").start().await.unwrap();
+    let mut socket = raw_connect(&server).await;
+
+    let older = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}"#;
+    let reply = exchange(&mut socket, older).await;
+    assert_eq!(reply["result"]["protocolVersion"], "2024-11-05");
+    assert_eq!(reply["result"]["serverInfo"]["name"], "radiology-backend");
+
+    let unknown = r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{"protocolVersion":"1999-01-01"}}"#;
+    let reply = exchange(&mut socket, unknown).await;
+    assert_eq!(reply["result"]["protocolVersion"], mcp::protocol::LATEST_PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn test_scripted_tools_resources_and_prompts() {
+    let server = MockServer::builder()
+        .tool_response("version", json!({ "version": "1.0" }))
+        .tool("measure", |arguments| match arguments["mm"].as_f64() {
+            Some(mm) => Ok(json!({ "cm": mm / 10.0 })),
+            None => Err("mm is required".to_string()),
+        })
+        .resource("studies://recent", "Recent studies", "application/json")
+        .prompt("chest_ct", "Chest CT read", &["clinical_indication"])
+        .method("images/upload", |_| Err(RpcError::new(-32000, "uploads disabled")))
+        .start()
+        .await
+        .unwrap();
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    let names: Vec<_> = client.list_tools().await.unwrap().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, ["version", "measure"]);
+
+    let result = client.call_tool("measure", json!({ "mm": 42.0 })).await.unwrap();
+    assert_eq!(result.structured(), Some(json!({ "cm": 4.2 })));
+    assert_eq!(client.call_tool("version", json!({})).await.unwrap().structured(), Some(json!({ "version": "1.0" })));
+
+    let err = client.call_tool("measure", json!({})).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: None, ref message } if message == "mm is required"));
+    let err = client.call_tool("segment", json!({})).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: Some(INVALID_PARAMS), .. }));
+
+    let resources = client.request("resources/list", None).await.unwrap();
+    assert_eq!(resources["resources"][0]["uri"], "studies://recent");
+    let prompts = client.request("prompts/list", None).await.unwrap();
+    assert_eq!(prompts["prompts"][0]["arguments"][0]["name"], "clinical_indication");
+
+    let err = client.request("images/upload", Some(json!({}))).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: Some(-32000), .. }));
+
+    let calls = server.requests_for("tools/call");
+    assert_eq!(calls.len(), 4);
+    assert_eq!(calls[0]["params"]["arguments"]["mm"], 42.0);
+}
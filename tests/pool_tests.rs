@@ -0,0 +1,251 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use mcp::connect::ReconnectPolicy;
+use mcp::mock::{Fault, FaultRule, MockServer, MockServerBuilder, RpcError};
+use mcp::pool::{Balance, BackendPool, HealthPolicy};
+use mcp::{McpClient, RadiologyCluster, RadiologyError};
+
+mod common;
+use common::{connect, image, wait_for};
+
+fn analysis_server() -> MockServerBuilder {
+    MockServer::builder().tool_response(
+        "analyze_image",
+        json!({ "status": "success", "findings": "Normal", "confidence": 0.9 }),
+    )
+}
+
+async fn start(count: usize) -> Vec<MockServer> {
+    let mut servers = Vec::new();
+    for _ in 0..count {
+        servers.push(analysis_server().start().await.unwrap());
+    }
+    servers
+}
+
+async fn pool_of(name: &str, balance: Balance, servers: &[MockServer], weights: &[u32]) -> BackendPool {
+    let mut pool = BackendPool::new(name, balance);
+    for (i, server) in servers.iter().enumerate() {
+        let weight = weights.get(i).copied().unwrap_or(1);
+        pool = pool.weighted_member(&format!("{}-{}", name, i), connect(server).await, weight);
+    }
+    pool
+}
+
+async fn cluster_with(pool: BackendPool) -> RadiologyCluster {
+    let spare = analysis_server().start().await.unwrap();
+    let cluster = RadiologyCluster::new(connect(&spare).await);
+    let name = pool.name().to_string();
+    cluster.register_pool(pool);
+    cluster.initialize_context_on("ct", "ct-model", &name).await.unwrap();
+    cluster
+}
+
+fn calls(servers: &[MockServer]) -> Vec<usize> {
+    servers.iter().map(|server| server.requests_for("tools/call").len()).collect()
+}
+
+#[tokio::test]
+async fn test_round_robin_spreads_submissions_evenly() {
+    let servers = start(3).await;
+    let cluster = cluster_with(pool_of("ct", Balance::RoundRobin, &servers, &[]).await).await;
+
+    for i in 0..6 {
+        cluster.submit_image("ct", image(&format!("IMG{}", i))).await.unwrap();
+    }
+    assert_eq!(calls(&servers), vec![2, 2, 2]);
+}
+
+#[tokio::test]
+async fn test_weighted_balance_follows_weights() {
+    let servers = start(2).await;
+    let cluster = cluster_with(pool_of("ct", Balance::Weighted, &servers, &[1, 3]).await).await;
+
+    for i in 0..8 {
+        cluster.submit_image("ct", image(&format!("IMG{}", i))).await.unwrap();
+    }
+    assert_eq!(calls(&servers), vec![2, 6]);
+}
+
+#[tokio::test]
+async fn test_least_outstanding_prefers_idle_members() {
+    let servers = start(2).await;
+    let pool = Arc::new(pool_of("ct", Balance::LeastOutstanding, &servers, &[]).await);
+
+    let first = pool.acquire().unwrap();
+    let second = pool.acquire().unwrap();
+    assert_ne!(first.backend().name(), second.backend().name());
+
+    // With one member busy, new work goes to the idle one however many times it's asked
+    let busy = first.backend().name().to_string();
+    drop(second);
+    for _ in 0..3 {
+        let lease = pool.acquire().unwrap();
+        assert_ne!(lease.backend().name(), busy);
+    }
+    let outstanding: Vec<usize> = pool.status().iter().map(|member| member.outstanding).collect();
+    assert_eq!(outstanding.iter().sum::<usize>(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_submissions_use_every_member() {
+    let servers = start(3).await;
+    let cluster = Arc::new(cluster_with(pool_of("ct", Balance::LeastOutstanding, &servers, &[]).await).await);
+
+    let mut handles = Vec::new();
+    for i in 0..60 {
+        let cluster = cluster.clone();
+        handles.push(tokio::spawn(async move { cluster.submit_image("ct", image(&format!("IMG{}", i))).await }));
+    }
+    for handle in handles {
+        handle.await.unwrap().unwrap();
+    }
+
+    let calls = calls(&servers);
+    assert_eq!(calls.iter().sum::<usize>(), 60);
+    assert!(calls.iter().all(|&n| n > 0), "unbalanced: {:?}", calls);
+    let pool = cluster.backends().get("ct").unwrap();
+    assert!(pool.status().iter().all(|member| member.outstanding == 0));
+}
+
+#[tokio::test]
+async fn test_failing_member_is_ejected() {
+    let healthy = analysis_server().start().await.unwrap();
+    let failing = analysis_server()
+        .fault(FaultRule::on("tools/call", Fault::Error(RpcError::new(-32603, "model crashed"))))
+        .start()
+        .await
+        .unwrap();
+    let health = HealthPolicy {
+        interval: Duration::from_secs(60),
+        timeout: Duration::from_secs(1),
+        failure_threshold: 2,
+    };
+    let pool = BackendPool::new("ct", Balance::RoundRobin)
+        .member("healthy", connect(&healthy).await)
+        .member("failing", connect(&failing).await)
+        .health_checks(health);
+    let cluster = cluster_with(pool).await;
+
+    let mut failures = 0;
+    for i in 0..10 {
+        if cluster.submit_image("ct", image(&format!("IMG{}", i))).await.is_err() {
+            failures += 1;
+        }
+    }
+    assert_eq!(failures, 2);
+    assert_eq!(failing.requests_for("tools/call").len(), 2);
+
+    let status = cluster.backends().get("ct").unwrap().status();
+    assert!(status[0].healthy);
+    assert!(!status[1].healthy);
+}
+
+#[tokio::test]
+async fn test_unhealthy_member_is_readmitted_after_recovering() {
+    // Reserve a port the restarted server can bind again
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+
+    let steady = analysis_server().start().await.unwrap();
+    let flaky = analysis_server().bind(&addr).start().await.unwrap();
+    let reconnect = ReconnectPolicy {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(20),
+        jitter: false,
+        max_attempts: None,
+        ..ReconnectPolicy::default()
+    };
+    let flaky_client = Arc::new(McpClient::connect_with(&flaky.url(), Some(reconnect)).await.unwrap());
+    let health = HealthPolicy {
+        interval: Duration::from_millis(30),
+        timeout: Duration::from_millis(30),
+        failure_threshold: 1,
+    };
+    let pool = BackendPool::new("ct", Balance::RoundRobin)
+        .member("steady", connect(&steady).await)
+        .member("flaky", flaky_client)
+        .health_checks(health);
+    let cluster = cluster_with(pool).await;
+    let pool = cluster.backends().get("ct").unwrap();
+
+    drop(flaky);
+    wait_for(|| !pool.status()[1].healthy).await;
+
+    // While it's out every submission goes to the steady member
+    for i in 0..4 {
+        cluster.submit_image("ct", image(&format!("IMG{}", i))).await.unwrap();
+    }
+    assert_eq!(steady.requests_for("tools/call").len(), 4);
+
+    let restarted = analysis_server().bind(&addr).start().await.unwrap();
+    wait_for(|| pool.status()[1].healthy).await;
+    for i in 0..4 {
+        cluster.submit_image("ct", image(&format!("IMG{}", i))).await.unwrap();
+    }
+    assert_eq!(restarted.requests_for("tools/call").len(), 2);
+}
+
+#[test]
+fn test_pool_registered_outside_a_runtime_checks_health_once_used() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (cluster, pool, steady, flaky) = runtime.block_on(async {
+        let steady = analysis_server().start().await.unwrap();
+        let flaky = analysis_server()
+            .fault(FaultRule::on("tools/call", Fault::Error(RpcError::new(-32603, "model crashed"))).times(1))
+            .start()
+            .await
+            .unwrap();
+        let pool = BackendPool::new("ct", Balance::RoundRobin)
+            .member("flaky", connect(&flaky).await)
+            .member("steady", connect(&steady).await)
+            .health_checks(HealthPolicy {
+                interval: Duration::from_millis(20),
+                timeout: Duration::from_millis(50),
+                failure_threshold: 1,
+            });
+        (RadiologyCluster::new(connect(&steady).await), pool, steady, flaky)
+    });
+    // Registered from synchronous setup code, where no health check can start yet
+    cluster.register_pool(pool);
+
+    runtime.block_on(async {
+        cluster.initialize_context_on("ct", "ct-model", "ct").await.unwrap();
+        assert!(cluster.submit_image("ct", image("IMG0")).await.is_err());
+
+        // The failure ejected the member, and the check started on first use readmits it
+        let pool = cluster.backends().get("ct").unwrap();
+        wait_for(|| pool.status()[0].healthy).await;
+        for i in 1..5 {
+            cluster.submit_image("ct", image(&format!("IMG{}", i))).await.unwrap();
+        }
+        assert_eq!(flaky.requests_for("tools/call").len(), 3);
+        assert_eq!(steady.requests_for("tools/call").len(), 2);
+    });
+}
+
+#[tokio::test]
+async fn test_pool_without_healthy_members_is_unavailable() {
+    let server = analysis_server().start().await.unwrap();
+    let health = HealthPolicy {
+        interval: Duration::from_millis(20),
+        timeout: Duration::from_millis(20),
+        failure_threshold: 1,
+    };
+    let pool = BackendPool::new("ct", Balance::RoundRobin)
+        .member("only", connect(&server).await)
+        .health_checks(health);
+    let cluster = cluster_with(pool).await;
+    let pool = cluster.backends().get("ct").unwrap();
+
+    drop(server);
+    wait_for(|| !pool.status()[0].healthy).await;
+    let err = cluster.submit_image("ct", image("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Unavailable(ref name) if name == "ct"));
+    assert!(err.is_retryable());
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::json;
+
+use mcp::context::ContextConfig;
+use mcp::deid::{DeidPolicy, FieldAction};
+use mcp::mock::{MockServer, MockServerBuilder};
+use mcp::prompt::{PromptError, PromptLibrary, PromptSource, PromptTemplate};
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
+
+fn analysis_server() -> MockServerBuilder {
+    MockServer::builder().tool_response(
+        "analyze_image",
+        json!({ "status": "success", "findings": "Normal", "confidence": 0.9 }),
+    )
+}
+
+// The default de-identification policy drops fields it doesn't list, so allow the indication
+async fn cluster_for(server: &MockServer) -> RadiologyCluster {
+    let client = McpClient::connect(&server.url()).await.unwrap();
+    let policy = DeidPolicy::default().field("clinical_indication", FieldAction::Keep);
+    RadiologyCluster::new(Arc::new(client)).with_deid_policy(policy)
+}
+
+fn chest_ct(id: &str) -> RadiologyImage {
+    let metadata = HashMap::from([
+        ("modality".to_string(), "CT".to_string()),
+        ("body_part".to_string(), "CHEST".to_string()),
+        ("clinical_indication".to_string(), "Persistent cough".to_string()),
+    ]);
+    RadiologyImage {
+        image_id: id.to_string(),
+        data: vec![1, 2, 3],
+        metadata,
+        dicom: None,
+    }
+}
+
+fn variables(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+// The prompt of the nth analysis the server received
+fn sent_prompt(server: &MockServer, n: usize) -> String {
+    server.requests_for("tools/call")[n]["params"]["arguments"]["prompt"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_templates_render_variables_and_sections() {
+    let template = PromptTemplate::parse(
+        "chest",
+        1,
+        "Read this {modality} of the {body_part}.{#prior_findings} Compare with: {prior_findings}.{/prior_findings} Reply as {{\"findings\": ...}}",
+    )
+    .unwrap();
+    assert_eq!(
+        template.variables().into_iter().collect::<Vec<_>>(),
+        vec!["body_part", "modality", "prior_findings"]
+    );
+
+    let first_read = template.render(&variables(&[("modality", "CT"), ("body_part", "chest")]));
+    assert_eq!(first_read, r#"Read this CT of the chest. Reply as {"findings": ...}"#);
+
+    let follow_up = template.render(&variables(&[("modality", "CT"), ("prior_findings", "5 mm nodule")]));
+    assert_eq!(follow_up, r#"Read this CT of the . Compare with: 5 mm nodule. Reply as {"findings": ...}"#);
+}
+
+#[test]
+fn test_malformed_templates_are_rejected() {
+    for text in [
+        "{#prior_findings}never closed",
+        "{#a}{/b}",
+        "{/a}",
+        "stray }",
+        "{unclosed",
+        "{not a name}",
+    ] {
+        let err = PromptTemplate::parse("bad", 1, text).unwrap_err();
+        assert!(matches!(err, PromptError::Syntax(_)), "{}: {:?}", text, err);
+    }
+}
+
+#[test]
+fn test_library_loads_versioned_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("chest-ct.v1.txt"), "v1 {modality}").unwrap();
+    std::fs::write(dir.path().join("chest-ct.v2.txt"), "v2 {modality}").unwrap();
+    std::fs::write(dir.path().join("head-mr.v10.txt"), "head {body_part}").unwrap();
+    std::fs::write(dir.path().join("README.md"), "not a template").unwrap();
+
+    let library = PromptLibrary::new();
+    assert_eq!(library.load_dir(dir.path()).unwrap(), 3);
+    assert_eq!(library.names(), vec!["chest-ct", "head-mr"]);
+    assert_eq!(library.versions("chest-ct"), vec![1, 2]);
+    assert_eq!(library.get("chest-ct", None).unwrap().text(), "v2 {modality}");
+    assert_eq!(library.get("chest-ct", Some(1)).unwrap().text(), "v1 {modality}");
+    assert!(matches!(library.get("chest-ct", Some(3)), Err(PromptError::NotFound { version: Some(3), .. })));
+
+    let unversioned = dir.path().join("chest-ct.txt");
+    std::fs::write(&unversioned, "no version").unwrap();
+    assert!(matches!(library.load_file(&unversioned), Err(PromptError::FileName(_))));
+}
+
+#[tokio::test]
+async fn test_contexts_use_library_templates() {
+    let server = analysis_server().start().await.unwrap();
+    let cluster = cluster_for(&server).await;
+    cluster.prompts().insert(PromptTemplate::parse("chest-ct", 1, "v1: {modality} {body_part}, {clinical_indication}").unwrap());
+
+    let latest = PromptSource::Library {
+        name: "chest-ct".to_string(),
+        version: None,
+    };
+    let pinned = PromptSource::Library {
+        name: "chest-ct".to_string(),
+        version: Some(1),
+    };
+    cluster.create_context("latest", ContextConfig::new("ct-model").prompt(latest)).unwrap();
+    cluster.create_context("pinned", ContextConfig::new("ct-model").prompt(pinned)).unwrap();
+
+    cluster.submit_image("latest", chest_ct("IMG1")).await.unwrap();
+    cluster.prompts().insert(PromptTemplate::parse("chest-ct", 2, "v2: {modality}").unwrap());
+    cluster.submit_image("latest", chest_ct("IMG2")).await.unwrap();
+    cluster.submit_image("pinned", chest_ct("IMG3")).await.unwrap();
+
+    assert_eq!(sent_prompt(&server, 0), "v1: CT CHEST, Persistent cough");
+    assert_eq!(sent_prompt(&server, 1), "v2: CT");
+    assert_eq!(sent_prompt(&server, 2), "v1: CT CHEST, Persistent cough");
+}
+
+#[tokio::test]
+async fn test_contexts_can_use_server_prompts() {
+    let server = analysis_server()
+        .prompt("chest_ct", "Chest CT read", &["modality", "clinical_indication"])
+        .prompt_text("chest_ct", "Server read of a {modality}: {clinical_indication}")
+        .start()
+        .await
+        .unwrap();
+    let cluster = cluster_for(&server).await;
+    let source = PromptSource::Server {
+        name: "chest_ct".to_string(),
+    };
+    cluster.create_context("ct", ContextConfig::new("ct-model").prompt(source)).unwrap();
+
+    cluster.submit_image("ct", chest_ct("IMG1")).await.unwrap();
+    assert_eq!(sent_prompt(&server, 0), "Server read of a CT: Persistent cough");
+    let get = &server.requests_for("prompts/get")[0]["params"];
+    assert_eq!(get["name"], "chest_ct");
+    assert_eq!(get["arguments"]["body_part"], "CHEST");
+
+    let client = McpClient::connect(&server.url()).await.unwrap();
+    let prompts = client.list_prompts().await.unwrap();
+    assert_eq!(prompts[0].name, "chest_ct");
+    assert_eq!(prompts[0].arguments.len(), 2);
+    let err = client.get_prompt("missing", &HashMap::new()).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { .. }), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_prompt_errors_surface() {
+    let server = analysis_server().start().await.unwrap();
+    let cluster = cluster_for(&server).await;
+
+    let err = cluster.create_context("ct", ContextConfig::new("ct-model").prompt_template("{#oops}")).unwrap_err();
+    assert!(matches!(err, RadiologyError::Prompt(PromptError::Syntax(_))));
+
+    let missing = PromptSource::Library {
+        name: "missing".to_string(),
+        version: None,
+    };
+    cluster.create_context("ct", ContextConfig::new("ct-model").prompt(missing)).unwrap();
+    let err = cluster.submit_image("ct", chest_ct("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Prompt(PromptError::NotFound { .. })));
+    assert!(server.requests_for("tools/call").is_empty());
+}
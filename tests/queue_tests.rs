@@ -0,0 +1,207 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp::context::ContextConfig;
+use mcp::mock::MockServer;
+use mcp::queue::{JobId, JobQueue, JobStatus, Overflow, Priority, QueueConfig};
+use mcp::store::MemoryResultStore;
+use mcp::{RadiologyCluster, RadiologyError};
+
+mod common;
+use common::{called_ids, connect, ct_cluster, image, slow_server, wait_for};
+
+async fn cluster_for(server: &MockServer) -> Arc<RadiologyCluster> {
+    let cluster = ct_cluster(server, Arc::new(MemoryResultStore::new())).await;
+    cluster.initialize_context("mri", "mri-model").await.unwrap();
+    cluster
+}
+
+fn running(queue: &JobQueue, jobs: &[JobId]) -> usize {
+    jobs.iter().filter(|&&job| queue.status(job) == Some(JobStatus::Running)).count()
+}
+
+#[tokio::test]
+async fn test_jobs_complete_and_report_status() {
+    let server = slow_server(Duration::from_millis(10)).await;
+    let queue = JobQueue::new(cluster_for(&server).await, QueueConfig::new());
+
+    let mut jobs = Vec::new();
+    for i in 0..5 {
+        jobs.push(queue.submit("ct", image(&format!("IMG{}", i)), Priority::Routine).await.unwrap());
+    }
+    for (i, job) in jobs.iter().enumerate() {
+        match queue.wait(*job).await {
+            Some(JobStatus::Completed(result)) => assert_eq!(result.findings, format!("Findings for IMG{}", i)),
+            other => panic!("unexpected status {:?}", other),
+        }
+        assert!(queue.status(*job).unwrap().is_finished());
+    }
+    assert_eq!(queue.status(JobId(999)), None);
+    assert_eq!(queue.queued(), 0);
+    assert_eq!(queue.running(), 0);
+}
+
+#[tokio::test]
+async fn test_stat_jobs_jump_the_queue() {
+    let server = slow_server(Duration::from_millis(50)).await;
+    let queue = JobQueue::new(cluster_for(&server).await, QueueConfig::new().max_concurrent(1));
+
+    let mut jobs = Vec::new();
+    for id in ["R1", "R2", "R3"] {
+        jobs.push(queue.submit("ct", image(id), Priority::Routine).await.unwrap());
+    }
+    jobs.push(queue.submit("ct", image("STAT1"), Priority::Stat).await.unwrap());
+    for job in jobs {
+        queue.wait(job).await;
+    }
+
+    // R1 was already running when the STAT read arrived
+    assert_eq!(called_ids(&server), vec!["R1", "STAT1", "R2", "R3"]);
+}
+
+#[tokio::test]
+async fn test_context_limit_does_not_block_other_contexts() {
+    let server = slow_server(Duration::from_millis(200)).await;
+    let config = QueueConfig::new().context_limit("ct", 1);
+    let queue = JobQueue::new(cluster_for(&server).await, config);
+
+    let mut ct = Vec::new();
+    let mut mri = Vec::new();
+    for i in 0..3 {
+        ct.push(queue.submit("ct", image(&format!("CT{}", i)), Priority::Routine).await.unwrap());
+    }
+    for i in 0..3 {
+        mri.push(queue.submit("mri", image(&format!("MR{}", i)), Priority::Routine).await.unwrap());
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(running(&queue, &ct), 1);
+    assert_eq!(running(&queue, &mri), 3);
+    assert_eq!(queue.queued(), 2);
+
+    for job in ct.into_iter().chain(mri) {
+        assert!(matches!(queue.wait(job).await, Some(JobStatus::Completed(_))));
+    }
+}
+
+#[tokio::test]
+async fn test_backend_limit_spans_contexts() {
+    let server = slow_server(Duration::from_millis(200)).await;
+    let config = QueueConfig::new().default_backend_limit(2);
+    let queue = JobQueue::new(cluster_for(&server).await, config);
+
+    let mut jobs = Vec::new();
+    for i in 0..3 {
+        jobs.push(queue.submit("ct", image(&format!("CT{}", i)), Priority::Routine).await.unwrap());
+        jobs.push(queue.submit("mri", image(&format!("MR{}", i)), Priority::Routine).await.unwrap());
+    }
+
+    // Both contexts share the default backend
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(running(&queue, &jobs), 2);
+    for job in jobs {
+        queue.wait(job).await;
+    }
+}
+
+#[tokio::test]
+async fn test_queued_jobs_follow_their_context_to_a_new_backend() {
+    let server = slow_server(Duration::from_millis(300)).await;
+    let other = slow_server(Duration::from_millis(300)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.register_backend("other", connect(&other).await);
+    let queue = JobQueue::new(cluster.clone(), QueueConfig::new().default_backend_limit(1));
+
+    let first = queue.submit("ct", image("IMG0"), Priority::Routine).await.unwrap();
+    let second = queue.submit("ct", image("IMG1"), Priority::Routine).await.unwrap();
+    assert_eq!(running(&queue, &[first, second]), 1);
+    wait_for(|| called_ids(&server) == ["IMG0"]).await;
+
+    // Once the context moves, its waiting job counts against the new backend and starts there
+    cluster.update_context("ct", ContextConfig::new("ct-model").backend("other")).unwrap();
+    queue.resume();
+    assert_eq!(running(&queue, &[first, second]), 2);
+    assert!(matches!(queue.wait(second).await, Some(JobStatus::Completed(_))));
+    assert_eq!(called_ids(&other), vec!["IMG1"]);
+    queue.wait(first).await;
+}
+
+#[tokio::test]
+async fn test_full_queue_rejects() {
+    let server = slow_server(Duration::from_millis(200)).await;
+    let config = QueueConfig::new().max_concurrent(1).capacity(2).overflow(Overflow::Reject);
+    let queue = JobQueue::new(cluster_for(&server).await, config);
+
+    // One running, two waiting
+    for i in 0..3 {
+        queue.submit("ct", image(&format!("IMG{}", i)), Priority::Routine).await.unwrap();
+    }
+    let err = queue.submit("ct", image("IMG3"), Priority::Stat).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::QueueFull { capacity: 2 }));
+    assert!(queue.try_submit("ct", image("IMG4"), Priority::Routine).await.is_err());
+}
+
+#[tokio::test]
+async fn test_full_queue_applies_backpressure() {
+    let server = slow_server(Duration::from_millis(100)).await;
+    let config = QueueConfig::new().max_concurrent(1).capacity(1);
+    let queue = JobQueue::new(cluster_for(&server).await, config);
+
+    queue.submit("ct", image("IMG0"), Priority::Routine).await.unwrap();
+    queue.submit("ct", image("IMG1"), Priority::Routine).await.unwrap();
+
+    // No space until IMG0 finishes and IMG1 starts
+    let blocked = tokio::time::timeout(Duration::from_millis(30), queue.submit("ct", image("IMG2"), Priority::Routine)).await;
+    assert!(blocked.is_err());
+    assert!(matches!(queue.try_submit("ct", image("IMG2"), Priority::Routine).await, Err(RadiologyError::QueueFull { .. })));
+
+    let job = tokio::time::timeout(Duration::from_secs(2), queue.submit("ct", image("IMG2"), Priority::Routine))
+        .await
+        .expect("submission never got space")
+        .unwrap();
+    assert!(matches!(queue.wait(job).await, Some(JobStatus::Completed(_))));
+    assert_eq!(called_ids(&server), vec!["IMG0", "IMG1", "IMG2"]);
+}
+
+#[tokio::test]
+async fn test_failed_jobs_report_the_error() {
+    let server = MockServer::builder()
+        .tool("analyze_image", |_| Err("model unavailable".to_string()))
+        .start()
+        .await
+        .unwrap();
+    let queue = JobQueue::new(cluster_for(&server).await, QueueConfig::new());
+
+    let job = queue.submit("ct", image("IMG1"), Priority::Routine).await.unwrap();
+    match queue.wait(job).await {
+        Some(JobStatus::Failed(message)) => assert!(message.contains("model unavailable"), "{}", message),
+        other => panic!("unexpected status {:?}", other),
+    }
+
+    let err = queue.submit("missing", image("IMG2"), Priority::Routine).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(_)));
+}
+
+#[tokio::test]
+async fn test_only_recent_finished_statuses_are_kept() {
+    let server = slow_server(Duration::from_millis(20)).await;
+    let queue = JobQueue::new(cluster_for(&server).await, QueueConfig::new().max_concurrent(1).retain_finished(2));
+
+    let mut jobs = Vec::new();
+    for i in 0..4 {
+        jobs.push(queue.submit("ct", image(&format!("IMG{}", i)), Priority::Routine).await.unwrap());
+    }
+    // A waiter registered before the job was pruned still gets its status
+    let (first, _) = tokio::join!(queue.wait(jobs[0]), async {
+        for job in &jobs[1..] {
+            assert!(queue.wait(*job).await.unwrap().is_finished());
+        }
+    });
+    assert!(matches!(first, Some(JobStatus::Completed(_))));
+
+    assert_eq!(queue.status(jobs[0]), None);
+    assert_eq!(queue.status(jobs[1]), None);
+    assert!(queue.status(jobs[2]).unwrap().is_finished());
+    assert!(queue.status(jobs[3]).unwrap().is_finished());
+    assert_eq!(queue.wait(jobs[0]).await, None);
+}
@@ -1,132 +1,38 @@
 use std::sync::Arc;
 use std::collections::HashMap;
-use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
-use tokio::sync::oneshot;
-use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::accept_async;
 
-// Import your application's types
-// Note: We need to make them public in main.rs or create them again here
-#[derive(Clone, Serialize, Deserialize)]
-struct RadiologyImage {
-    image_id: String,
-    data: Vec<u8>,
-    metadata: HashMap<String, String>,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-struct RadiologyResult {
-    image_id: String,
-    findings: String,
-    confidence_score: f32,
-    analysis_date: String,
-}
+use mcp::connect::{connect_with_retry, Connector};
+use mcp::mock::{Latency, MockServer};
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
 
 // Helper function to start a test server
-async fn start_test_server() -> (String, oneshot::Sender<()>) {
-    // Find an available port
-    let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
-    let addr = listener.local_addr().expect("Failed to get local address");
-    let server_url = format!("ws://{}", addr);
-    
-    // Channel to signal server shutdown
-    let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
-    
-    // Spawn the test server
-    tokio::spawn(async move {
-        println!("Test server running on {}", addr);
-        
-        tokio::select! {
-            _ = async {
-                while let Ok((stream, _)) = listener.accept().await {
-                    let peer = stream.peer_addr().expect("Failed to get peer address");
-                    println!("Connection from {}", peer);
-                    
-                    tokio::spawn(async move {
-                        let mut ws_stream = accept_async(stream).await
-                            .expect("Failed to accept WebSocket");
-                        
-                        while let Some(Ok(msg)) = ws_stream.next().await {
-                            if msg.is_text() || msg.is_binary() {
-                                println!("Received: {}", msg);
-                                
-                                // Mock response
-                                let response = serde_json::json!({
-                                    "status": "success",
-                                    "findings": "Test findings: Normal scan results",
-                                    "confidence": 0.95,
-                                    "analysis_date": "2023-01-15T14:30:00Z"
-                                });
-                                
-                                ws_stream.send(Message::Text(response.to_string())).await
-                                    .expect("Failed to send response");
-                            }
-                        }
-                    });
-                }
-            } => {},
-            _ = shutdown_rx => {
-                println!("Test server shutting down");
-            }
-        }
-    });
-    
-    (server_url, shutdown_tx)
+async fn start_test_server() -> MockServer {
+    MockServer::builder()
+        .tool_response("analyze_image", serde_json::json!({
+            "status": "success",
+            "findings": "Test findings: Normal scan results",
+            "confidence": 0.95,
+            "analysis_date": "2023-01-15T14:30:00Z"
+        }))
+        .start()
+        .await
+        .expect("Failed to start test server")
 }
 
 #[tokio::test]
 async fn test_radiology_cluster_initialization() {
     // Start a test server
-    let (server_url, _shutdown) = start_test_server().await;
-    
-    // Import the client from your main application
-    // For testing, we'll simulate the client behavior
-    use mcp_rust_sdk::client::Client;
-    use mcp_rust_sdk::transport::websocket::WebSocketTransport;
+    let server = start_test_server().await;
     
     // Connect to the test server
-    let transport = WebSocketTransport::new(&server_url).await
+    let client = McpClient::connect(&server.url()).await
         .expect("Failed to connect to test server");
     
-    // Create a client
-    let client = Arc::new(Client::new(Arc::new(transport)));
-    
-    // Define RadiologyCluster for testing
-    struct RadiologyCluster {
-        client: Arc<Client>,
-        contexts: std::sync::Mutex<HashMap<String, String>>,
-    }
-    
-    impl RadiologyCluster {
-        fn new(client: Arc<Client>) -> Self {
-            RadiologyCluster {
-                client,
-                contexts: std::sync::Mutex::new(HashMap::new()),
-            }
-        }
-        
-        async fn initialize_context(&self, context_id: &str, model_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-            self.contexts.lock().unwrap().insert(context_id.to_string(), model_name.to_string());
-            Ok(())
-        }
-        
-        async fn submit_image(&self, context_id: &str, image: RadiologyImage) -> Result<String, Box<dyn std::error::Error>> {
-            let message = serde_json::json!({
-                "image_id": image.image_id,
-                "metadata": image.metadata
-            }).to_string();
-            
-            let options: Option<Value> = None;
-            let response = self.client.request(&message, options).await?;
-            
-            Ok(response.to_string())
-        }
-    }
-    
     // Create the RadiologyCluster
-    let cluster = RadiologyCluster::new(client);
+    let cluster = RadiologyCluster::new(Arc::new(client));
     
     // Test context initialization
     let result = cluster.initialize_context("test-context", "test-model").await;
@@ -141,53 +47,140 @@ async fn test_radiology_cluster_initialization() {
         image_id: "TEST001".to_string(),
         data: vec![0, 1, 2, 3],
         metadata,
+        dicom: None,
     };
     
     let response = cluster.submit_image("test-context", test_image).await;
     assert!(response.is_ok(), "Failed to submit image");
     
-    let response_text = response.unwrap();
-    println!("Response: {}", response_text);
-    
-    // Verify response contains expected fields
-    let response_json: Value = serde_json::from_str(&response_text).expect("Failed to parse response");
-    assert!(response_json.get("status").is_some(), "Response missing status field");
-    assert!(response_json.get("findings").is_some(), "Response missing findings field");
+    // Verify the decoded result carries the server's findings
+    let result = response.unwrap();
+    assert_eq!(result.image_id, "TEST001");
+    assert_eq!(result.findings, "Test findings: Normal scan results");
 }
 
 #[tokio::test]
 async fn test_connection_retry_logic() {
-    // Test the retry logic by attempting to connect to a non-existent server first
-    use std::time::Duration;
-    
-    async fn connect_with_retry(url: &str, max_retries: u32, delay: Duration) -> Result<String, String> {
-        let mut attempts = 0;
-        
-        loop {
-            attempts += 1;
-            println!("Connection attempt {}/{}", attempts, max_retries);
-            
-            // Simulate connection attempt
-            if url.contains("nonexistent") && attempts < max_retries {
-                // Simulate failure for non-existent server
-                if attempts >= max_retries {
-                    return Err(format!("Failed to connect after {} attempts", max_retries));
-                }
-                
-                println!("Connection failed. Retrying in {:?}...", delay);
-                tokio::time::sleep(delay).await;
-            } else {
-                // Simulate success on final attempt or for valid URL
-                return Ok("Connected".to_string());
-            }
-        }
-    }
-    
-    // Test with non-existent server (should retry then fail)
-    let bad_result = connect_with_retry("ws://nonexistent:1234", 2, Duration::from_millis(100)).await;
-    assert!(bad_result.is_ok(), "Expected successful retry");
+    // Grab a free port, then release it so nothing is listening there
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let addr = listener.local_addr().expect("Failed to get local address");
+    drop(listener);
+    let url = format!("ws://{}", addr);
+    
+    // With no server, every attempt fails and the last error is returned
+    let started = Instant::now();
+    let bad_result = connect_with_retry(&url, 3, Duration::from_millis(50)).await;
+    assert!(matches!(bad_result, Err(RadiologyError::Transport(_))), "Expected connection failure");
+    assert!(started.elapsed() >= Duration::from_millis(100), "Expected a delay between attempts");
+    
+    // A server that comes up while we are retrying is picked up by a later attempt
+    let connector = Connector::new().max_retries(10).delay(Duration::from_millis(50));
+    let late_server = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let listener = TcpListener::bind(addr).await.expect("Failed to rebind");
+        let (stream, _) = listener.accept().await.expect("Failed to accept");
+        let _ws_stream = accept_async(stream).await.expect("Failed to accept WebSocket");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    });
     
-    // Test with immediate success
-    let good_result = connect_with_retry("ws://localhost:8080", 3, Duration::from_millis(100)).await;
+    let good_result = connector.connect(&url).await;
     assert!(good_result.is_ok(), "Expected successful connection");
+    late_server.await.unwrap();
+}
+#[tokio::test]
+async fn test_get_results_returns_submitted_analyses() {
+    let server = start_test_server().await;
+    let client = McpClient::connect(&server.url()).await
+        .expect("Failed to connect to test server");
+    let cluster = RadiologyCluster::new(Arc::new(client));
+
+    cluster.initialize_context("test-context", "test-model").await.unwrap();
+    assert!(cluster.get_results("test-context").await.unwrap().is_empty());
+
+    let image = RadiologyImage {
+        image_id: "TEST001".to_string(),
+        data: vec![0, 1, 2, 3],
+        metadata: HashMap::new(),
+        dicom: None,
+    };
+    let result = cluster.submit_image("test-context", image).await.expect("Failed to submit image");
+    assert!((result.confidence_score - 0.95).abs() < f32::EPSILON);
+
+    let results = cluster.get_results("test-context").await.unwrap();
+    assert_eq!(results, vec![result]);
+    assert_eq!(results[0].image_id, "TEST001");
+    assert_eq!(results[0].findings, "Test findings: Normal scan results");
+    assert_eq!(results[0].analysis_date, "2023-01-15T14:30:00Z");
+    assert!(cluster.get_results("other-context").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_submit_to_unknown_context_fails() {
+    let server = start_test_server().await;
+    let client = McpClient::connect(&server.url()).await
+        .expect("Failed to connect to test server");
+    let cluster = RadiologyCluster::new(Arc::new(client));
+
+    let image = RadiologyImage {
+        image_id: "TEST001".to_string(),
+        data: vec![],
+        metadata: HashMap::new(),
+        dicom: None,
+    };
+    let err = cluster.submit_image("missing-context", image).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(ref id) if id == "missing-context"));
+}
+
+// Test server that delays every answer by a random latency and reorders them,
+// so responses come back out of order
+async fn start_concurrent_test_server() -> MockServer {
+    MockServer::builder()
+        .tool("analyze_image", |arguments| Ok(serde_json::json!({
+            "status": "success",
+            "findings": format!("Findings for {}", arguments["image_id"]),
+            "confidence": 0.9
+        })))
+        .latency(Latency::Uniform { min: Duration::ZERO, max: Duration::from_millis(30) })
+        .reorder(4)
+        .start()
+        .await
+        .expect("Failed to start test server")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_submissions_from_spawned_tasks() {
+    let server = start_concurrent_test_server().await;
+    let client = McpClient::connect(&server.url()).await
+        .expect("Failed to connect to test server");
+    let cluster = Arc::new(RadiologyCluster::new(Arc::new(client)));
+
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster.initialize_context("mri", "mri-model").await.unwrap();
+
+    let mut handles = Vec::new();
+    for i in 0..300 {
+        let cluster = cluster.clone();
+        handles.push(tokio::spawn(async move {
+            let context = if i % 2 == 0 { "ct" } else { "mri" };
+            let image = RadiologyImage {
+                image_id: format!("IMG{:03}", i),
+                data: vec![],
+                metadata: HashMap::new(),
+                dicom: None,
+            };
+            cluster.submit_image(context, image).await
+        }));
+    }
+
+    let timeout = Duration::from_secs(30);
+    for handle in handles {
+        let result = tokio::time::timeout(timeout, handle).await
+            .expect("Submission hung")
+            .expect("Submission task panicked")
+            .expect("Submission failed");
+        assert!(result.findings.starts_with("Findings for"));
+    }
+
+    assert_eq!(cluster.get_results("ct").await.unwrap().len(), 150);
+    assert_eq!(cluster.get_results("mri").await.unwrap().len(), 150);
 }
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+use mcp::client::ConnectionState;
+use mcp::connect::{Connector, ReconnectPolicy};
+use mcp::mock::{Fault, FaultRule, MockServer, MockServerBuilder};
+use mcp::protocol::{ToolAnnotations, LATEST_PROTOCOL_VERSION};
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
+
+fn quick_policy() -> ReconnectPolicy {
+    ReconnectPolicy {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+        multiplier: 2.0,
+        jitter: false,
+        max_attempts: Some(5),
+    }
+}
+
+fn read_only() -> ToolAnnotations {
+    ToolAnnotations {
+        read_only_hint: Some(true),
+        ..Default::default()
+    }
+}
+
+async fn analysis_server(configure: impl FnOnce(MockServerBuilder) -> MockServerBuilder) -> MockServer {
+    let builder = MockServer::builder().tool("analyze_image", |arguments| {
+        Ok(json!({
+            "status": "success",
+            "findings": format!("Findings for {}", arguments["image_id"]),
+            "confidence": 0.9
+        }))
+    });
+    configure(builder).start().await.unwrap()
+}
+
+async fn supervised(server: &MockServer) -> McpClient {
+    McpClient::connect_with(&server.url(), Some(quick_policy())).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_idempotent_call_is_replayed_after_reconnect() {
+    let server = analysis_server(|b| {
+        b.annotate("analyze_image", read_only())
+            .fault(FaultRule::on("tools/call", Fault::Disconnect).times(1))
+    })
+    .await;
+    let client = supervised(&server).await;
+
+    // Listing tools teaches the client which ones are safe to repeat
+    client.list_tools().await.unwrap();
+    let result = client.call_tool("analyze_image", json!({ "image_id": "IMG1" })).await.unwrap();
+    assert_eq!(result.structured().unwrap()["findings"], "Findings for \"IMG1\"");
+
+    // The handshake ran again on the new connection before the call was resent
+    assert_eq!(server.requests_for("initialize").len(), 2);
+    assert_eq!(server.requests_for("tools/call").len(), 2);
+    assert!(client.is_connected());
+}
+
+#[tokio::test]
+async fn test_reconnect_relearns_which_tools_to_replay() {
+    let server = analysis_server(|b| {
+        b.annotate("analyze_image", read_only())
+            .fault(FaultRule::on("tools/call", Fault::Disconnect).times(2))
+    })
+    .await;
+    let client = supervised(&server).await;
+
+    // Without a tool listing the first call can't be repeated
+    let err = client.call_tool("analyze_image", json!({ "image_id": "IMG1" })).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)), "unexpected error: {:?}", err);
+
+    // The reconnect listed the tools, so the next dropped call is resent
+    let result = client.call_tool("analyze_image", json!({ "image_id": "IMG2" })).await.unwrap();
+    assert_eq!(result.structured().unwrap()["findings"], "Findings for \"IMG2\"");
+    assert_eq!(server.requests_for("tools/list").len(), 2);
+    assert_eq!(server.requests_for("tools/call").len(), 3);
+}
+
+#[tokio::test]
+async fn test_failed_restores_do_not_start_more_reconnects() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            // The first connection drops on `ping`; every later one refuses the handshake and closes
+            let first = accepted.fetch_add(1, Ordering::SeqCst) == 0;
+            tokio::spawn(async move {
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = socket.next().await {
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    let reply = match request["method"].as_str().unwrap_or_default() {
+                        "initialize" if first => json!({ "jsonrpc": "2.0", "id": request["id"], "result": {
+                            "protocolVersion": LATEST_PROTOCOL_VERSION,
+                            "capabilities": {},
+                            "serverInfo": { "name": "flaky", "version": "1.0" }
+                        }}),
+                        "initialize" => json!({ "jsonrpc": "2.0", "id": request["id"], "error": { "code": -32001, "message": "not yet" } }),
+                        "ping" => break,
+                        _ => continue,
+                    };
+                    socket.send(Message::Text(reply.to_string())).await.unwrap();
+                    if !first {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    let policy = ReconnectPolicy { max_attempts: Some(3), ..quick_policy() };
+    let client = McpClient::connect_with(&url, Some(policy)).await.unwrap();
+    client.initialize().await.unwrap();
+    let err = tokio::time::timeout(Duration::from_secs(5), client.request("ping", None)).await.unwrap().unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)), "unexpected error: {:?}", err);
+    assert_eq!(client.state(), ConnectionState::Closed);
+
+    // One reconnect made its three attempts, and nothing else tried again
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(connections.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_non_idempotent_call_fails_but_connection_recovers() {
+    let server = analysis_server(|b| b.fault(FaultRule::on("tools/call", Fault::Disconnect).times(1))).await;
+    let client = supervised(&server).await;
+
+    let err = client.call_tool("analyze_image", json!({ "image_id": "IMG1" })).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)), "unexpected error: {:?}", err);
+    assert_eq!(server.requests_for("tools/call").len(), 1);
+
+    // Later calls wait for the reconnect instead of failing
+    let result = client.call_tool("analyze_image", json!({ "image_id": "IMG2" })).await.unwrap();
+    assert_eq!(result.structured().unwrap()["findings"], "Findings for \"IMG2\"");
+    assert_eq!(server.requests_for("initialize").len(), 2);
+}
+
+#[tokio::test]
+async fn test_safe_methods_are_replayed() {
+    let server = analysis_server(|b| b.fault(FaultRule::on("ping", Fault::Disconnect).times(1))).await;
+    let client = supervised(&server).await;
+
+    client.request("ping", None).await.unwrap();
+    assert_eq!(server.requests_for("ping").len(), 2);
+}
+
+#[tokio::test]
+async fn test_unsupervised_client_stays_closed() {
+    let server = analysis_server(|b| b.fault(FaultRule::on("ping", Fault::Disconnect).times(1))).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+
+    assert!(client.request("ping", None).await.is_err());
+    assert_eq!(client.state(), ConnectionState::Closed);
+    let err = client.request("ping", None).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)));
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let server = analysis_server(|b| b).await;
+    let url = server.url();
+    let client = Connector::new()
+        .reconnect(ReconnectPolicy { max_attempts: Some(3), ..quick_policy() })
+        .connect(&url)
+        .await
+        .unwrap();
+    client.initialize().await.unwrap();
+
+    let mut state = client.subscribe();
+    drop(server);
+    state.wait_for(|s| *s == ConnectionState::Reconnecting).await.unwrap();
+
+    let closed = tokio::time::timeout(Duration::from_secs(5), state.wait_for(|s| *s == ConnectionState::Closed)).await;
+    assert!(closed.is_ok(), "client never gave up");
+    let err = client.request("ping", None).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Transport(_)));
+}
+
+#[tokio::test]
+async fn test_reconnects_to_restarted_server() {
+    // Reserve a port the restarted server can bind again
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    drop(listener);
+
+    let server = analysis_server(|b| b.bind(&addr)).await;
+    let client = McpClient::connect_with(&server.url(), Some(ReconnectPolicy { max_attempts: None, ..quick_policy() }))
+        .await
+        .unwrap();
+    let cluster = RadiologyCluster::new(Arc::new(client));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    drop(server);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let restarted = analysis_server(|b| b.bind(&addr)).await;
+
+    let image = RadiologyImage {
+        image_id: "IMG1".to_string(),
+        data: vec![],
+        metadata: HashMap::new(),
+        dicom: None,
+    };
+    let result = tokio::time::timeout(Duration::from_secs(5), cluster.submit_image("ct", image))
+        .await
+        .expect("submission hung")
+        .unwrap();
+    assert!(result.findings.starts_with("Findings for"));
+    assert_eq!(restarted.requests_for("initialize").len(), 1);
+}
+
+#[test]
+fn test_reconnect_delay_grows_and_is_capped() {
+    let policy = quick_policy();
+    assert_eq!(policy.delay(0), Duration::from_millis(10));
+    assert_eq!(policy.delay(1), Duration::from_millis(20));
+    assert_eq!(policy.delay(10), Duration::from_millis(50));
+
+    let jittered = ReconnectPolicy { jitter: true, ..policy };
+    for attempt in 0..10 {
+        let capped = Duration::from_millis(10 * 2u64.pow(attempt)).min(Duration::from_millis(50));
+        let delay = jittered.delay(attempt);
+        assert!(delay >= capped / 2 && delay <= capped, "attempt {}: {:?}", attempt, delay);
+    }
+}
@@ -0,0 +1,202 @@
+use mcp::decode::{DecodeError, DefaultResponseDecoder, ResponseDecoder};
+use mcp::findings::{Code, Finding, FindingsReport, Laterality, Measurement, Severity, RADLEX, SNOMED_CT};
+use mcp::RadiologyResult;
+use serde_json::{json, Value};
+
+#[test]
+fn test_decodes_nested_mock_server_shape() {
+    let response = json!({
+        "status": "success",
+        "message": "Analysis completed successfully",
+        "results": {
+            "findings": "Mock radiology findings: No abnormalities detected",
+            "confidence": 0.92
+        }
+    });
+
+    let result = DefaultResponseDecoder.decode("IMG001", &response).unwrap();
+    assert_eq!(result.image_id, "IMG001");
+    assert_eq!(result.findings, "Mock radiology findings: No abnormalities detected");
+    assert!((result.confidence_score - 0.92).abs() < f32::EPSILON);
+    assert!(chrono::DateTime::parse_from_rfc3339(&result.analysis_date).is_ok());
+}
+
+#[test]
+fn test_decodes_flat_test_server_shape() {
+    let response = json!({
+        "status": "success",
+        "findings": "Test findings: Normal scan results",
+        "confidence": 0.95,
+        "analysis_date": "2023-01-15T14:30:00Z"
+    });
+
+    let result = DefaultResponseDecoder.decode("TEST001", &response).unwrap();
+    assert_eq!(result, RadiologyResult {
+        image_id: "TEST001".to_string(),
+        findings: "Test findings: Normal scan results".to_string(),
+        confidence_score: 0.95,
+        analysis_date: "2023-01-15T14:30:00Z".to_string(),
+        study_instance_uid: None,
+        series_instance_uid: None,
+        report: None,
+    });
+}
+
+#[test]
+fn test_accepts_confidence_score_alias() {
+    let response = json!({ "findings": "Normal", "confidence_score": 0.5 });
+    let result = DefaultResponseDecoder.decode("IMG001", &response).unwrap();
+    assert!((result.confidence_score - 0.5).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_rejects_out_of_range_confidence() {
+    for confidence in [1.5, -0.1] {
+        let response = json!({ "findings": "Normal", "confidence": confidence });
+        let err = DefaultResponseDecoder.decode("IMG001", &response).unwrap_err();
+        assert!(matches!(err, DecodeError::ConfidenceOutOfRange(c) if c == confidence));
+    }
+}
+
+#[test]
+fn test_reports_missing_and_mistyped_fields() {
+    let missing_findings = json!({ "status": "success", "results": { "confidence": 0.9 } });
+    assert!(matches!(
+        DefaultResponseDecoder.decode("IMG001", &missing_findings),
+        Err(DecodeError::MissingField("findings"))
+    ));
+
+    let missing_confidence = json!({ "findings": "Normal" });
+    assert!(matches!(
+        DefaultResponseDecoder.decode("IMG001", &missing_confidence),
+        Err(DecodeError::MissingField("confidence"))
+    ));
+
+    let wrong_type = json!({ "findings": "Normal", "confidence": "high" });
+    assert!(matches!(
+        DefaultResponseDecoder.decode("IMG001", &wrong_type),
+        Err(DecodeError::InvalidType { field: "confidence", .. })
+    ));
+}
+
+#[test]
+fn test_closures_act_as_decoders() {
+    let decoder = |image_id: &str, response: &Value| -> Result<RadiologyResult, DecodeError> {
+        Ok(RadiologyResult {
+            image_id: image_id.to_string(),
+            findings: response["report"].as_str().ok_or(DecodeError::MissingField("report"))?.to_string(),
+            confidence_score: 1.0,
+            analysis_date: "2024-01-01T00:00:00Z".to_string(),
+            study_instance_uid: None,
+            series_instance_uid: None,
+            report: None,
+        })
+    };
+
+    let result = decoder.decode("IMG001", &json!({ "report": "Custom" })).unwrap();
+    assert_eq!(result.findings, "Custom");
+}
+
+#[test]
+fn test_decodes_structured_findings() {
+    let response = json!({
+        "status": "success",
+        "results": {
+            "findings": [
+                {
+                    "description": "Solid nodule",
+                    "location": "upper lobe",
+                    "laterality": "R",
+                    "size_mm": 8,
+                    "severity": "mild",
+                    "radlex": "RID3875",
+                    "codes": [{ "system": "SNOMED-CT", "code": "27925004", "display": "Nodule" }],
+                    "confidence": 0.7
+                },
+                {
+                    "description": "Pleural effusion",
+                    "laterality": "bilateral",
+                    "measurements": [{ "value": 2.5, "unit": "cm", "dimension": "depth" }],
+                    "confidence": 0.9
+                },
+                "No pneumothorax"
+            ],
+            "impression": "Indeterminate pulmonary nodule"
+        }
+    });
+
+    let result = DefaultResponseDecoder.decode("IMG001", &response).unwrap();
+    let report = result.report.unwrap();
+    assert_eq!(report.findings[0], Finding {
+        description: "Solid nodule".to_string(),
+        location: Some("upper lobe".to_string()),
+        laterality: Some(Laterality::Right),
+        measurements: vec![Measurement { value: 8.0, unit: "mm".to_string(), dimension: None }],
+        severity: Some(Severity::Mild),
+        codes: vec![
+            Code { scheme: SNOMED_CT.to_string(), code: "27925004".to_string(), meaning: Some("Nodule".to_string()) },
+            Code { scheme: RADLEX.to_string(), code: "RID3875".to_string(), meaning: None },
+        ],
+        confidence: Some(0.7),
+    });
+    assert_eq!(report.findings[2].description, "No pneumothorax");
+    assert_eq!(report.impression.as_deref(), Some("Indeterminate pulmonary nodule"));
+
+    assert_eq!(
+        result.findings,
+        "right upper lobe: Solid nodule (8 mm, mild)\n\
+         bilateral: Pleural effusion (2.5 cm depth)\n\
+         No pneumothorax\n\
+         Impression: Indeterminate pulmonary nodule"
+    );
+    // Without an overall confidence the least confident finding sets it
+    assert!((result.confidence_score - 0.7).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_plain_text_findings_stay_compatible() {
+    let response = json!({ "findings": "Normal chest", "impression": "No acute disease", "confidence": 0.9 });
+    let result = DefaultResponseDecoder.decode("IMG001", &response).unwrap();
+    assert_eq!(result.findings, "Normal chest");
+    assert_eq!(result.report, Some(FindingsReport {
+        findings: Vec::new(),
+        impression: Some("No acute disease".to_string()),
+    }));
+
+    // Results stored before structured findings existed still load, and plain ones serialize as before
+    let stored = json!({
+        "image_id": "IMG001",
+        "findings": "Normal chest",
+        "confidence_score": 0.5,
+        "analysis_date": "2023-01-15T14:30:00Z"
+    });
+    let result: RadiologyResult = serde_json::from_value(stored.clone()).unwrap();
+    assert_eq!(result.report, None);
+    assert_eq!(serde_json::to_value(&result).unwrap(), stored);
+}
+
+#[test]
+fn test_rejects_invalid_findings() {
+    for (finding, reason) in [
+        (json!({ "location": "liver" }), "description"),
+        (json!({ "description": "Cyst", "laterality": "upper" }), "laterality"),
+        (json!({ "description": "Cyst", "severity": "alarming" }), "severity"),
+        (json!({ "description": "Cyst", "confidence": 1.2 }), "confidence"),
+        (json!({ "description": "Cyst", "measurements": [{ "unit": "mm" }] }), "value"),
+        (json!(42), "object"),
+    ] {
+        let response = json!({ "findings": ["Normal", finding], "confidence": 0.9 });
+        let err = DefaultResponseDecoder.decode("IMG001", &response).unwrap_err();
+        assert!(
+            matches!(&err, DecodeError::InvalidFinding { index: 1, reason: r } if r.contains(reason)),
+            "{:?}",
+            err
+        );
+    }
+
+    let response = json!({ "findings": { "description": "Cyst" }, "confidence": 0.9 });
+    assert!(matches!(
+        DefaultResponseDecoder.decode("IMG001", &response),
+        Err(DecodeError::InvalidType { field: "findings", .. })
+    ));
+}
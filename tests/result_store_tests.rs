@@ -0,0 +1,145 @@
+use std::sync::Mutex;
+
+use chrono::{TimeZone, Utc};
+use mcp::store::{MemoryResultStore, ResultQuery, ResultStore, SledResultStore, StoreError};
+use mcp::{AnalysisMessage, RadiologyResult};
+
+fn result(image_id: &str, analysis_date: &str) -> RadiologyResult {
+    RadiologyResult {
+        image_id: image_id.to_string(),
+        findings: format!("Findings for {}", image_id),
+        confidence_score: 0.9,
+        analysis_date: analysis_date.to_string(),
+        study_instance_uid: None,
+        series_instance_uid: None,
+        report: None,
+    }
+}
+
+// Shared assertions so both backends are held to the same behavior
+fn exercise_store(store: &dyn ResultStore) {
+    store.insert("ct", &result("IMG001", "2024-01-10T08:00:00Z")).unwrap();
+    store.insert("ct", &result("IMG002", "2024-02-10T08:00:00Z")).unwrap();
+    store.insert("ct", &result("IMG001", "2024-03-10T08:00:00Z")).unwrap();
+    store.insert("mri", &result("IMG003", "2024-02-10T08:00:00Z")).unwrap();
+
+    let ct = store.query(&ResultQuery::for_context("ct")).unwrap();
+    assert_eq!(ct.len(), 3);
+    assert_eq!(ct[0].image_id, "IMG001");
+    assert_eq!(ct[1].image_id, "IMG002");
+
+    let by_image = store.query(&ResultQuery::for_context("ct").image_id("IMG001")).unwrap();
+    assert_eq!(by_image.len(), 2);
+    assert!(by_image.iter().all(|r| r.image_id == "IMG001"));
+
+    let february = ResultQuery::for_context("ct")
+        .from(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())
+        .until(Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap());
+    let in_range = store.query(&february).unwrap();
+    assert_eq!(in_range, vec![result("IMG002", "2024-02-10T08:00:00Z")]);
+
+    assert!(store.query(&ResultQuery::for_context("unknown")).unwrap().is_empty());
+}
+
+#[test]
+fn test_memory_store_queries() {
+    exercise_store(&MemoryResultStore::new());
+}
+
+#[test]
+fn test_sled_store_queries() {
+    let dir = tempfile::tempdir().unwrap();
+    exercise_store(&SledResultStore::open(dir.path()).unwrap());
+}
+
+#[test]
+fn test_sled_store_survives_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = sled::open(dir.path()).unwrap();
+    {
+        let store = SledResultStore::from_db(db.clone());
+        store.insert("ct", &result("IMG001", "2024-01-10T08:00:00Z")).unwrap();
+    }
+
+    let store = SledResultStore::from_db(db);
+    let results = store.query(&ResultQuery::for_context("ct")).unwrap();
+    assert_eq!(results, vec![result("IMG001", "2024-01-10T08:00:00Z")]);
+}
+
+#[test]
+fn test_unparseable_dates_are_excluded_from_range_queries() {
+    let store = MemoryResultStore::new();
+    store.insert("ct", &result("IMG001", "yesterday")).unwrap();
+
+    assert_eq!(store.query(&ResultQuery::for_context("ct")).unwrap().len(), 1);
+    let bounded = ResultQuery::for_context("ct").from(Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap());
+    assert!(store.query(&bounded).unwrap().is_empty());
+}
+
+fn exercise_insert_once(store: &dyn ResultStore) {
+    assert!(store.insert_once("ct", &result("IMG001", "2024-01-10T08:00:00Z")).unwrap());
+    assert!(!store.insert_once("ct", &result("IMG001", "2024-03-10T08:00:00Z")).unwrap());
+    assert!(store.insert_once("mri", &result("IMG001", "2024-03-10T08:00:00Z")).unwrap());
+
+    let ct = store.query(&ResultQuery::for_context("ct")).unwrap();
+    assert_eq!(ct, vec![result("IMG001", "2024-01-10T08:00:00Z")]);
+}
+
+#[test]
+fn test_insert_once_keeps_the_first_result_per_image() {
+    exercise_insert_once(&MemoryResultStore::new());
+    let dir = tempfile::tempdir().unwrap();
+    exercise_insert_once(&SledResultStore::open(dir.path()).unwrap());
+}
+
+fn exercise_conversations(store: &dyn ResultStore) {
+    let first = [AnalysisMessage::user("Analyze IMG1"), AnalysisMessage::assistant("Nodule")];
+    store.append_messages("ct", "IMG1", &first).unwrap();
+    store.append_messages("ct", "IMG10", &[AnalysisMessage::user("Analyze IMG10")]).unwrap();
+    store.append_messages("ct", "IMG1", &[AnalysisMessage::user("Size?")]).unwrap();
+
+    let conversation = store.conversation("ct", "IMG1").unwrap();
+    assert_eq!(conversation.len(), 3);
+    assert_eq!(conversation[..2], first);
+    assert_eq!(conversation[2].content, "Size?");
+    assert_eq!(store.conversation("ct", "IMG10").unwrap().len(), 1);
+    assert!(store.conversation("mri", "IMG1").unwrap().is_empty());
+}
+
+#[test]
+fn test_conversations_are_kept_per_image() {
+    exercise_conversations(&MemoryResultStore::new());
+    let dir = tempfile::tempdir().unwrap();
+    exercise_conversations(&SledResultStore::open(dir.path()).unwrap());
+}
+
+// A store written before conversations existed: only the required methods
+#[derive(Default)]
+struct ResultsOnlyStore {
+    results: Mutex<Vec<(String, RadiologyResult)>>,
+}
+
+impl ResultStore for ResultsOnlyStore {
+    fn insert(&self, context_id: &str, result: &RadiologyResult) -> Result<(), StoreError> {
+        self.results.lock().unwrap().push((context_id.to_string(), result.clone()));
+        Ok(())
+    }
+
+    fn query(&self, query: &ResultQuery) -> Result<Vec<RadiologyResult>, StoreError> {
+        let results = self.results.lock().unwrap();
+        Ok(results
+            .iter()
+            .filter(|(context_id, result)| *context_id == query.context_id && query.matches(result))
+            .map(|(_, result)| result.clone())
+            .collect())
+    }
+}
+
+#[test]
+fn test_stores_without_conversations_keep_working() {
+    let store = ResultsOnlyStore::default();
+    exercise_store(&store);
+
+    store.append_messages("ct", "IMG001", &[AnalysisMessage::user("Analyze IMG001")]).unwrap();
+    assert!(store.conversation("ct", "IMG001").unwrap().is_empty());
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use mcp::mock::{Fault, FaultRule, MockServer, RpcError};
+use mcp::retry::{Backoff, ConfigError, RetryConfig, RetryPolicy, RetryStrategy};
+use mcp::{McpClient, RadiologyCluster, RadiologyError, RadiologyImage};
+
+fn lost() -> RadiologyError {
+    RadiologyError::Transport("connection closed".into())
+}
+
+fn fixed(millis: u64) -> RetryPolicy {
+    RetryPolicy::new(Backoff::Fixed(Duration::from_millis(millis)))
+}
+
+#[test]
+fn test_backoff_delays() {
+    let fixed: Vec<_> = Backoff::Fixed(Duration::from_millis(5)).delays().take(3).collect();
+    assert_eq!(fixed, vec![Duration::from_millis(5); 3]);
+
+    let exponential = Backoff::Exponential {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+        multiplier: 2.0,
+        jitter: false,
+    };
+    let delays: Vec<_> = exponential.delays().take(5).map(|d| d.as_millis()).collect();
+    assert_eq!(delays, vec![10, 20, 40, 50, 50]);
+
+    let decorrelated = Backoff::DecorrelatedJitter {
+        base: Duration::from_millis(10),
+        max: Duration::from_millis(200),
+    };
+    let mut previous = Duration::from_millis(10);
+    for delay in decorrelated.delays().take(50) {
+        assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(200));
+        assert!(delay <= (previous * 3).min(Duration::from_millis(200)));
+        previous = delay;
+    }
+}
+
+#[test]
+fn test_error_classification() {
+    assert!(lost().is_retryable());
+    assert!(RadiologyError::Timeout(Duration::from_secs(1)).is_retryable());
+    assert!(RadiologyError::Rejected { code: Some(-32603), message: "internal".into() }.is_retryable());
+    assert!(!RadiologyError::Rejected { code: Some(-32602), message: "bad params".into() }.is_retryable());
+    assert!(!RadiologyError::Rejected { code: None, message: "tool failed".into() }.is_retryable());
+    assert!(!RadiologyError::UnknownContext("ct".into()).is_retryable());
+}
+
+#[tokio::test]
+async fn test_run_retries_until_success() {
+    let calls = AtomicU32::new(0);
+    let result = fixed(1)
+        .max_attempts(5)
+        .run(|attempt| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { if attempt < 3 { Err(lost()) } else { Ok(attempt) } }
+        })
+        .await;
+    assert_eq!(result.unwrap(), 3);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_run_gives_up_after_max_attempts() {
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = fixed(1)
+        .max_attempts(4)
+        .run(|_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(lost()) }
+        })
+        .await;
+    assert!(matches!(result, Err(RadiologyError::Transport(_))));
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_non_retryable_errors_are_returned_immediately() {
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = fixed(1)
+        .run(|_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(RadiologyError::UnknownContext("ct".into())) }
+        })
+        .await;
+    assert!(matches!(result, Err(RadiologyError::UnknownContext(_))));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // A custom predicate decides instead
+    let calls = AtomicU32::new(0);
+    let result: Result<(), _> = fixed(1)
+        .retry_if(|err| matches!(err, RadiologyError::UnknownContext(_)))
+        .run(|_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(RadiologyError::UnknownContext("ct".into())) }
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_max_elapsed_stops_retrying() {
+    let calls = AtomicU32::new(0);
+    let started = Instant::now();
+    let result: Result<(), _> = fixed(40)
+        .unlimited_attempts()
+        .max_elapsed(Duration::from_millis(100))
+        .run(|_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(lost()) }
+        })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert!(started.elapsed() < Duration::from_millis(200));
+}
+
+#[test]
+fn test_config_from_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("retry.json");
+    std::fs::write(&path, r#"{ "strategy": "decorrelated_jitter", "max_attempts": null, "initial_delay_ms": 50 }"#).unwrap();
+
+    let config = RetryConfig::from_file(&path).unwrap();
+    assert_eq!(config.strategy, RetryStrategy::DecorrelatedJitter);
+    assert_eq!(config.max_attempts, None);
+    assert_eq!(config.max_delay_ms, RetryConfig::default().max_delay_ms);
+    assert_eq!(
+        config.backoff(),
+        Backoff::DecorrelatedJitter { base: Duration::from_millis(50), max: Duration::from_secs(10) }
+    );
+
+    std::fs::write(&path, "{ not json").unwrap();
+    assert!(matches!(RetryConfig::from_file(&path), Err(ConfigError::Parse(_))));
+}
+
+#[test]
+fn test_config_from_env() {
+    // Each test uses its own prefix, so parallel tests don't see each other's variables
+    std::env::set_var("RETRY_TEST_STRATEGY", "fixed");
+    std::env::set_var("RETRY_TEST_MAX_ATTEMPTS", "7");
+    std::env::set_var("RETRY_TEST_INITIAL_DELAY_MS", "25");
+    std::env::set_var("RETRY_TEST_MAX_ELAPSED_MS", "none");
+
+    let config = RetryConfig::from_env("RETRY_TEST").unwrap();
+    assert_eq!(config.strategy, RetryStrategy::Fixed);
+    assert_eq!(config.max_attempts, Some(7));
+    assert_eq!(config.max_elapsed_ms, None);
+    assert_eq!(config.backoff(), Backoff::Fixed(Duration::from_millis(25)));
+
+    std::env::set_var("RETRY_BAD_MULTIPLIER", "fast");
+    let err = RetryConfig::from_env("RETRY_BAD").unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { ref key, .. } if key == "RETRY_BAD_MULTIPLIER"));
+}
+
+#[test]
+fn test_config_rejects_shrinking_delays() {
+    // A negative multiplier would make every other delay negative
+    std::env::set_var("RETRY_NEGATIVE_MULTIPLIER", "-2");
+    let err = RetryConfig::from_env("RETRY_NEGATIVE").unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { ref key, .. } if key == "RETRY_NEGATIVE_MULTIPLIER"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("retry.json");
+    std::fs::write(&path, r#"{ "multiplier": 0.5 }"#).unwrap();
+    let err = RetryConfig::from_file(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { ref key, .. } if key == "multiplier"));
+
+    std::fs::write(&path, r#"{ "initial_delay_ms": 5000, "max_delay_ms": 100 }"#).unwrap();
+    let err = RetryConfig::from_file(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::InvalidValue { ref key, .. } if key == "initial_delay_ms"));
+
+    // A fixed delay has no maximum to exceed
+    std::fs::write(&path, r#"{ "strategy": "fixed", "initial_delay_ms": 5000, "max_delay_ms": 100 }"#).unwrap();
+    assert!(RetryConfig::from_file(&path).is_ok());
+
+    // Built by hand, an invalid backoff still yields valid delays
+    let backoff = Backoff::Exponential {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(100),
+        multiplier: -2.0,
+        jitter: false,
+    };
+    assert!(backoff.delays().take(4).all(|delay| delay <= Duration::from_millis(100)));
+}
+
+#[tokio::test]
+async fn test_submit_image_retries_server_errors() {
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "status": "success", "findings": "Normal", "confidence": 0.9 }))
+        .fault(FaultRule::on("tools/call", Fault::Error(RpcError::new(-32603, "model crashed"))).times(2))
+        .start()
+        .await
+        .unwrap();
+    let client = Arc::new(McpClient::connect(&server.url()).await.unwrap());
+    let image = RadiologyImage {
+        image_id: "IMG1".to_string(),
+        data: vec![1, 2, 3],
+        metadata: HashMap::new(),
+        dicom: None,
+    };
+
+    // Without a policy the first failure is final
+    let cluster = RadiologyCluster::new(client.clone());
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    let err = cluster.submit_image("ct", image.clone()).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Rejected { code: Some(-32603), .. }));
+
+    let cluster = RadiologyCluster::new(client).with_retry_policy(fixed(5));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    let result = cluster.submit_image("ct", image).await.unwrap();
+    assert_eq!(result.findings, "Normal");
+    assert_eq!(server.requests_for("tools/call").len(), 3);
+}
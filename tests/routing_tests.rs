@@ -0,0 +1,104 @@
+use serde_json::json;
+
+use mcp::backend::DEFAULT_BACKEND;
+use mcp::mock::MockServer;
+use mcp::{RadiologyCluster, RadiologyError};
+
+mod common;
+use common::{connect, image};
+
+// A server whose findings name it, so tests can tell which one answered
+async fn named_server(name: &'static str) -> MockServer {
+    MockServer::builder()
+        .server_info(name, "1.0")
+        .tool("analyze_image", move |arguments| {
+            Ok(json!({
+                "status": "success",
+                "findings": format!("{} read by {}", arguments["model"].as_str().unwrap_or_default(), name),
+                "confidence": 0.9
+            }))
+        })
+        .start()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_contexts_are_routed_to_their_backends() {
+    let ct_server = named_server("ct-server").await;
+    let mri_server = named_server("mri-server").await;
+
+    let cluster = RadiologyCluster::new(connect(&ct_server).await);
+    cluster.register_backend("mri", connect(&mri_server).await);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster.initialize_context_on("mri", "mri-model", "mri").await.unwrap();
+
+    let ct = cluster.submit_image("ct", image("IMG1")).await.unwrap();
+    let mri = cluster.submit_image("mri", image("IMG2")).await.unwrap();
+    assert_eq!(ct.findings, "ct-model read by ct-server");
+    assert_eq!(mri.findings, "mri-model read by mri-server");
+
+    assert_eq!(ct_server.requests_for("tools/call").len(), 1);
+    assert_eq!(mri_server.requests_for("tools/call").len(), 1);
+    assert_eq!(cluster.get_results("mri").await.unwrap(), vec![mri]);
+}
+
+#[tokio::test]
+async fn test_unknown_backend_is_rejected() {
+    let server = named_server("ct-server").await;
+    let cluster = RadiologyCluster::new(connect(&server).await);
+
+    let err = cluster.initialize_context_on("mri", "mri-model", "mri").await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownBackend(ref name) if name == "mri"));
+    let err = cluster.submit_image("mri", image("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(_)));
+}
+
+#[tokio::test]
+async fn test_reregistered_backend_takes_over() {
+    let old_server = named_server("old-server").await;
+    let new_server = named_server("new-server").await;
+    let cluster = RadiologyCluster::new(connect(&old_server).await);
+    cluster.register_backend("mri", connect(&old_server).await);
+    cluster.initialize_context_on("mri", "mri-model", "mri").await.unwrap();
+
+    let before = cluster.submit_image("mri", image("IMG1")).await.unwrap();
+    assert_eq!(before.findings, "mri-model read by old-server");
+
+    cluster.register_backend("mri", connect(&new_server).await);
+    let after = cluster.submit_image("mri", image("IMG2")).await.unwrap();
+    assert_eq!(after.findings, "mri-model read by new-server");
+
+    // A context whose backend went away can't submit
+    cluster.backends().remove("mri");
+    let err = cluster.submit_image("mri", image("IMG3")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownBackend(ref name) if name == "mri"));
+}
+
+#[tokio::test]
+async fn test_missing_tool_names_the_backend() {
+    let ct_server = named_server("ct-server").await;
+    let bare_server = MockServer::builder().start().await.unwrap();
+    let cluster = RadiologyCluster::new(connect(&ct_server).await);
+    cluster.register_backend("bare", connect(&bare_server).await);
+    cluster.initialize_context_on("bare", "model", "bare").await.unwrap();
+
+    let err = cluster.submit_image("bare", image("IMG1")).await.unwrap_err();
+    assert!(err.to_string().contains("protocol error"));
+    assert!(matches!(err, RadiologyError::Protocol { ref message, .. } if message.contains("'bare'")));
+}
+
+#[tokio::test]
+async fn test_registry_lists_backends() {
+    let server = named_server("ct-server").await;
+    let cluster = RadiologyCluster::new(connect(&server).await);
+    cluster.register_backend("mri", connect(&server).await);
+    cluster.register_backend("ct", connect(&server).await);
+
+    assert_eq!(cluster.backends().names(), vec!["ct", DEFAULT_BACKEND, "mri"]);
+    let pool = cluster.backends().get("mri").unwrap();
+    assert_eq!(pool.name(), "mri");
+    let backend = &pool.backends()[0];
+    assert_eq!(backend.name(), "mri");
+    assert_eq!(backend.tools().await.unwrap()[0].name, "analyze_image");
+}
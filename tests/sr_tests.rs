@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use mcp::dicom::{DicomTags, EXPLICIT_VR_LITTLE_ENDIAN};
+use mcp::findings::{Code, Finding, FindingsReport, Laterality, Measurement, Severity};
+use mcp::sr::{ReferencedImage, SrError, StructuredReport, COMPREHENSIVE_SR_STORAGE};
+use mcp::{RadiologyImage, RadiologyResult};
+
+const CT_IMAGE_STORAGE: &str = "1.2.840.10008.5.1.4.1.1.2";
+
+// One element of a parsed dataset; sequences keep their items
+#[derive(Debug)]
+struct Element {
+    tag: (u16, u16),
+    vr: String,
+    value: Vec<u8>,
+    items: Vec<Vec<Element>>,
+}
+
+// Minimal reader for the explicit VR little endian, defined length output of the exporter
+fn read(bytes: &[u8]) -> Vec<Element> {
+    let mut elements = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+        let tag = (u16_at(pos), u16_at(pos + 2));
+        let vr = String::from_utf8(bytes[pos + 4..pos + 6].to_vec()).unwrap();
+        let (len, header) = match vr.as_str() {
+            "OB" | "SQ" | "UC" | "UT" => (u32_at(pos + 8) as usize, 12),
+            _ => (u16_at(pos + 6) as usize, 8),
+        };
+        assert_eq!(len % 2, 0, "odd length value in {:04X?}", tag);
+        let value = bytes[pos + header..pos + header + len].to_vec();
+        pos += header + len;
+
+        let mut items = Vec::new();
+        if vr == "SQ" {
+            let mut at = 0;
+            while at < value.len() {
+                assert_eq!(&value[at..at + 4], &[0xFE, 0xFF, 0x00, 0xE0]);
+                let item_len = u32::from_le_bytes(value[at + 4..at + 8].try_into().unwrap()) as usize;
+                items.push(read(&value[at + 8..at + 8 + item_len]));
+                at += 8 + item_len;
+            }
+        }
+        elements.push(Element { tag, vr, value, items });
+    }
+    assert!(elements.windows(2).all(|pair| pair[0].tag < pair[1].tag), "tags out of order");
+    elements
+}
+
+fn get(elements: &[Element], tag: (u16, u16)) -> &Element {
+    elements.iter().find(|element| element.tag == tag).unwrap_or_else(|| panic!("missing {:04X?}", tag))
+}
+
+fn text(elements: &[Element], tag: (u16, u16)) -> String {
+    String::from_utf8(get(elements, tag).value.clone()).unwrap().trim_end_matches(['\0', ' ']).to_string()
+}
+
+fn items(elements: &[Element], tag: (u16, u16)) -> &[Vec<Element>] {
+    &get(elements, tag).items
+}
+
+// Code value of a content item's concept name
+fn concept(item: &[Element]) -> String {
+    text(&items(item, (0x0040, 0xA043))[0], (0x0008, 0x0100))
+}
+
+// The child content items with the given concept name
+fn children<'a>(item: &'a [Element], code: &str) -> Vec<&'a [Element]> {
+    items(item, (0x0040, 0xA730))
+        .iter()
+        .filter(|child| child.iter().any(|e| e.tag == (0x0040, 0xA043)) && concept(child) == code)
+        .map(Vec::as_slice)
+        .collect()
+}
+
+fn child<'a>(item: &'a [Element], code: &str) -> &'a [Element] {
+    children(item, code).into_iter().next().unwrap_or_else(|| panic!("no {} item", code))
+}
+
+// Part 10 file: the dataset after the preamble, prefix and meta group
+fn dataset(bytes: &[u8]) -> (Vec<Element>, Vec<Element>) {
+    assert_eq!(&bytes[128..132], b"DICM");
+    let elements = read(&bytes[132..]);
+    let (meta, dataset): (Vec<Element>, Vec<Element>) = elements.into_iter().partition(|e| e.tag.0 == 0x0002);
+    (meta, dataset)
+}
+
+fn result(report: Option<FindingsReport>) -> RadiologyResult {
+    RadiologyResult {
+        image_id: "1.2.3.4.5.6".to_string(),
+        findings: "Solid nodule".to_string(),
+        confidence_score: 0.75,
+        analysis_date: "2024-03-01T09:30:15Z".to_string(),
+        study_instance_uid: Some("1.2.3.4".to_string()),
+        series_instance_uid: Some("1.2.3.4.5".to_string()),
+        report,
+    }
+}
+
+#[test]
+fn test_exports_structured_findings_as_measurement_report() {
+    let report = FindingsReport {
+        findings: vec![
+            Finding {
+                description: "Solid nodule".to_string(),
+                location: Some("upper lobe".to_string()),
+                laterality: Some(Laterality::Right),
+                measurements: vec![Measurement { value: 8.0, unit: "mm".to_string(), dimension: None }],
+                severity: Some(Severity::Mild),
+                codes: vec![Code::new("RadLex", "RID3875", Some("nodule"))],
+                confidence: Some(0.7),
+            },
+            Finding {
+                description: "Small effusion".to_string(),
+                ..Finding::default()
+            },
+        ],
+        impression: Some("Indeterminate pulmonary nodule".to_string()),
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("report.dcm");
+    let sr = StructuredReport::new(&result(Some(report)))
+        .reference(ReferencedImage::new(CT_IMAGE_STORAGE, "1.2.3.4.5.6", "1.2.3.4.5"))
+        .reference(ReferencedImage::new(CT_IMAGE_STORAGE, "1.2.3.4.5.7", "1.2.3.4.5"))
+        .reference(ReferencedImage::new(CT_IMAGE_STORAGE, "1.2.3.4.9.1", "1.2.3.4.9"))
+        .patient_id("P12345")
+        .patient_name("Doe^Jane");
+    sr.write(&path).unwrap();
+
+    let (meta, root) = dataset(&std::fs::read(&path).unwrap());
+    assert_eq!(text(&meta, (0x0002, 0x0002)), COMPREHENSIVE_SR_STORAGE);
+    assert_eq!(text(&meta, (0x0002, 0x0003)), sr.uid());
+    assert_eq!(text(&meta, (0x0002, 0x0010)), EXPLICIT_VR_LITTLE_ENDIAN);
+    let meta_len = meta[1..].iter().map(|e| e.value.len() + if e.vr == "OB" { 12 } else { 8 }).sum::<usize>();
+    assert_eq!(get(&meta, (0x0002, 0x0000)).value, (meta_len as u32).to_le_bytes());
+
+    assert_eq!(text(&root, (0x0008, 0x0016)), COMPREHENSIVE_SR_STORAGE);
+    assert_eq!(text(&root, (0x0008, 0x0018)), sr.uid());
+    assert_eq!(text(&root, (0x0008, 0x0060)), "SR");
+    assert_eq!(text(&root, (0x0008, 0x0023)), "20240301");
+    assert_eq!(text(&root, (0x0008, 0x0033)), "093015");
+    assert_eq!(text(&root, (0x0010, 0x0010)), "Doe^Jane");
+    assert_eq!(text(&root, (0x0010, 0x0020)), "P12345");
+    assert_eq!(text(&root, (0x0020, 0x000D)), "1.2.3.4");
+    assert_eq!(text(&root, (0x0040, 0xA493)), "UNVERIFIED");
+    assert_eq!(text(&items(&root, (0x0040, 0xA504))[0], (0x0040, 0xDB00)), "1500");
+
+    // Evidence lists every referenced instance under its study and series
+    let evidence = items(&root, (0x0040, 0xA375));
+    assert_eq!(evidence.len(), 1);
+    assert_eq!(text(&evidence[0], (0x0020, 0x000D)), "1.2.3.4");
+    let series = items(&evidence[0], (0x0008, 0x1115));
+    assert_eq!(series.len(), 2);
+    assert_eq!(text(&series[0], (0x0020, 0x000E)), "1.2.3.4.5");
+    let instances: Vec<String> =
+        items(&series[0], (0x0008, 0x1199)).iter().map(|sop| text(sop, (0x0008, 0x1155))).collect();
+    assert_eq!(instances, vec!["1.2.3.4.5.6", "1.2.3.4.5.7"]);
+
+    // Content tree
+    assert_eq!(text(&root, (0x0040, 0xA040)), "CONTAINER");
+    assert_eq!(concept(&root), "126000");
+    let library = child(child(&root, "111028"), "126200");
+    let images = items(library, (0x0040, 0xA730));
+    assert_eq!(images.len(), 3);
+    assert_eq!(text(&images[2], (0x0040, 0xA040)), "IMAGE");
+    let sop = &items(&images[2], (0x0008, 0x1199))[0];
+    assert_eq!(text(sop, (0x0008, 0x1150)), CT_IMAGE_STORAGE);
+    assert_eq!(text(sop, (0x0008, 0x1155)), "1.2.3.4.9.1");
+
+    let groups = children(child(&root, "126010"), "125007");
+    assert_eq!(groups.len(), 2);
+    let nodule = groups[0];
+    assert_eq!(text(child(nodule, "112039"), (0x0040, 0xA160)), "Finding 1");
+    let codes = children(nodule, "121071");
+    let code = &items(codes[0], (0x0040, 0xA168))[0];
+    assert_eq!(
+        (text(code, (0x0008, 0x0100)), text(code, (0x0008, 0x0102)), text(code, (0x0008, 0x0104))),
+        ("RID3875".to_string(), "RADLEX".to_string(), "nodule".to_string())
+    );
+    assert_eq!(text(codes[1], (0x0040, 0xA160)), "Solid nodule");
+    assert_eq!(text(&items(child(nodule, "272741003"), (0x0040, 0xA168))[0], (0x0008, 0x0100)), "24028007");
+    assert_eq!(text(child(nodule, "363698007"), (0x0040, 0xA160)), "upper lobe");
+    assert_eq!(text(&items(child(nodule, "246112005"), (0x0040, 0xA168))[0], (0x0008, 0x0100)), "255604002");
+    let size = &items(child(nodule, "410668003"), (0x0040, 0xA300))[0];
+    assert_eq!(text(size, (0x0040, 0xA30A)), "8");
+    assert_eq!(text(&items(size, (0x0040, 0x08EA))[0], (0x0008, 0x0100)), "mm");
+    let confidence = &items(child(nodule, "confidence"), (0x0040, 0xA300))[0];
+    assert_eq!(text(confidence, (0x0040, 0xA30A)), "0.7");
+    assert!(children(groups[1], "272741003").is_empty());
+
+    let evaluations = child(&root, "C0034375");
+    assert_eq!(text(child(evaluations, "121073"), (0x0040, 0xA160)), "Indeterminate pulmonary nodule");
+    assert!(children(evaluations, "121070").is_empty());
+}
+
+#[test]
+fn test_exports_plain_text_results_for_dicom_images() {
+    let image = RadiologyImage {
+        image_id: "1.2.3.4.5.6".to_string(),
+        data: vec![1, 2, 3],
+        metadata: HashMap::new(),
+        dicom: Some(DicomTags {
+            study_instance_uid: Some("1.2.3.4".to_string()),
+            series_instance_uid: Some("1.2.3.4.5".to_string()),
+            sop_class_uid: Some(CT_IMAGE_STORAGE.to_string()),
+            sop_instance_uid: Some("1.2.3.4.5.6".to_string()),
+            ..DicomTags::default()
+        }),
+    };
+    let referenced = ReferencedImage::from_image(&image).unwrap();
+    assert_eq!(referenced.study_instance_uid.as_deref(), Some("1.2.3.4"));
+
+    let plain = RadiologyResult {
+        study_instance_uid: None,
+        ..result(None)
+    };
+    let bytes = StructuredReport::new(&plain).reference(referenced).to_bytes().unwrap();
+    let (_, root) = dataset(&bytes);
+    // The study comes from the referenced image when the result doesn't carry one
+    assert_eq!(text(&root, (0x0020, 0x000D)), "1.2.3.4");
+    assert!(children(&root, "126010").is_empty());
+    let evaluations = child(&root, "C0034375");
+    assert_eq!(text(child(evaluations, "121070"), (0x0040, 0xA160)), "Solid nodule");
+    let confidence = &items(child(evaluations, "confidence"), (0x0040, 0xA300))[0];
+    assert_eq!(text(confidence, (0x0040, 0xA30A)), "0.75");
+
+    let untagged = RadiologyImage { dicom: None, ..image };
+    assert_eq!(ReferencedImage::from_image(&untagged), None);
+}
+
+#[test]
+fn test_export_errors() {
+    let image = ReferencedImage::new(CT_IMAGE_STORAGE, "1.2.3.4.5.6", "1.2.3.4.5");
+
+    let err = StructuredReport::new(&result(None)).to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::NoReferencedImages));
+
+    let no_study = RadiologyResult {
+        study_instance_uid: None,
+        ..result(None)
+    };
+    let err = StructuredReport::new(&no_study).reference(image.clone()).to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::MissingStudyInstanceUid));
+
+    // A de-identified pseudonym is not a UID the PACS could match
+    let pseudonym = ReferencedImage::new(CT_IMAGE_STORAGE, "ANON-000001", "1.2.3.4.5");
+    let err = StructuredReport::new(&result(None)).reference(pseudonym).to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::InvalidUid(uid) if uid == "ANON-000001"));
+
+    let report = StructuredReport::new(&result(None)).reference(image.clone()).series_instance_uid("1.02.3");
+    let err = report.to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::InvalidUid(_)));
+
+    // A decimal string can't hold NaN or infinity
+    let unmeasurable = FindingsReport {
+        findings: vec![
+            Finding::default(),
+            Finding {
+                measurements: vec![Measurement { value: f64::NAN, unit: "mm".to_string(), dimension: None }],
+                ..Finding::default()
+            },
+        ],
+        impression: None,
+    };
+    let err = StructuredReport::new(&result(Some(unmeasurable))).reference(image.clone()).to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::NonFiniteValue(value) if value == "a measurement of finding 2"));
+
+    let overconfident = RadiologyResult {
+        confidence_score: f32::INFINITY,
+        ..result(None)
+    };
+    let err = StructuredReport::new(&overconfident).reference(image).to_bytes().unwrap_err();
+    assert!(matches!(err, SrError::NonFiniteValue(value) if value == "confidence score"));
+}
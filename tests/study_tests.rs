@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+
+use mcp::mock::{Fault, FaultRule, MockServer, RpcError};
+use mcp::retry::{Backoff, RetryPolicy};
+use mcp::study::StudySubmission;
+use mcp::transfer::{TransferConfig, UPLOAD_METHOD};
+use mcp::{McpClient, RadiologyCluster, RadiologyImage, RadiologyStudy};
+
+// Server whose analyses report a different confidence on every call
+async fn start_server() -> MockServer {
+    let calls = AtomicU64::new(0);
+    MockServer::builder()
+        .tool("analyze_image", move |_| {
+            let confidence = 0.5 + (calls.fetch_add(1, Ordering::Relaxed) % 5) as f64 / 10.0;
+            Ok(json!({ "findings": "Normal", "confidence": confidence }))
+        })
+        .start()
+        .await
+        .unwrap()
+}
+
+fn image(image_id: &str, study_uid: &str, series_uid: &str) -> RadiologyImage {
+    let mut metadata = HashMap::new();
+    metadata.insert("patient_id".to_string(), "P12345".to_string());
+    metadata.insert("modality".to_string(), "CT".to_string());
+    metadata.insert("study_instance_uid".to_string(), study_uid.to_string());
+    metadata.insert("series_instance_uid".to_string(), series_uid.to_string());
+    RadiologyImage {
+        image_id: image_id.to_string(),
+        data: vec![1, 2, 3],
+        metadata,
+        dicom: None,
+    }
+}
+
+fn sample_images() -> Vec<RadiologyImage> {
+    vec![
+        image("IMG1", "1.1", "1.1.1"),
+        image("IMG2", "1.1", "1.1.2"),
+        image("IMG3", "1.1", "1.1.1"),
+        image("IMG4", "2.2", "2.2.1"),
+    ]
+}
+
+#[test]
+fn test_images_are_grouped_by_study_and_series() {
+    let studies = RadiologyStudy::from_images(sample_images());
+
+    assert_eq!(studies.len(), 2);
+    assert_eq!(studies[0].study_instance_uid, "1.1");
+    assert_eq!(studies[0].patient_id.as_deref(), Some("P12345"));
+    assert_eq!(studies[0].series.len(), 2);
+
+    let first_series = &studies[0].series[0];
+    assert_eq!(first_series.series_instance_uid, "1.1.1");
+    assert_eq!(first_series.modality.as_deref(), Some("CT"));
+    let ids: Vec<_> = first_series.images.iter().map(|i| i.image_id.as_str()).collect();
+    assert_eq!(ids, ["IMG1", "IMG3"]);
+
+    assert_eq!(studies[1].series[0].images[0].image_id, "IMG4");
+}
+
+#[tokio::test]
+async fn test_per_series_submission_sends_one_request_per_series() {
+    let server = start_server().await;
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server.url()).await.unwrap()));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let study = RadiologyStudy::from_images(sample_images()).remove(0);
+    let result = cluster.submit_study("ct", &study, StudySubmission::PerSeries).await.unwrap();
+
+    assert_eq!(result.study_instance_uid, "1.1");
+    assert_eq!(result.series.len(), 2);
+    assert!(result.series.iter().all(|s| s.image_results.is_empty()));
+    assert_eq!(result.series[0].result.image_id, "1.1.1");
+    assert_eq!(result.series[0].result.study_instance_uid.as_deref(), Some("1.1"));
+
+    let calls = server.requests_for("tools/call");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0]["params"]["arguments"]["images"].as_array().unwrap().len(), 2);
+    assert_eq!(calls[1]["params"]["arguments"]["images"].as_array().unwrap().len(), 1);
+
+    let stored = cluster.get_study_results("ct", "1.1").await.unwrap();
+    assert_eq!(stored.len(), 2);
+    assert!(cluster.get_study_results("ct", "2.2").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_fan_out_submission_aggregates_image_results() {
+    let server = start_server().await;
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server.url()).await.unwrap()));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let study = RadiologyStudy::from_images(sample_images()).remove(0);
+    let result = cluster.submit_study("ct", &study, StudySubmission::FanOut).await.unwrap();
+
+    assert_eq!(server.requests_for("tools/call").len(), 3);
+    let series = &result.series[0];
+    assert_eq!(series.image_results.len(), 2);
+    assert!(series.image_results.iter().all(|r| r.series_instance_uid.as_deref() == Some("1.1.1")));
+
+    // The series confidence is the most cautious of its images
+    let lowest = series.image_results.iter().map(|r| r.confidence_score).fold(f32::MAX, f32::min);
+    assert_eq!(series.result.confidence_score, lowest);
+    assert!(series.result.findings.contains("IMG1: Normal"));
+    assert!(series.result.findings.contains("IMG3: Normal"));
+
+    // Three image results plus two series-level results, all linked to the study
+    let stored = cluster.get_study_results("ct", "1.1").await.unwrap();
+    assert_eq!(stored.len(), 5);
+}
+
+#[tokio::test]
+async fn test_per_series_submission_keeps_requests_small_and_retries() {
+    let server = MockServer::builder()
+        .tool_response("analyze_image", json!({ "findings": "Normal", "confidence": 0.9 }))
+        .method(UPLOAD_METHOD, |params| Ok(json!({ "received": params["sequence"] })))
+        .fault(FaultRule::on("tools/call", Fault::Error(RpcError::new(-32603, "model crashed"))).times(1))
+        .start()
+        .await
+        .unwrap();
+    let transfer = TransferConfig {
+        inline_limit: 7,
+        ..TransferConfig::default()
+    };
+    let cluster = RadiologyCluster::new(Arc::new(McpClient::connect(&server.url()).await.unwrap()))
+        .with_transfer_config(transfer)
+        .with_retry_policy(RetryPolicy::new(Backoff::Fixed(Duration::ZERO)).max_attempts(2));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+
+    let images: Vec<_> = (1..=4).map(|i| image(&format!("IMG{}", i), "1.1", "1.1.1")).collect();
+    let study = RadiologyStudy::from_images(images).remove(0);
+    cluster.submit_study("ct", &study, StudySubmission::PerSeries).await.unwrap();
+
+    // The first attempt failed and was retried; within it, two 3-byte images fit the
+    // 7-byte limit together and the rest were uploaded
+    let calls = server.requests_for("tools/call");
+    assert_eq!(calls.len(), 2);
+    let kinds: Vec<&str> = calls[1]["params"]["arguments"]["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|content| content["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["image", "image", "resource", "resource"]);
+}
@@ -0,0 +1,140 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+use mcp::mock::MockServer;
+use mcp::{McpClient, RadiologyCluster, RadiologyError, DEFAULT_TIMEOUT};
+
+mod common;
+use common::{connect, delayed_server, image};
+
+// Every analysis takes `delay` to answer
+async fn slow_server(delay: Duration) -> MockServer {
+    delayed_server(delay, |_| "Normal".to_string()).await
+}
+
+async fn cluster_for(server: &MockServer) -> RadiologyCluster {
+    let cluster = RadiologyCluster::new(connect(server).await);
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster
+}
+
+// Wait briefly for the client's cancellation to reach the server
+async fn cancellations(server: &MockServer) -> Vec<serde_json::Value> {
+    for _ in 0..50 {
+        let cancelled = server.requests_for("notifications/cancelled");
+        if !cancelled.is_empty() {
+            return cancelled;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Vec::new()
+}
+
+#[tokio::test]
+async fn test_context_timeout_cancels_the_request() {
+    let server = slow_server(Duration::from_secs(2)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.set_context_timeout("ct", Some(Duration::from_millis(100))).unwrap();
+
+    let started = Instant::now();
+    let err = cluster.submit_image("ct", image("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Timeout(t) if t == Duration::from_millis(100)), "unexpected error: {:?}", err);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    let call = &server.requests_for("tools/call")[0];
+    let cancelled = cancellations(&server).await;
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!(cancelled[0]["params"]["requestId"], call["id"]);
+    assert!(cluster.get_results("ct").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_per_call_timeout_overrides_context_default() {
+    let server = slow_server(Duration::from_millis(150)).await;
+    let cluster = cluster_for(&server).await;
+    cluster.set_context_timeout("ct", Some(Duration::from_millis(50))).unwrap();
+
+    let result = cluster
+        .submit_image_with_timeout("ct", image("IMG1"), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(result.findings, "Normal");
+    assert!(server.requests_for("notifications/cancelled").is_empty());
+
+    let err = cluster.submit_image("ct", image("IMG2")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Timeout(_)));
+}
+
+#[tokio::test]
+async fn test_cluster_default_applies_without_context_timeout() {
+    let server = slow_server(Duration::from_millis(500)).await;
+    let client = McpClient::connect(&server.url()).await.unwrap();
+    let cluster = RadiologyCluster::new(Arc::new(client)).with_default_timeout(Duration::from_millis(50));
+    cluster.initialize_context("ct", "ct-model").await.unwrap();
+    cluster.initialize_context("mri", "mri-model").await.unwrap();
+    cluster.set_context_timeout("mri", Some(Duration::from_secs(5))).unwrap();
+
+    let err = cluster.submit_image("ct", image("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Timeout(t) if t == Duration::from_millis(50)));
+    assert!(cluster.submit_image("mri", image("IMG2")).await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_hung_backend_times_out_by_default() {
+    // Paused time skips ahead to the next timer whenever the runtime is idle
+    let server = slow_server(Duration::from_secs(3600)).await;
+    let cluster = cluster_for(&server).await;
+
+    let err = cluster.submit_image("ct", image("IMG1")).await.unwrap_err();
+    assert!(matches!(err, RadiologyError::Timeout(t) if t == DEFAULT_TIMEOUT), "unexpected error: {:?}", err);
+
+    let client = McpClient::connect(&server.url()).await.unwrap();
+    let patient = RadiologyCluster::new(Arc::new(client)).without_default_timeout();
+    patient.initialize_context("ct", "ct-model").await.unwrap();
+    let result = patient.submit_image("ct", image("IMG2")).await.unwrap();
+    assert_eq!(result.findings, "Normal");
+}
+
+#[tokio::test]
+async fn test_dropped_future_sends_cancellation() {
+    let server = slow_server(Duration::from_millis(300)).await;
+    let client = Arc::new(McpClient::connect(&server.url()).await.unwrap());
+    client.initialize().await.unwrap();
+
+    let call = {
+        let client = client.clone();
+        tokio::spawn(async move { client.call_tool("analyze_image", json!({})).await })
+    };
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    call.abort();
+
+    let cancelled = cancellations(&server).await;
+    assert_eq!(cancelled.len(), 1);
+    assert_eq!(cancelled[0]["params"]["requestId"], server.requests_for("tools/call")[0]["id"]);
+
+    // The server drops the cancelled answer and the connection keeps working
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert!(client.is_connected());
+    client.request("ping", None).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_completed_requests_are_not_cancelled() {
+    let server = slow_server(Duration::ZERO).await;
+    let cluster = cluster_for(&server).await;
+    cluster.set_context_timeout("ct", Some(Duration::from_secs(5))).unwrap();
+
+    cluster.submit_image("ct", image("IMG1")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(server.requests_for("notifications/cancelled").is_empty());
+}
+
+#[tokio::test]
+async fn test_timeout_for_unknown_context_fails() {
+    let server = slow_server(Duration::ZERO).await;
+    let cluster = cluster_for(&server).await;
+    let err = cluster.set_context_timeout("missing", Some(Duration::from_secs(1))).unwrap_err();
+    assert!(matches!(err, RadiologyError::UnknownContext(ref id) if id == "missing"));
+}